@@ -0,0 +1,213 @@
+//! Capture over the Chrome DevTools Protocol with chromiumoxide, which
+//! launches Chrome itself instead of going through chromedriver.
+
+use crate::config::CaptureConfig;
+use crate::error::{CaptureError, Result};
+use crate::human::PointerStep;
+use crate::PageRect;
+use chromiumoxide::browser::{Browser, BrowserConfig};
+use chromiumoxide::cdp::browser_protocol::emulation::SetDeviceMetricsOverrideParams;
+use chromiumoxide::cdp::browser_protocol::input::{DispatchMouseEventParams, DispatchMouseEventType, MouseButton};
+use chromiumoxide::cdp::browser_protocol::page::CaptureScreenshotFormat;
+use chromiumoxide::handler::viewport::Viewport;
+use chromiumoxide::layout::Point;
+use chromiumoxide::page::{Page, ScreenshotParams};
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+
+/// A Chrome launched over CDP with a game open in its only page.
+pub struct CdpSession {
+    browser: Browser,
+    page: Page,
+    lock_zoom: bool,
+    // drives the CDP connection; ends when the browser closes
+    handler: JoinHandle<()>,
+}
+
+impl CdpSession {
+    pub async fn start(config: &CaptureConfig) -> Result<Self> {
+        let mut builder = BrowserConfig::builder().viewport(None);
+        if !config.headless {
+            builder = builder.with_head();
+        }
+        if let Some(viewport) = config.viewport {
+            builder = builder
+                .window_size(viewport.width, viewport.height)
+                .viewport(Viewport {
+                    width: viewport.width,
+                    height: viewport.height,
+                    ..Viewport::default()
+                });
+        }
+        if let Some([x, y]) = config.window_position {
+            builder = builder.arg(format!("--window-position={x},{y}"));
+        }
+        if config.lock_zoom {
+            builder = builder.arg("--force-device-scale-factor=1");
+        }
+        if let Some(path) = &config.browser_path {
+            builder = builder.chrome_executable(path);
+        }
+        let browser_config = builder.build().map_err(CaptureError::CdpLaunch)?;
+
+        let (browser, mut handler) = Browser::launch(browser_config).await?;
+        let handler = tokio::spawn(async move {
+            while let Some(event) = handler.next().await {
+                if let Err(e) = event {
+                    debug!(error = %e, "CDP handler stopped");
+                    break;
+                }
+            }
+        });
+
+        info!(url = config.url, "opening game over CDP");
+        let page = browser.new_page(config.url.as_str()).await?;
+        if config.lock_zoom {
+            // keeps the viewport set above; 0 leaves the window's own size
+            let (width, height) = config.viewport.map_or((0, 0), |viewport| (viewport.width, viewport.height));
+            page.execute(SetDeviceMetricsOverrideParams::new(width, height, 1.0, false)).await?;
+        }
+        Ok(Self {
+            browser,
+            page,
+            lock_zoom: config.lock_zoom,
+            handler,
+        })
+    }
+
+    /// Makes the page area exactly `viewport` by emulating a screen of that size.
+    pub async fn set_viewport(&self, viewport: crate::config::Viewport) -> Result<()> {
+        // a scale factor of 0 leaves the browser's own
+        let scale = if self.lock_zoom { 1.0 } else { 0.0 };
+        let params = SetDeviceMetricsOverrideParams::new(viewport.width, viewport.height, scale, false);
+        self.page.execute(params).await?;
+        Ok(())
+    }
+
+    pub async fn click_element(&self, selector: &str) -> Result<()> {
+        self.page.find_element(selector).await?.click().await?;
+        Ok(())
+    }
+
+    /// PNG of the element matching `selector`, or of the whole page.
+    pub async fn screenshot(&self, selector: Option<&str>) -> Result<Vec<u8>> {
+        if let Some(selector) = selector {
+            let element = self.page.find_element(selector).await?;
+            return Ok(element.screenshot(CaptureScreenshotFormat::Png).await?);
+        }
+        let params = ScreenshotParams::builder().format(CaptureScreenshotFormat::Png).build();
+        Ok(self.page.screenshot(params).await?)
+    }
+
+    /// Where the element matching `selector`, or else the viewport, is on the page.
+    pub async fn rect(&self, selector: Option<&str>) -> Result<PageRect> {
+        if let Some(selector) = selector {
+            let argument = serde_json::to_string(selector).expect("strings always serialize");
+            let rect = self
+                .page
+                .evaluate(format!("(function () {{ {} }})({argument})", crate::RECT_JS))
+                .await?
+                .into_value::<serde_json::Value>()
+                .map_err(chromiumoxide::error::CdpError::from)?;
+            return crate::page_rect(rect, selector);
+        }
+        let (width, height) = self
+            .page
+            .evaluate("[window.innerWidth, window.innerHeight]")
+            .await?
+            .into_value::<(f64, f64)>()
+            .map_err(chromiumoxide::error::CdpError::from)?;
+        Ok(PageRect { x: 0.0, y: 0.0, width, height })
+    }
+
+    pub async fn device_pixel_ratio(&self) -> Result<f64> {
+        let ratio = self
+            .page
+            .evaluate("window.devicePixelRatio")
+            .await?
+            .into_value::<f64>()
+            .map_err(chromiumoxide::error::CdpError::from)?;
+        Ok(ratio)
+    }
+
+    pub async fn is_shown(&self, selector: &str) -> Result<bool> {
+        let selector = serde_json::to_string(selector).expect("strings always serialize");
+        let shown = self
+            .page
+            .evaluate(format!("(function () {{ {} }})({selector})", crate::IS_SHOWN_JS))
+            .await?
+            .into_value::<bool>()
+            .map_err(chromiumoxide::error::CdpError::from)?;
+        Ok(shown)
+    }
+
+    /// Runs `script` as the body of a function and returns its result.
+    pub async fn execute(&self, script: &str) -> Result<serde_json::Value> {
+        let value = self
+            .page
+            .evaluate(format!("(function () {{ {script} }})()"))
+            .await?
+            .into_value::<serde_json::Value>()
+            .map_err(chromiumoxide::error::CdpError::from)?;
+        Ok(value)
+    }
+
+    pub async fn click(&self, x: f64, y: f64) -> Result<()> {
+        self.page.click(Point { x, y }).await?;
+        Ok(())
+    }
+
+    /// Presses at `from`, moves to `to` in small steps over `duration`, and releases.
+    pub async fn drag(&self, from: Point, to: Point, duration: Duration) -> Result<()> {
+        // roughly one step per frame at 60fps
+        let steps = (duration.as_millis() / 16).max(1) as u32;
+
+        self.mouse(DispatchMouseEventType::MousePressed, from).await?;
+        for step in 1..=steps {
+            let t = f64::from(step) / f64::from(steps);
+            let point = Point {
+                x: from.x + (to.x - from.x) * t,
+                y: from.y + (to.y - from.y) * t,
+            };
+            self.mouse(DispatchMouseEventType::MouseMoved, point).await?;
+            tokio::time::sleep(duration / steps).await;
+        }
+        self.mouse(DispatchMouseEventType::MouseReleased, to).await
+    }
+
+    /// Carries out a pointer gesture step by step, starting wherever the
+    /// first move goes.
+    pub(crate) async fn perform(&self, steps: &[PointerStep]) -> Result<()> {
+        let mut at = Point { x: 0.0, y: 0.0 };
+        for step in steps {
+            match *step {
+                PointerStep::MoveTo { x, y, duration } => {
+                    at = Point { x, y };
+                    self.mouse(DispatchMouseEventType::MouseMoved, at).await?;
+                    tokio::time::sleep(duration).await;
+                }
+                PointerStep::Down => self.mouse(DispatchMouseEventType::MousePressed, at).await?,
+                PointerStep::Up => self.mouse(DispatchMouseEventType::MouseReleased, at).await?,
+                PointerStep::Pause(duration) => tokio::time::sleep(duration).await,
+            }
+        }
+        Ok(())
+    }
+
+    async fn mouse(&self, kind: DispatchMouseEventType, point: Point) -> Result<()> {
+        let mut params = DispatchMouseEventParams::new(kind, point.x, point.y);
+        params.button = Some(MouseButton::Left);
+        params.click_count = Some(1);
+        self.page.execute(params).await?;
+        Ok(())
+    }
+
+    pub async fn close(mut self) -> Result<()> {
+        self.browser.close().await?;
+        self.browser.wait().await.map_err(CaptureError::Driver)?;
+        self.handler.abort();
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+//! Finds the installed Chrome's version and fetches the matching chromedriver
+//! from Chrome for Testing, caching it per version. Only used with
+//! `manage_driver` enabled, as Chrome for Testing publishes no checksums, so
+//! the download can't be verified beyond HTTPS.
+
+use crate::error::{CaptureError, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use tokio::process::Command;
+use tracing::{debug, info};
+
+/// Chrome for Testing's index of the newest release of every build (e.g. `126.0.6478`).
+pub const VERSIONS_URL: &str =
+    "https://googlechromelabs.github.io/chrome-for-testing/latest-patch-versions-per-build-with-downloads.json";
+
+#[derive(Debug, Deserialize)]
+struct Versions {
+    builds: HashMap<String, Build>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Build {
+    version: String,
+    downloads: HashMap<String, Vec<Download>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Download {
+    platform: String,
+    url: String,
+}
+
+/// Version of the locally installed Chrome, e.g. `126.0.6478.126`.
+pub async fn installed_chrome_version() -> Option<String> {
+    for (program, args) in version_commands() {
+        let Ok(output) = Command::new(program).args(args).output().await else {
+            continue;
+        };
+        if let Some(version) = parse_version(&String::from_utf8_lossy(&output.stdout)) {
+            debug!(program, version, "found chrome");
+            return Some(version);
+        }
+    }
+    None
+}
+
+fn version_commands() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(windows) {
+        vec![(
+            "reg",
+            &["query", r"HKEY_CURRENT_USER\Software\Google\Chrome\BLBeacon", "/v", "version"],
+        )]
+    } else if cfg!(target_os = "macos") {
+        vec![("/Applications/Google Chrome.app/Contents/MacOS/Google Chrome", &["--version"])]
+    } else {
+        vec![
+            ("google-chrome", &["--version"]),
+            ("google-chrome-stable", &["--version"]),
+            ("chromium", &["--version"]),
+            ("chromium-browser", &["--version"]),
+        ]
+    }
+}
+
+// the first whitespace-separated word shaped like 126.0.6478.126
+fn parse_version(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .find(|word| word.split('.').count() == 4 && word.split('.').all(|part| part.parse::<u32>().is_ok()))
+        .map(str::to_string)
+}
+
+/// Chrome for Testing's name for the platform this was built for.
+pub fn platform() -> Option<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => Some("linux64"),
+        ("macos", "aarch64") => Some("mac-arm64"),
+        ("macos", "x86_64") => Some("mac-x64"),
+        ("windows", "x86_64") => Some("win64"),
+        ("windows", "x86") => Some("win32"),
+        _ => None,
+    }
+}
+
+/// Where downloaded chromedrivers are kept, one directory per version.
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("solitaire-ocr")
+        .join("chromedriver")
+}
+
+fn executable_name() -> &'static str {
+    if cfg!(windows) {
+        "chromedriver.exe"
+    } else {
+        "chromedriver"
+    }
+}
+
+/// Path of a chromedriver matching the installed Chrome, downloading it into
+/// [`cache_dir`] the first time that Chrome version is seen.
+pub async fn ensure_chromedriver() -> Result<PathBuf> {
+    let chrome_version = installed_chrome_version().await.ok_or(CaptureError::ChromeNotFound)?;
+    let platform = platform().ok_or_else(|| CaptureError::NoDriverForVersion {
+        version: chrome_version.clone(),
+        platform: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+    })?;
+
+    // drivers are released per build, so 126.0.6478.126 is served by the newest 126.0.6478.x
+    let build = chrome_version.rsplit_once('.').map_or(chrome_version.as_str(), |(build, _)| build);
+    let build_dir = cache_dir().join(build);
+    let path = build_dir.join(executable_name());
+    if path.is_file() {
+        debug!(path = %path.display(), "using cached chromedriver");
+        return Ok(path);
+    }
+
+    let versions: Versions = get(VERSIONS_URL).await?.json().await.map_err(|source| download_error(VERSIONS_URL, source))?;
+    let url = versions
+        .builds
+        .get(build)
+        .and_then(|build| Some((build, build.downloads.get("chromedriver")?)))
+        .and_then(|(build, downloads)| {
+            let download = downloads.iter().find(|download| download.platform == platform)?;
+            debug!(version = build.version, "matching chromedriver");
+            Some(download.url.clone())
+        })
+        .ok_or_else(|| CaptureError::NoDriverForVersion {
+            version: chrome_version.clone(),
+            platform: platform.to_string(),
+        })?;
+
+    info!(chrome = chrome_version, url, "downloading matching chromedriver");
+    let archive = get(&url).await?.bytes().await.map_err(|source| download_error(&url, source))?;
+    extract_driver(&archive, &build_dir, &path)?;
+
+    Ok(path)
+}
+
+async fn get(url: &str) -> Result<reqwest::Response> {
+    reqwest::get(url)
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|source| download_error(url, source))
+}
+
+fn download_error(url: &str, source: reqwest::Error) -> CaptureError {
+    CaptureError::DriverDownload {
+        url: url.to_string(),
+        source: Box::new(source),
+    }
+}
+
+// the archive holds chromedriver-<platform>/chromedriver next to license files
+fn extract_driver(archive: &[u8], dir: &Path, path: &Path) -> Result<()> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(archive)).map_err(CaptureError::DriverArchive)?;
+    let index = (0..zip.len())
+        .find(|&i| {
+            zip.by_index(i)
+                .is_ok_and(|entry| entry.name().rsplit('/').next() == Some(executable_name()))
+        })
+        .ok_or(CaptureError::DriverArchive(zip::result::ZipError::FileNotFound))?;
+
+    let mut binary = Vec::new();
+    zip.by_index(index)
+        .map_err(CaptureError::DriverArchive)?
+        .read_to_end(&mut binary)
+        .map_err(|source| io_error(path, source))?;
+
+    fs::create_dir_all(dir).map_err(|source| io_error(dir, source))?;
+    // write next to the final path and rename, so a partial download is never picked up as cached
+    let partial = path.with_extension("partial");
+    fs::write(&partial, binary).map_err(|source| io_error(&partial, source))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&partial, fs::Permissions::from_mode(0o755)).map_err(|source| io_error(&partial, source))?;
+    }
+    fs::rename(&partial, path).map_err(|source| io_error(path, source))
+}
+
+fn io_error(path: &Path, source: io::Error) -> CaptureError {
+    CaptureError::Io {
+        path: path.into(),
+        source,
+    }
+}
@@ -0,0 +1,434 @@
+use crate::human::HumanConfig;
+use crate::retry::RetryConfig;
+use crate::settle::SettleConfig;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    pub backend: Backend,
+    pub browser: Browser,
+    /// Page the game is played on.
+    pub url: String,
+    /// CSS selector of an element that shows once the page has loaded,
+    /// waited for before starting a game. Without one, the page counts as
+    /// loaded as soon as the start screen's button can be clicked.
+    pub wait_for: Option<String>,
+    /// JavaScript run in the page before every screenshot, e.g. to hide
+    /// banners that would cover the game.
+    pub pre_capture_script: Option<String>,
+    /// Which new game to start.
+    pub difficulty: Difficulty,
+    /// Chrome executable for the CDP backend [default: found automatically].
+    pub browser_path: Option<String>,
+    /// Driver executable to launch instead of looking one up.
+    pub driver_path: Option<String>,
+    /// For Chrome, download and cache a chromedriver matching the installed
+    /// version rather than relying on the one on `PATH`. Off by default, as
+    /// Chrome for Testing publishes no checksums to verify the download with.
+    pub manage_driver: bool,
+    /// Run the browser without a visible window.
+    pub headless: bool,
+    /// Size of the page area, so screenshots have the same resolution on
+    /// every machine. Written as `"1920x1080"`.
+    pub viewport: Option<Viewport>,
+    /// Screen position of the window's top left corner, as `[x, y]`.
+    pub window_position: Option<[u32; 2]>,
+    /// Start the browser at 100% zoom with a device scale factor of 1, so
+    /// neither the user's zoom nor display scaling changes what's captured.
+    /// Chrome has no launch flag for zoom, so its scale factor is pinned
+    /// through DevTools once the page is open, and starting fails if a zoom
+    /// Chrome remembers for the site still scales the page.
+    pub lock_zoom: bool,
+    /// Size in pixels every screenshot must have once scaled to CSS pixels,
+    /// usually the resolution the profile's templates were made at. Any other
+    /// size is an error rather than a silent miss in detection.
+    pub expected_size: Option<Viewport>,
+    /// CSS selector of the element to screenshot, so the page's toolbar and
+    /// banners stay out of the image. Empty screenshots the whole page.
+    pub canvas_selector: String,
+    /// Scale screenshots from a HiDPI display back down to CSS pixels, the
+    /// size templates are made at.
+    pub normalize_pixel_ratio: bool,
+    /// How long a drag takes to move from its start to its end, since the
+    /// game ignores drags that jump straight to the drop point.
+    pub drag_duration_ms: u64,
+    /// Randomized pointer paths and timing for played moves.
+    pub human: HumanConfig,
+    /// JavaScript run in the page to read the game state straight from the
+    /// game, as the body of a function returning `{draw_pile, game_piles,
+    /// discard_pile}` in the same form as detected states, or `null` if it
+    /// isn't available.
+    pub state_script: Option<String>,
+    /// Selectors of the game's own buttons.
+    pub controls: ControlsConfig,
+    /// Address the WebDriver server is started on and connected to. Its port
+    /// is passed to the driver with `--port`. Defaults to localhost on
+    /// `webdriver_port`.
+    pub webdriver_url: Option<String>,
+    /// Launch a local driver for `webdriver_url`. Turn off to use a server
+    /// that's already running, e.g. a Selenium Grid hub.
+    pub start_driver: bool,
+    /// Merged over the generated WebDriver capabilities, e.g. for options a
+    /// grid needs. Nested objects are merged key by key.
+    pub extra_capabilities: serde_json::Map<String, serde_json::Value>,
+    /// Port to start the driver on when no `webdriver_url` is given. A free
+    /// one is picked if neither is set.
+    pub webdriver_port: Option<u16>,
+    /// WebDriver server to attach to instead of starting one. The page its
+    /// browser shows is captured as is.
+    pub attach: Option<String>,
+    /// `host:port` of a Chrome started with `--remote-debugging-port`, for the
+    /// session to take over instead of launching a new browser.
+    pub debugger_address: Option<String>,
+    /// Applied to connecting to the driver and waiting for the game to load.
+    pub retry: RetryConfig,
+    /// Waiting for a new game to finish dealing before capturing it.
+    pub settle: SettleConfig,
+}
+
+/// CSS selectors of the game's buttons and dialogs, for backtracking,
+/// restarting and noticing finished games.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlsConfig {
+    pub undo: String,
+    /// Leads back to the start screen, where a difficulty is picked.
+    pub new_game: String,
+    /// Shown once every card is on the foundations.
+    pub win_dialog: String,
+    /// Shown when no moves are left, for versions of the game that have one.
+    pub lose_dialog: Option<String>,
+    /// Closes whichever end-of-game dialog is open.
+    pub dismiss_dialog: String,
+}
+
+impl Default for ControlsConfig {
+    fn default() -> Self {
+        Self {
+            undo: "#solitaire-undo-button".to_string(),
+            new_game: "#solitaire-new-game-button".to_string(),
+            win_dialog: "#solitaire-win-dialog".to_string(),
+            lose_dialog: None,
+            dismiss_dialog: "#solitaire-win-dialog button".to_string(),
+        }
+    }
+}
+
+/// How a game ended, going by the dialog it shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameOutcome {
+    Won,
+    Lost,
+}
+
+impl fmt::Display for GameOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GameOutcome::Won => "won",
+            GameOutcome::Lost => "lost",
+        })
+    }
+}
+
+/// The game draws everything onto a single canvas, which `canvas_selector`
+/// can be set to. Off by default, since the layout and the selftest fixture
+/// are calibrated on whole-page screenshots.
+pub const DEFAULT_CANVAS_SELECTOR: &str = "canvas";
+
+impl CaptureConfig {
+    /// WebDriver capabilities requesting the configured browser and window mode.
+    pub fn capabilities(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut args = Vec::new();
+        let mut prefs = serde_json::Map::new();
+        match self.browser {
+            Browser::Chrome => {
+                if self.headless {
+                    args.push("--headless=new".to_string());
+                }
+                if let Some(Viewport { width, height }) = self.viewport {
+                    args.push(format!("--window-size={width},{height}"));
+                }
+                if let Some([x, y]) = self.window_position {
+                    args.push(format!("--window-position={x},{y}"));
+                }
+                // zoom can't be set at launch, BrowserSession::start checks it
+                if self.lock_zoom {
+                    args.push("--force-device-scale-factor=1".to_string());
+                }
+            }
+            Browser::Firefox => {
+                if self.headless {
+                    args.push("-headless".to_string());
+                }
+                if let Some(Viewport { width, height }) = self.viewport {
+                    args.extend([format!("--width={width}"), format!("--height={height}")]);
+                }
+                if self.lock_zoom {
+                    prefs.insert("layout.css.devPixelsPerPx".to_string(), "1.0".into());
+                    prefs.insert("browser.zoom.siteSpecific".to_string(), false.into());
+                }
+            }
+        }
+
+        let options_key = match self.browser {
+            Browser::Chrome => "goog:chromeOptions",
+            Browser::Firefox => "moz:firefoxOptions",
+        };
+        let options = match (&self.debugger_address, self.browser) {
+            // launch arguments don't apply to a browser that's already running
+            (Some(address), Browser::Chrome) => serde_json::json!({ "debuggerAddress": address }),
+            _ => serde_json::json!({ "args": args, "prefs": prefs }),
+        };
+
+        let mut capabilities = serde_json::Map::new();
+        capabilities.insert("browserName".to_string(), self.browser.name().into());
+        capabilities.insert(options_key.to_string(), options);
+        merge_json(&mut capabilities, &self.extra_capabilities);
+        capabilities
+    }
+}
+
+// recursively overwrite `base` with `overlay`, keeping keys the overlay doesn't mention
+fn merge_json(base: &mut serde_json::Map<String, serde_json::Value>, overlay: &serde_json::Map<String, serde_json::Value>) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(serde_json::Value::Object(base_object)), serde_json::Value::Object(overlay_object)) => {
+                merge_json(base_object, overlay_object);
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            backend: Backend::default(),
+            browser: Browser::default(),
+            url: crate::SOLITAIRE_URL.to_string(),
+            wait_for: None,
+            pre_capture_script: None,
+            difficulty: Difficulty::default(),
+            browser_path: None,
+            driver_path: None,
+            manage_driver: false,
+            headless: false,
+            viewport: None,
+            window_position: None,
+            lock_zoom: true,
+            expected_size: None,
+            webdriver_url: None,
+            start_driver: true,
+            extra_capabilities: serde_json::Map::new(),
+            webdriver_port: None,
+            canvas_selector: String::new(),
+            normalize_pixel_ratio: true,
+            drag_duration_ms: 300,
+            human: HumanConfig::default(),
+            state_script: None,
+            controls: ControlsConfig::default(),
+            attach: None,
+            debugger_address: None,
+            retry: RetryConfig::default(),
+            settle: SettleConfig::default(),
+        }
+    }
+}
+
+/// How the browser is driven.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Through chromedriver or geckodriver.
+    #[default]
+    WebDriver,
+    /// Chrome over the DevTools Protocol, without a driver. Needs the `cdp` feature.
+    Cdp,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "webdriver" => Ok(Backend::WebDriver),
+            "cdp" => Ok(Backend::Cdp),
+            _ => Err(format!("unknown capture backend {s:?}, expected webdriver or cdp")),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Backend::WebDriver => "webdriver",
+            Backend::Cdp => "cdp",
+        })
+    }
+}
+
+/// Game mode picked on the start screen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    /// Draw one card at a time.
+    #[default]
+    Easy,
+    /// Draw three at a time, fanned out in the waste.
+    Hard,
+}
+
+impl Difficulty {
+    /// Id of the start screen button for this mode.
+    pub fn button_id(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "solitaire-easy-button",
+            Difficulty::Hard => "solitaire-hard-button",
+        }
+    }
+
+    /// Cards dealt from the stock to the waste at a time.
+    pub fn draw_count(self) -> usize {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Hard => 3,
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Hard => "hard",
+        })
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Difficulty::Easy),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!("unknown difficulty {s:?}, expected easy or hard")),
+        }
+    }
+}
+
+/// Browser to run the game in, each driven by its own WebDriver server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Browser {
+    #[default]
+    Chrome,
+    Firefox,
+}
+
+impl Browser {
+    /// Executable of the WebDriver server for this browser.
+    pub fn driver(self) -> &'static str {
+        match self {
+            Browser::Chrome => "chromedriver",
+            Browser::Firefox => "geckodriver",
+        }
+    }
+
+    /// W3C `browserName` capability.
+    pub fn name(self) -> &'static str {
+        match self {
+            Browser::Chrome => "chrome",
+            Browser::Firefox => "firefox",
+        }
+    }
+}
+
+impl fmt::Display for Browser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Browser {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chrome" => Ok(Browser::Chrome),
+            "firefox" => Ok(Browser::Firefox),
+            _ => Err(format!("unknown browser {s:?}, expected chrome or firefox")),
+        }
+    }
+}
+
+/// Width and height of the page area in CSS pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Viewport {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl fmt::Display for Viewport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl FromStr for Viewport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid viewport {s:?}, expected WIDTHxHEIGHT like 1920x1080");
+        let (width, height) = s.split_once('x').ok_or_else(invalid)?;
+        Ok(Self {
+            width: width.trim().parse().map_err(|_| invalid())?,
+            height: height.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl TryFrom<String> for Viewport {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Viewport> for String {
+    fn from(viewport: Viewport) -> Self {
+        viewport.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn viewport_parses_width_by_height() {
+        assert_eq!("1920x1080".parse(), Ok(Viewport { width: 1920, height: 1080 }));
+        assert_eq!(" 1280 x 720 ".parse(), Ok(Viewport { width: 1280, height: 720 }));
+        assert_eq!("0x0".parse(), Ok(Viewport { width: 0, height: 0 }));
+    }
+
+    #[test]
+    fn viewport_rejects_anything_else() {
+        for s in ["", "x", "1920", "1920x", "x1080", "1920X1080", "1920x1080x2", "-1x1080", "1920.5x1080", "axb"] {
+            assert!(s.parse::<Viewport>().is_err(), "{s:?} parsed");
+        }
+    }
+
+    #[test]
+    fn viewport_round_trips_through_its_display() {
+        let viewport = Viewport { width: 1366, height: 768 };
+        assert_eq!(viewport.to_string().parse(), Ok(viewport));
+    }
+}
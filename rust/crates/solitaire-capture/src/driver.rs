@@ -0,0 +1,68 @@
+use crate::error::{CaptureError, Result};
+use std::path::Path;
+use tokio::process::{Child, Command};
+use tracing::{debug, warn};
+
+/// A WebDriver server this process launched, stopped again when dropped.
+///
+/// The driver runs in its own process group with the browser it starts, so
+/// both go away together whether the session is closed, a run fails partway
+/// or the task holding it panics. Without this, every failed run left a
+/// driver and a browser behind.
+#[derive(Debug)]
+pub struct DriverProcess {
+    child: Child,
+}
+
+impl DriverProcess {
+    /// Spawns the WebDriver server at `program` listening on `port`.
+    pub fn spawn(program: &Path, port: u16) -> Result<Self> {
+        let mut command = Command::new(program);
+        command.arg(format!("--port={port}")).kill_on_drop(true);
+        #[cfg(unix)]
+        command.process_group(0);
+        let child = command.spawn().map_err(CaptureError::Driver)?;
+        debug!(pid = child.id(), program = %program.display(), "started driver");
+        Ok(Self { child })
+    }
+
+    pub fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    /// Kills the driver and its browser and waits for the driver to exit.
+    pub async fn stop(mut self) -> Result<()> {
+        self.kill_group();
+        self.child.kill().await.map_err(CaptureError::Driver)
+    }
+
+    // the driver's browser doesn't exit with it, so signal the whole group
+    fn kill_group(&self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.child.id() {
+            // SAFETY: kill has no memory safety preconditions; a stale group just fails with ESRCH
+            unsafe {
+                libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+            }
+        }
+    }
+}
+
+impl Drop for DriverProcess {
+    fn drop(&mut self) {
+        if self.child.id().is_none() {
+            return;
+        }
+        self.kill_group();
+        if let Err(e) = self.child.start_kill() {
+            warn!(error = %e, "failed to kill driver");
+            return;
+        }
+        // drop can't wait without blocking the runtime; if the driver hasn't
+        // exited yet, tokio reaps it in the background. Close the session to
+        // wait for it with `stop`.
+        if let Ok(None) = self.child.try_wait() {
+            debug!("driver still exiting, leaving it to the runtime to reap");
+        }
+    }
+}
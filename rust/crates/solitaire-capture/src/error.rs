@@ -0,0 +1,99 @@
+use crate::config::{Backend, Browser, Viewport};
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CaptureError {
+    #[error("WebDriver process error: {0}")]
+    Driver(#[source] io::Error),
+
+    #[error("couldn't find an installed Chrome to match chromedriver to")]
+    ChromeNotFound,
+
+    #[error("no chromedriver available for Chrome {version} on {platform}")]
+    NoDriverForVersion { version: String, platform: String },
+
+    #[error("failed to download {url}: {source}")]
+    DriverDownload {
+        url: String,
+        #[source]
+        source: Box<reqwest::Error>,
+    },
+
+    #[error("invalid chromedriver archive: {0}")]
+    DriverArchive(#[source] zip::result::ZipError),
+
+    #[error("invalid WebDriver URL {url:?}: {source}")]
+    WebDriverUrl {
+        url: String,
+        #[source]
+        source: url::ParseError,
+    },
+
+    #[error("failed to connect to WebDriver at {url}: {source}")]
+    WebDriverConnect {
+        url: String,
+        #[source]
+        source: Box<fantoccini::error::NewSessionError>,
+    },
+
+    #[cfg(feature = "cdp")]
+    #[error("invalid CDP browser config: {0}")]
+    CdpLaunch(String),
+
+    #[cfg(feature = "cdp")]
+    #[error("CDP command failed: {0}")]
+    Cdp(#[from] Box<chromiumoxide::error::CdpError>),
+
+    #[error("capture backend {0:?} isn't available in this build")]
+    BackendUnavailable(&'static str),
+
+    #[error("the {backend} capture backend doesn't support {browser}")]
+    UnsupportedBrowser { backend: Backend, browser: Browser },
+
+    #[error("screenshot is {actual} but {expected} was expected; check the viewport, browser zoom and display scaling")]
+    UnexpectedSize { expected: Viewport, actual: Viewport },
+
+    #[error("page is still zoomed to {ratio}x (pinch zoom {scale}x); reset the browser zoom for the game to 100%")]
+    Zoomed { ratio: f64, scale: f64 },
+
+    #[error("nothing visible matches {0:?} on the page")]
+    NotShown(String),
+
+    #[error("browser command failed: {0}")]
+    Command(#[from] Box<fantoccini::error::CmdError>),
+
+    #[error("failed to write {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl CaptureError {
+    /// Whether retrying has a reasonable chance of succeeding, e.g. a WebDriver hiccup.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Self::WebDriverConnect { .. } | Self::Command(_) | Self::NotShown(_) => true,
+            #[cfg(feature = "cdp")]
+            Self::Cdp(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<fantoccini::error::CmdError> for CaptureError {
+    fn from(error: fantoccini::error::CmdError) -> Self {
+        Self::Command(Box::new(error))
+    }
+}
+
+pub type Result<T> = std::result::Result<T, CaptureError>;
+
+#[cfg(feature = "cdp")]
+impl From<chromiumoxide::error::CdpError> for CaptureError {
+    fn from(error: chromiumoxide::error::CdpError) -> Self {
+        Self::Cdp(Box::new(error))
+    }
+}
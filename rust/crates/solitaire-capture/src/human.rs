@@ -0,0 +1,141 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::debug;
+
+// one pointer move per frame at 60fps
+const STEP: Duration = Duration::from_millis(16);
+
+/// Makes played moves look less mechanical: the pointer travels on slightly
+/// curved paths, lands a little off center, and waits a varying time before
+/// each action, now and then for a longer pause.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HumanConfig {
+    pub enabled: bool,
+    /// Furthest a click or drag lands from the point aimed at, in CSS pixels.
+    pub jitter_px: f64,
+    /// Furthest a path bows away from a straight line, as a fraction of its length.
+    pub curve: f64,
+    /// Range of time taken to move the pointer to a card before clicking it.
+    pub min_move_ms: u64,
+    pub max_move_ms: u64,
+    /// Range of time waited before every click or drag.
+    pub min_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Chance that a wait is stretched into a pause of up to `max_pause_ms`.
+    pub pause_chance: f64,
+    pub max_pause_ms: u64,
+}
+
+impl Default for HumanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            jitter_px: 4.0,
+            curve: 0.15,
+            min_move_ms: 120,
+            max_move_ms: 400,
+            min_delay_ms: 200,
+            max_delay_ms: 800,
+            pause_chance: 0.05,
+            max_pause_ms: 4000,
+        }
+    }
+}
+
+/// One step of a pointer gesture, carried out by whichever backend is in use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PointerStep {
+    /// Moves in a straight line to `x`, `y` over `duration`.
+    MoveTo { x: f64, y: f64, duration: Duration },
+    Down,
+    Up,
+    Pause(Duration),
+}
+
+impl HumanConfig {
+    /// Waits the time a person might take before their next action.
+    pub async fn hesitate(&self) {
+        let delay = {
+            let mut rng = rand::rng();
+            let max_delay = self.max_delay_ms.max(self.min_delay_ms);
+            let mut delay = rng.random_range(self.min_delay_ms..=max_delay);
+            if rng.random_bool(self.pause_chance.clamp(0.0, 1.0)) {
+                delay = rng.random_range(delay..=self.max_pause_ms.max(delay));
+                debug!(delay_ms = delay, "pausing");
+            }
+            Duration::from_millis(delay)
+        };
+        sleep(delay).await;
+    }
+
+    /// `point` moved by up to `jitter_px` in a random direction.
+    pub(crate) fn jitter(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        if self.jitter_px <= 0.0 {
+            return (x, y);
+        }
+        let mut rng = rand::rng();
+        let angle = rng.random_range(0.0..std::f64::consts::TAU);
+        let distance = rng.random_range(0.0..self.jitter_px);
+        (x + distance * angle.cos(), y + distance * angle.sin())
+    }
+
+    /// Steps from the pointer at `from` to a click on `to`.
+    pub(crate) fn click(&self, from: (f64, f64), to: (f64, f64)) -> Vec<PointerStep> {
+        let (duration, hold) = {
+            let mut rng = rand::rng();
+            let max_move = self.max_move_ms.max(self.min_move_ms);
+            (rng.random_range(self.min_move_ms..=max_move), rng.random_range(40..=120))
+        };
+        let mut steps = self.path(from, to, Duration::from_millis(duration));
+        steps.extend([
+            PointerStep::Down,
+            PointerStep::Pause(Duration::from_millis(hold)),
+            PointerStep::Up,
+        ]);
+        steps
+    }
+
+    /// Steps from the pointer at `pointer` to a drag from `from` to `to`
+    /// taking about `duration`.
+    pub(crate) fn drag(&self, pointer: (f64, f64), from: (f64, f64), to: (f64, f64), duration: Duration) -> Vec<PointerStep> {
+        let (approach, duration) = {
+            let mut rng = rand::rng();
+            let max_move = self.max_move_ms.max(self.min_move_ms);
+            let approach = rng.random_range(self.min_move_ms..=max_move);
+            (approach, duration.mul_f64(rng.random_range(0.8..1.25)))
+        };
+        let mut steps = self.path(pointer, from, Duration::from_millis(approach));
+        steps.push(PointerStep::Down);
+        steps.extend(self.path(from, to, duration));
+        steps.push(PointerStep::Up);
+        steps
+    }
+
+    // a quadratic curve bowed to a random side, eased in and out
+    fn path(&self, from: (f64, f64), to: (f64, f64), duration: Duration) -> Vec<PointerStep> {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let bow = match self.curve > 0.0 {
+            true => rand::rng().random_range(-self.curve..self.curve),
+            false => 0.0,
+        };
+        // perpendicular to the straight line, scaled by its length
+        let control = (from.0 + dx / 2.0 - dy * bow, from.1 + dy / 2.0 + dx * bow);
+
+        let count = (duration.as_millis() / STEP.as_millis()).max(1) as u32;
+        (1..=count)
+            .map(|i| {
+                let t = f64::from(i) / f64::from(count);
+                let t = t * t * (3.0 - 2.0 * t);
+                let u = 1.0 - t;
+                PointerStep::MoveTo {
+                    x: u * u * from.0 + 2.0 * u * t * control.0 + t * t * to.0,
+                    y: u * u * from.1 + 2.0 * u * t * control.1 + t * t * to.1,
+                    duration: duration / count,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,688 @@
+//! Drives Google Solitaire in Chrome or Firefox through WebDriver to take
+//! screenshots, or in Chrome over CDP with the `cdp` feature.
+
+#[cfg(feature = "cdp")]
+pub mod cdp;
+pub mod chromedriver;
+mod config;
+mod driver;
+mod error;
+mod human;
+mod retry;
+mod settle;
+
+pub use config::{
+    Backend, Browser, CaptureConfig, ControlsConfig, Difficulty, GameOutcome, Viewport, DEFAULT_CANVAS_SELECTOR,
+};
+pub use driver::DriverProcess;
+pub use error::{CaptureError, Result};
+pub use human::HumanConfig;
+pub use retry::RetryConfig;
+pub use settle::SettleConfig;
+
+use fantoccini::actions::{InputSource, MouseActions, PointerAction, MOUSE_BUTTON_LEFT};
+use fantoccini::wd::WebDriverCompatibleCommand;
+use fantoccini::{Client, ClientBuilder, Locator};
+use human::PointerStep;
+use std::sync::Mutex;
+use std::{env, fs, net::TcpListener, path::{Path, PathBuf}, time::Duration};
+use tracing::{debug, info, warn};
+use url::Url;
+
+// an element that's display: none or detached has no client rects
+const IS_SHOWN_JS: &str =
+    "const element = document.querySelector(arguments[0]); return !!element && element.getClientRects().length > 0;";
+// relative to the viewport, unlike WebDriver's element rect, which is
+// relative to the document and so off by however far the page is scrolled
+const RECT_JS: &str = "const element = document.querySelector(arguments[0]); \
+    if (!element) return null; \
+    const rect = element.getBoundingClientRect(); \
+    return [rect.x, rect.y, rect.width, rect.height];";
+
+pub const SOLITAIRE_URL: &str = "https://www.google.com/logos/fnbx/solitaire/standalone.html";
+/// Port assumed for a `webdriver_url` that doesn't name one; chromedriver's own default is 9515.
+pub const DEFAULT_WEBDRIVER_PORT: u16 = 4444;
+
+/// A rectangle on the page in CSS pixels, relative to the top left of the viewport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// A browser with a game open in it, driven through WebDriver or CDP.
+///
+/// A driver or browser this session launched is killed if the session is
+/// dropped without [`close`](Self::close), e.g. when a future holding it is cancelled.
+pub struct BrowserSession {
+    session: Session,
+    config: CaptureConfig,
+    // where the last click or drag left the mouse, for human-like moves to start from
+    pointer: Mutex<(f64, f64)>,
+}
+
+// one per browser, so the size of the variants doesn't matter
+#[cfg_attr(feature = "cdp", allow(clippy::large_enum_variant))]
+enum Session {
+    /// The driver process is only present if this session started it. An
+    /// attached session's WebDriver session is left open on close.
+    WebDriver {
+        driver: Option<DriverProcess>,
+        client: Client,
+        attached: bool,
+    },
+    #[cfg(feature = "cdp")]
+    Cdp(cdp::CdpSession),
+}
+
+impl BrowserSession {
+    /// Launches the configured browser and starts a new game, or
+    /// attaches to `config.attach` if set.
+    #[tracing::instrument(name = "start_session", skip_all, fields(backend = ?config.backend))]
+    pub async fn start(config: &CaptureConfig) -> Result<Self> {
+        match config.backend {
+            Backend::WebDriver => {}
+            Backend::Cdp if config.browser != Browser::Chrome => {
+                return Err(CaptureError::UnsupportedBrowser {
+                    backend: Backend::Cdp,
+                    browser: config.browser,
+                })
+            }
+            #[cfg(feature = "cdp")]
+            Backend::Cdp => {
+                let session = Self {
+                    session: Session::Cdp(cdp::CdpSession::start(config).await?),
+                    config: config.clone(),
+                    pointer: Mutex::default(),
+                };
+                session.start_game().await?;
+                return Ok(session);
+            }
+            #[cfg(not(feature = "cdp"))]
+            Backend::Cdp => return Err(CaptureError::BackendUnavailable("cdp")),
+        }
+
+        if let Some(webdriver_url) = &config.attach {
+            return Self::attach(config, webdriver_url).await;
+        }
+
+        let (webdriver_url, port) = driver_address(config)?;
+
+        // start the browser and go to the game
+        let driver = match config.start_driver {
+            true => Some(start_driver(&resolve_driver(config).await, port)?),
+            false => {
+                info!(url = webdriver_url, "using remote WebDriver server");
+                None
+            }
+        };
+        let client = connect(config, &webdriver_url).await?;
+        let session = Self {
+            session: Session::WebDriver {
+                driver,
+                client,
+                attached: false,
+            },
+            config: config.clone(),
+            pointer: Mutex::default(),
+        };
+        let client = session.client().expect("session was just started through WebDriver");
+
+        if let Some(viewport) = config.viewport {
+            session.set_viewport(viewport).await?;
+        }
+        if let Some([x, y]) = config.window_position {
+            session.set_window_position(x, y).await?;
+        }
+
+        info!(url = config.url, "opening game");
+        client.goto(&config.url).await?;
+        if config.lock_zoom && config.browser == Browser::Chrome {
+            reset_zoom(client).await?;
+        }
+        session.start_game().await?;
+
+        Ok(session)
+    }
+
+    /// Connects to an already running WebDriver server and captures whatever
+    /// page its browser shows, without starting a driver or a new game.
+    ///
+    /// The WebDriver session is left open on [`close`](Self::close). With
+    /// `debugger_address` set, Chrome attaches to a browser that's already
+    /// running, so a game in progress can be picked up.
+    #[tracing::instrument(name = "attach_session", skip_all, fields(url = webdriver_url))]
+    pub async fn attach(config: &CaptureConfig, webdriver_url: &str) -> Result<Self> {
+        let client = connect(config, webdriver_url).await?;
+        client.persist().await?;
+        let url = client.current_url().await?;
+        info!(%url, "attached to running browser");
+        let session = Self {
+            session: Session::WebDriver {
+                driver: None,
+                client,
+                attached: true,
+            },
+            config: config.clone(),
+            pointer: Mutex::default(),
+        };
+
+        if let Some(viewport) = config.viewport {
+            session.set_viewport(viewport).await?;
+        }
+        if let Some([x, y]) = config.window_position {
+            session.set_window_position(x, y).await?;
+        }
+
+        Ok(session)
+    }
+
+    /// Picks the configured difficulty on the start screen and waits for the deal.
+    async fn start_game(&self) -> Result<()> {
+        if let Some(selector) = &self.config.wait_for {
+            self.wait_for(selector).await?;
+        }
+        let difficulty = self.config.difficulty;
+        info!(%difficulty, "starting new game");
+        let button = format!("#{}", difficulty.button_id());
+        self.config
+            .retry
+            .run("start a new game", || self.click_element(&button))
+            .await?;
+        self.wait_until_settled(&self.config.settle).await
+    }
+
+    /// Abandons the game in progress and deals a new one of the configured difficulty.
+    pub async fn new_game(&self) -> Result<()> {
+        self.click_element(&self.config.controls.new_game).await?;
+        self.start_game().await
+    }
+
+    /// Takes back the last move and waits for its animation to finish.
+    pub async fn undo(&self) -> Result<()> {
+        debug!("undoing last move");
+        self.click_element(&self.config.controls.undo).await?;
+        self.wait_until_settled(&self.config.settle).await
+    }
+
+    /// Whether the game is showing its end-of-game dialog, and how it ended.
+    pub async fn game_outcome(&self) -> Result<Option<GameOutcome>> {
+        let controls = &self.config.controls;
+        if self.is_shown(&controls.win_dialog).await? {
+            return Ok(Some(GameOutcome::Won));
+        }
+        if let Some(lose_dialog) = &controls.lose_dialog {
+            if self.is_shown(lose_dialog).await? {
+                return Ok(Some(GameOutcome::Lost));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Closes the end-of-game dialog and waits for the board to settle.
+    pub async fn dismiss_dialog(&self) -> Result<()> {
+        debug!("dismissing end-of-game dialog");
+        self.click_element(&self.config.controls.dismiss_dialog).await?;
+        self.wait_until_settled(&self.config.settle).await
+    }
+
+    /// Whether an element matching the CSS `selector` is on the page and rendered.
+    pub async fn is_shown(&self, selector: &str) -> Result<bool> {
+        match &self.session {
+            Session::WebDriver { client, .. } => {
+                let shown = client.execute(IS_SHOWN_JS, vec![selector.into()]).await?;
+                Ok(shown.as_bool().unwrap_or(false))
+            }
+            #[cfg(feature = "cdp")]
+            Session::Cdp(session) => session.is_shown(selector).await,
+        }
+    }
+
+    /// Runs `script` in the page as the body of a function and returns what it returns.
+    pub async fn execute(&self, script: &str) -> Result<serde_json::Value> {
+        match &self.session {
+            Session::WebDriver { client, .. } => Ok(client.execute(script, vec![]).await?),
+            #[cfg(feature = "cdp")]
+            Session::Cdp(session) => session.execute(script).await,
+        }
+    }
+
+    /// The game's own state as the configured `state_script` reports it, or
+    /// `None` if there's no script or it returned `null`.
+    pub async fn page_state(&self) -> Result<Option<serde_json::Value>> {
+        let Some(script) = &self.config.state_script else {
+            return Ok(None);
+        };
+        let value = self.execute(script).await?;
+        Ok(Some(value).filter(|value| !value.is_null()))
+    }
+
+    /// Waits until an element matching the CSS `selector` is shown, retrying
+    /// as `config.retry` allows.
+    pub async fn wait_for(&self, selector: &str) -> Result<()> {
+        debug!(selector, "waiting for element");
+        self.config
+            .retry
+            .run("wait for the page", || async {
+                match self.is_shown(selector).await? {
+                    true => Ok(()),
+                    false => Err(CaptureError::NotShown(selector.to_string())),
+                }
+            })
+            .await
+    }
+
+    /// Clicks the element matching the CSS `selector`.
+    pub async fn click_element(&self, selector: &str) -> Result<()> {
+        match &self.session {
+            Session::WebDriver { client, .. } => {
+                client.wait().for_element(Locator::Css(selector)).await?.click().await?;
+                Ok(())
+            }
+            #[cfg(feature = "cdp")]
+            Session::Cdp(session) => session.click_element(selector).await,
+        }
+    }
+
+    /// Resizes the window so the page area is exactly `viewport`, making up
+    /// for whatever the browser's toolbars and borders take. CDP sessions
+    /// emulate a screen of that size instead.
+    pub async fn set_viewport(&self, viewport: Viewport) -> Result<()> {
+        let client = match &self.session {
+            Session::WebDriver { client, .. } => client,
+            #[cfg(feature = "cdp")]
+            Session::Cdp(session) => return session.set_viewport(viewport).await,
+        };
+        client.set_window_size(viewport.width, viewport.height).await?;
+        let (width, height) = inner_size(client).await?;
+        if (width, height) != (viewport.width, viewport.height) {
+            let (outer_width, outer_height) = client.get_window_size().await?;
+            client
+                .set_window_size(
+                    (outer_width as u32 + viewport.width).saturating_sub(width),
+                    (outer_height as u32 + viewport.height).saturating_sub(height),
+                )
+                .await?;
+        }
+
+        let actual = inner_size(client).await?;
+        if actual == (viewport.width, viewport.height) {
+            debug!(requested = %viewport, "set viewport");
+        } else {
+            // e.g. a window manager that won't make the window that large
+            warn!(requested = %viewport, actual = ?actual, "couldn't size the page area to the viewport");
+        }
+        Ok(())
+    }
+
+    /// Moves the browser window's top left corner to `x`, `y` on screen. The
+    /// CDP backend places it at launch instead.
+    pub async fn set_window_position(&self, x: u32, y: u32) -> Result<()> {
+        if let Some(client) = self.client() {
+            client.set_window_position(x, y).await?;
+        }
+        Ok(())
+    }
+
+    /// Where the area [`screenshot`](Self::screenshot) captures is on the page:
+    /// the game canvas, or the whole viewport if no `canvas_selector` is configured.
+    pub async fn canvas_rect(&self) -> Result<PageRect> {
+        let selector = self.selector();
+        match &self.session {
+            Session::WebDriver { client, .. } => match selector {
+                Some(selector) => page_rect(client.execute(RECT_JS, vec![selector.into()]).await?, selector),
+                None => {
+                    let (width, height) = inner_size(client).await?;
+                    Ok(PageRect {
+                        x: 0.0,
+                        y: 0.0,
+                        width: width.into(),
+                        height: height.into(),
+                    })
+                }
+            },
+            #[cfg(feature = "cdp")]
+            Session::Cdp(session) => session.rect(selector).await,
+        }
+    }
+
+    /// Device pixels per CSS pixel, e.g. 2 on most HiDPI displays, which
+    /// screenshots come back scaled by.
+    pub async fn device_pixel_ratio(&self) -> Result<f64> {
+        match &self.session {
+            Session::WebDriver { client, .. } => {
+                let ratio = client.execute("return window.devicePixelRatio;", vec![]).await?;
+                Ok(ratio.as_f64().unwrap_or(1.0))
+            }
+            #[cfg(feature = "cdp")]
+            Session::Cdp(session) => session.device_pixel_ratio().await,
+        }
+    }
+
+    /// Left-clicks the page at `x`, `y` in CSS pixels from the top left of the viewport.
+    pub async fn click(&self, x: f64, y: f64) -> Result<()> {
+        debug!(x, y, "clicking");
+        let human = &self.config.human;
+        if human.enabled {
+            human.hesitate().await;
+            let target = human.jitter((x, y));
+            return self.perform(&human.click(self.pointer(), target), target).await;
+        }
+        *self.pointer.lock().expect("pointer lock poisoned") = (x, y);
+        match &self.session {
+            Session::WebDriver { client, .. } => {
+                let mouse = MouseActions::new("mouse".to_string())
+                    .then(PointerAction::MoveTo {
+                        duration: None,
+                        x: x.round() as i64,
+                        y: y.round() as i64,
+                    })
+                    .then(PointerAction::Down {
+                        button: MOUSE_BUTTON_LEFT,
+                    })
+                    .then(PointerAction::Up {
+                        button: MOUSE_BUTTON_LEFT,
+                    });
+                client.perform_actions(mouse).await?;
+                Ok(())
+            }
+            #[cfg(feature = "cdp")]
+            Session::Cdp(session) => session.click(x, y).await,
+        }
+    }
+
+    /// Presses the left button at `from`, moves to `to` over `duration` and
+    /// releases it there. Points are in CSS pixels from the top left of the viewport.
+    pub async fn drag(&self, from: (f64, f64), to: (f64, f64), duration: Duration) -> Result<()> {
+        debug!(?from, ?to, ?duration, "dragging");
+        let human = &self.config.human;
+        if human.enabled {
+            human.hesitate().await;
+            let (from, to) = (human.jitter(from), human.jitter(to));
+            return self.perform(&human.drag(self.pointer(), from, to, duration), to).await;
+        }
+        *self.pointer.lock().expect("pointer lock poisoned") = to;
+        match &self.session {
+            Session::WebDriver { client, .. } => {
+                let mouse = MouseActions::new("mouse".to_string())
+                    .then(PointerAction::MoveTo {
+                        duration: None,
+                        x: from.0.round() as i64,
+                        y: from.1.round() as i64,
+                    })
+                    .then(PointerAction::Down {
+                        button: MOUSE_BUTTON_LEFT,
+                    })
+                    .then(PointerAction::MoveTo {
+                        duration: Some(duration),
+                        x: to.0.round() as i64,
+                        y: to.1.round() as i64,
+                    })
+                    .then(PointerAction::Up {
+                        button: MOUSE_BUTTON_LEFT,
+                    });
+                client.perform_actions(mouse).await?;
+                Ok(())
+            }
+            #[cfg(feature = "cdp")]
+            Session::Cdp(session) => {
+                use chromiumoxide::layout::Point;
+                let from = Point { x: from.0, y: from.1 };
+                let to = Point { x: to.0, y: to.1 };
+                session.drag(from, to, duration).await
+            }
+        }
+    }
+
+    // carries out a gesture that leaves the mouse at `end`
+    async fn perform(&self, steps: &[PointerStep], end: (f64, f64)) -> Result<()> {
+        match &self.session {
+            Session::WebDriver { client, .. } => {
+                let mouse = steps.iter().fold(MouseActions::new("mouse".to_string()), |mouse, step| {
+                    mouse.then(match *step {
+                        PointerStep::MoveTo { x, y, duration } => PointerAction::MoveTo {
+                            duration: Some(duration),
+                            x: x.round() as i64,
+                            y: y.round() as i64,
+                        },
+                        PointerStep::Down => PointerAction::Down {
+                            button: MOUSE_BUTTON_LEFT,
+                        },
+                        PointerStep::Up => PointerAction::Up {
+                            button: MOUSE_BUTTON_LEFT,
+                        },
+                        PointerStep::Pause(duration) => PointerAction::Pause { duration },
+                    })
+                });
+                client.perform_actions(mouse).await?;
+            }
+            #[cfg(feature = "cdp")]
+            Session::Cdp(session) => session.perform(steps).await?,
+        }
+        *self.pointer.lock().expect("pointer lock poisoned") = end;
+        Ok(())
+    }
+
+    fn pointer(&self) -> (f64, f64) {
+        *self.pointer.lock().expect("pointer lock poisoned")
+    }
+
+    fn selector(&self) -> Option<&str> {
+        Some(self.config.canvas_selector.as_str()).filter(|selector| !selector.is_empty())
+    }
+
+    /// Waits for the page to stop changing, e.g. for the deal animation of a
+    /// new game to finish.
+    pub async fn wait_until_settled(&self, settle: &SettleConfig) -> Result<()> {
+        settle.wait(|| self.screenshot()).await.map(drop)
+    }
+
+    /// The WebDriver client, or `None` for a CDP session.
+    pub fn client(&self) -> Option<&Client> {
+        match &self.session {
+            Session::WebDriver { client, .. } => Some(client),
+            #[cfg(feature = "cdp")]
+            Session::Cdp(_) => None,
+        }
+    }
+
+    /// PNG screenshot of the game canvas as it is right now, or of the whole
+    /// page if no `canvas_selector` is configured. The `pre_capture_script`
+    /// runs first.
+    pub async fn screenshot(&self) -> Result<Vec<u8>> {
+        if let Some(script) = &self.config.pre_capture_script {
+            self.execute(script).await?;
+        }
+        let selector = self.selector();
+        let ss = match (&self.session, selector) {
+            (Session::WebDriver { client, .. }, Some(selector)) => {
+                client.find(Locator::Css(selector)).await?.screenshot().await?
+            }
+            (Session::WebDriver { client, .. }, None) => client.screenshot().await?,
+            #[cfg(feature = "cdp")]
+            (Session::Cdp(session), selector) => session.screenshot(selector).await?,
+        };
+        debug!(bytes = ss.len(), selector, "took screenshot");
+        Ok(ss)
+    }
+
+    /// Ends the session and stops the driver or browser it launched.
+    pub async fn close(self) -> Result<()> {
+        match self.session {
+            // an attached session belongs to whoever started it
+            Session::WebDriver { attached: true, .. } => Ok(()),
+            Session::WebDriver { driver, client, .. } => {
+                client.close().await?;
+                match driver {
+                    Some(driver) => driver.stop().await,
+                    None => Ok(()),
+                }
+            }
+            #[cfg(feature = "cdp")]
+            Session::Cdp(session) => session.close().await,
+        }
+    }
+}
+
+/// A DevTools command sent through chromedriver's `goog/cdp/execute` endpoint.
+#[derive(Debug)]
+struct CdpCommand {
+    method: &'static str,
+    params: serde_json::Value,
+}
+
+impl WebDriverCompatibleCommand for CdpCommand {
+    fn endpoint(&self, base_url: &Url, session_id: Option<&str>) -> std::result::Result<Url, url::ParseError> {
+        base_url.join(&format!("session/{}/goog/cdp/execute", session_id.unwrap_or_default()))
+    }
+
+    fn method_and_body(&self, _request_url: &Url) -> (http::Method, Option<String>) {
+        let body = serde_json::json!({ "cmd": self.method, "params": self.params });
+        (http::Method::POST, Some(body.to_string()))
+    }
+}
+
+// pins the device scale factor to 1, a size of 0 leaving the window's own.
+// That doesn't undo a zoom level Chrome remembers for the site, which still
+// scales the page, so fails if the page doesn't end up at one device pixel
+// per CSS pixel.
+async fn reset_zoom(client: &Client) -> Result<()> {
+    let command = CdpCommand {
+        method: "Emulation.setDeviceMetricsOverride",
+        params: serde_json::json!({ "width": 0, "height": 0, "deviceScaleFactor": 1, "mobile": false }),
+    };
+    client.issue_cmd(command).await?;
+
+    let zoom = client
+        .execute(
+            "return [window.devicePixelRatio, window.visualViewport ? window.visualViewport.scale : 1];",
+            vec![],
+        )
+        .await?;
+    let factor = |i: usize| zoom.get(i).and_then(serde_json::Value::as_f64).unwrap_or(1.0);
+    let (ratio, scale) = (factor(0), factor(1));
+    if (ratio - 1.0).abs() > 1e-3 || (scale - 1.0).abs() > 1e-3 {
+        return Err(CaptureError::Zoomed { ratio, scale });
+    }
+    debug!("reset zoom");
+    Ok(())
+}
+
+// the rectangle `RECT_JS` returned for `selector`
+pub(crate) fn page_rect(rect: serde_json::Value, selector: &str) -> Result<PageRect> {
+    let [x, y, width, height] =
+        serde_json::from_value::<[f64; 4]>(rect).map_err(|_| CaptureError::NotShown(selector.to_string()))?;
+    Ok(PageRect { x, y, width, height })
+}
+
+async fn inner_size(client: &Client) -> Result<(u32, u32)> {
+    let size = client
+        .execute("return [window.innerWidth, window.innerHeight];", vec![])
+        .await?;
+    let dimension = |i: usize| size.get(i).and_then(serde_json::Value::as_u64).unwrap_or(0) as u32;
+    Ok((dimension(0), dimension(1)))
+}
+
+/// Launches the browser's driver, starts a new game and writes a screenshot of it to `screenshot_path`.
+#[tracing::instrument(skip_all, fields(path = screenshot_path))]
+pub async fn capture(config: &CaptureConfig, screenshot_path: &str) -> Result<()> {
+    let session = BrowserSession::start(config).await?;
+
+    // take screenshot
+    let ss = session.screenshot().await?;
+    write_screenshot(&ss, screenshot_path)?;
+
+    session.close().await
+}
+
+pub fn write_screenshot(png: &[u8], path: &str) -> Result<()> {
+    fs::write(path, png).map_err(|source| CaptureError::Io {
+        path: path.into(),
+        source,
+    })
+}
+
+// the driver takes a moment after spawning before it accepts connections
+async fn connect(config: &CaptureConfig, webdriver_url: &str) -> Result<Client> {
+    debug!(url = webdriver_url, browser = %config.browser, "connecting to WebDriver");
+    config
+        .retry
+        .run("connect to WebDriver", || async {
+            ClientBuilder::native()
+                .capabilities(config.capabilities())
+                .connect(webdriver_url)
+                .await
+        })
+        .await
+        .map_err(|source| CaptureError::WebDriverConnect {
+            url: webdriver_url.to_string(),
+            source: Box::new(source),
+        })
+}
+
+/// URL to connect to and port to start the driver on: from `webdriver_url`
+/// if set, else localhost on `webdriver_port` or a free port.
+pub fn driver_address(config: &CaptureConfig) -> Result<(String, u16)> {
+    if let Some(webdriver_url) = &config.webdriver_url {
+        let url = Url::parse(webdriver_url).map_err(|source| CaptureError::WebDriverUrl {
+            url: webdriver_url.clone(),
+            source,
+        })?;
+        let port = url.port_or_known_default().unwrap_or(DEFAULT_WEBDRIVER_PORT);
+        return Ok((webdriver_url.clone(), port));
+    }
+
+    let port = match config.webdriver_port {
+        Some(port) => port,
+        None => free_port()?,
+    };
+    Ok((format!("http://localhost:{port}"), port))
+}
+
+/// A local port nothing is listening on right now.
+pub fn free_port() -> Result<u16> {
+    // the OS hands out an unused port for port 0; it's released again when the
+    // listener drops, leaving a short window in which something else could take it
+    TcpListener::bind(("127.0.0.1", 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .map_err(CaptureError::Driver)
+}
+
+pub fn start_chrome(port: u16) -> Result<DriverProcess> {
+    start_driver(Path::new(Browser::Chrome.driver()), port)
+}
+
+/// Spawns the WebDriver server at `program` listening on `port`.
+pub fn start_driver(program: &Path, port: u16) -> Result<DriverProcess> {
+    DriverProcess::spawn(program, port)
+}
+
+/// The driver executable to launch: `driver_path` if set, else for Chrome a
+/// managed chromedriver matching the installed version, else the browser's
+/// driver from `PATH`.
+pub async fn resolve_driver(config: &CaptureConfig) -> PathBuf {
+    if let Some(path) = &config.driver_path {
+        return path.into();
+    }
+    if config.browser == Browser::Chrome && config.manage_driver {
+        match chromedriver::ensure_chromedriver().await {
+            Ok(path) => return path,
+            Err(e) => warn!(error = %e, "couldn't set up a matching chromedriver, using the one on PATH"),
+        }
+    }
+    PathBuf::from(config.browser.driver())
+}
+
+/// Location of `browser`'s driver executable, if it is on `PATH`.
+pub fn find_driver(browser: Browser) -> Option<PathBuf> {
+    let name = if cfg!(windows) {
+        format!("{}.exe", browser.driver())
+    } else {
+        browser.driver().to_string()
+    };
+    env::var_os("PATH")
+        .and_then(|path| env::split_paths(&path).map(|dir| dir.join(&name)).find(|path| path.is_file()))
+}
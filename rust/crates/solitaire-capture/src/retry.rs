@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, future::Future, time::Duration};
+use tokio::time::sleep;
+use tracing::debug;
+
+/// How often and how patiently to retry a WebDriver step that can fail while
+/// the driver or page is still starting up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Total tries, including the first.
+    pub attempts: u32,
+    /// Wait before the second try; doubled after each failure.
+    pub initial_delay_ms: u64,
+    /// Upper bound for the doubled wait.
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 8,
+            initial_delay_ms: 100,
+            max_delay_ms: 5000,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Runs `attempt` until it succeeds or `attempts` tries have failed,
+    /// returning the last error.
+    pub async fn run<T, E, F, Fut>(&self, what: &str, mut attempt: F) -> Result<T, E>
+    where
+        E: Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut delays = self.delays();
+        for tries in 1.. {
+            match attempt().await {
+                Ok(value) => return Ok(value),
+                Err(e) => match delays.next() {
+                    Some(delay) => {
+                        debug!(what, tries, error = %e, retry_in = ?delay, "retrying");
+                        sleep(delay).await;
+                    }
+                    None => return Err(e),
+                },
+            }
+        }
+        unreachable!("the loop only exits by returning")
+    }
+
+    // the wait after each failed try but the last
+    fn delays(&self) -> impl Iterator<Item = Duration> {
+        let max_delay = Duration::from_millis(self.max_delay_ms);
+        std::iter::successors(Some(Duration::from_millis(self.initial_delay_ms)), move |&delay| {
+            Some((delay * 2).min(max_delay))
+        })
+        .take(self.attempts.saturating_sub(1) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn retry(attempts: u32, initial_delay_ms: u64, max_delay_ms: u64) -> RetryConfig {
+        RetryConfig {
+            attempts,
+            initial_delay_ms,
+            max_delay_ms,
+        }
+    }
+
+    fn millis(delays: impl Iterator<Item = Duration>) -> Vec<u128> {
+        delays.map(|delay| delay.as_millis()).collect()
+    }
+
+    #[test]
+    fn delays_double_up_to_the_cap() {
+        assert_eq!(millis(RetryConfig::default().delays()), [100, 200, 400, 800, 1600, 3200, 5000]);
+    }
+
+    #[test]
+    fn delays_of_a_single_attempt_are_none() {
+        assert!(millis(retry(1, 100, 5000).delays()).is_empty());
+        assert!(millis(retry(0, 100, 5000).delays()).is_empty());
+    }
+
+    #[test]
+    fn delays_keep_an_initial_delay_over_the_cap_once() {
+        assert_eq!(millis(retry(3, 500, 300).delays()), [500, 300]);
+    }
+
+    #[tokio::test]
+    async fn run_tries_until_success() {
+        let tries = Cell::new(0);
+        let result = retry(5, 0, 0)
+            .run("succeed on the third try", || {
+                tries.set(tries.get() + 1);
+                let tries = tries.get();
+                async move { if tries < 3 { Err("not yet") } else { Ok(tries) } }
+            })
+            .await;
+        assert_eq!(result, Ok(3));
+    }
+
+    #[tokio::test]
+    async fn run_returns_the_last_error() {
+        let tries = Cell::new(0);
+        let result: Result<(), String> = retry(3, 0, 0)
+            .run("always fail", || {
+                tries.set(tries.get() + 1);
+                let error = format!("try {}", tries.get());
+                async move { Err(error) }
+            })
+            .await;
+        assert_eq!(result, Err("try 3".to_string()));
+        assert_eq!(tries.get(), 3);
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use std::{future::Future, time::Duration};
+use tokio::time::{sleep, Instant};
+use tracing::{debug, warn};
+
+/// How to tell the deal animation has finished: the page is screenshotted
+/// every `interval_ms` until `stable_frames` in a row come out identical.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SettleConfig {
+    pub interval_ms: u64,
+    /// Identical consecutive screenshots needed, including the first.
+    pub stable_frames: u32,
+    /// Give up waiting and capture anyway after this long.
+    pub timeout_ms: u64,
+}
+
+impl Default for SettleConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 250,
+            stable_frames: 3,
+            timeout_ms: 10_000,
+        }
+    }
+}
+
+impl SettleConfig {
+    /// Polls `frame` until it returns the same bytes `stable_frames` times in
+    /// a row or `timeout_ms` passes, and returns the last frame.
+    pub async fn wait<E, F, Fut>(&self, mut frame: F) -> Result<Vec<u8>, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Vec<u8>, E>>,
+    {
+        let interval = Duration::from_millis(self.interval_ms);
+        let deadline = Instant::now() + Duration::from_millis(self.timeout_ms);
+
+        let mut last = frame().await?;
+        let mut stable = 1;
+        while stable < self.stable_frames {
+            if Instant::now() >= deadline {
+                warn!(timeout_ms = self.timeout_ms, "page didn't settle, capturing anyway");
+                break;
+            }
+            sleep(interval).await;
+            let next = frame().await?;
+            stable = if next == last { stable + 1 } else { 1 };
+            last = next;
+        }
+        debug!(stable, "page settled");
+        Ok(last)
+    }
+}
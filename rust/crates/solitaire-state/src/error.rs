@@ -0,0 +1,27 @@
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("game state JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("failed to read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to write {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("invalid game state: {0}")]
+    Invalid(String),
+}
+
+pub type Result<T> = std::result::Result<T, StateError>;
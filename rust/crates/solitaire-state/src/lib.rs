@@ -0,0 +1,234 @@
+//! Game state types for solitaire-ocr, with no dependency on OpenCV or the browser.
+
+mod error;
+
+pub use error::{Result, StateError};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+pub const RANK_LABELS: [&str; 13] = ["A", "2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K"];
+pub const SUIT_LABELS: [&str; 4] = ["hearts", "diamonds", "clubs", "spades"];
+/// A tableau card known to be face down because its back was detected, as
+/// opposed to `null`, a gap inferred from the layout.
+pub const FACE_DOWN: &str = "face_down";
+/// A pile nothing was detected in, not even its empty slot, which is only
+/// reported when the template set can detect empty slots; or a card also
+/// detected elsewhere with a better score.
+pub const UNKNOWN: &str = "unknown";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameState {
+    pub draw_pile: Vec<String>,
+    pub game_piles: Vec<Vec<String>>,
+    pub discard_pile: Vec<String>,
+    /// How sure detection is of each entry, if the state was detected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<Confidence>,
+    /// Cards left in the stock, if they could be estimated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stock_remaining: Option<u32>,
+    /// The face-up cards on top of the waste, if any were detected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub waste: Option<WasteFan>,
+    /// What the game's header shows, if it was read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<Stats>,
+    /// Whether the game is over, if the state was detected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<Outcome>,
+    /// Cards inside the game's hint highlight, if it shows one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<Vec<String>>,
+    /// The card the game shows as selected by a click, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selected: Option<String>,
+    /// Tableau cards read from their rank alone, their suit covered, with
+    /// the suit their neighbours leave them where only one fits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank_only: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Won,
+    Lost,
+    InProgress,
+}
+
+/// The score, time and move counter from the game's header; each `None` if
+/// it wasn't read.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stats {
+    pub score: Option<u32>,
+    /// Time played in seconds.
+    pub time: Option<u32>,
+    pub moves: Option<u32>,
+}
+
+/// The waste's visible cards: in draw-3 games up to three overlapping ones,
+/// of which only the top one can be played.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WasteFan {
+    pub playable: String,
+    /// The cards partly covered by the playable one, deepest first.
+    pub buried: Vec<String>,
+}
+
+/// Match scores laid out like a [`GameState`]'s piles: for a card, the lower
+/// of its rank and suit scores; `None` for entries that don't stand for one
+/// matched card, like `null` gaps and face-down cards.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Confidence {
+    pub draw_pile: Vec<Option<f32>>,
+    pub game_piles: Vec<Vec<Option<f32>>>,
+    pub discard_pile: Vec<Option<f32>>,
+}
+
+impl GameState {
+    /// Checks that every entry is `null`, [`FACE_DOWN`], [`UNKNOWN`], a
+    /// `<rank> <suit>` card or a bare rank whose suit couldn't be inferred
+    /// (see `rank_only`), and that no card appears more than once.
+    pub fn validate(&self) -> Result<()> {
+        let mut seen = HashSet::new();
+        let labels = self
+            .draw_pile
+            .iter()
+            .chain(self.game_piles.iter().flatten())
+            .chain(&self.discard_pile);
+
+        for label in labels {
+            // bare ranks stand for different cards of unknown suit, so may repeat
+            if label == "null" || label == FACE_DOWN || label == UNKNOWN || RANK_LABELS.contains(&label.as_str()) {
+                continue;
+            }
+            let valid = match label.split_once(' ') {
+                Some((rank, suit)) => RANK_LABELS.contains(&rank) && SUIT_LABELS.contains(&suit),
+                None => false,
+            };
+            if !valid {
+                return Err(StateError::Invalid(format!("unrecognized card {label:?}")));
+            }
+            if !seen.insert(label) {
+                return Err(StateError::Invalid(format!("{label} detected more than once")));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether both states have the same cards in the same places, whatever
+    /// their confidence.
+    pub fn same_cards(&self, other: &GameState) -> bool {
+        self.draw_pile == other.draw_pile
+            && self.game_piles == other.game_piles
+            && self.discard_pile == other.discard_pile
+    }
+
+    /// One line per pile whose contents in `actual` differ from this state's.
+    pub fn mismatches(&self, actual: &GameState) -> Vec<String> {
+        let mut mismatches = Vec::new();
+
+        if self.draw_pile != actual.draw_pile {
+            mismatches.push(format!(
+                "draw_pile: expected {:?}, got {:?}",
+                self.draw_pile, actual.draw_pile
+            ));
+        }
+
+        let columns = self.game_piles.len().max(actual.game_piles.len());
+        for i in 0..columns {
+            let expected = self.game_piles.get(i);
+            let actual = actual.game_piles.get(i);
+            if expected != actual {
+                mismatches.push(format!("game_piles[{i}]: expected {expected:?}, got {actual:?}"));
+            }
+        }
+
+        if self.discard_pile != actual.discard_pile {
+            mismatches.push(format!(
+                "discard_pile: expected {:?}, got {:?}",
+                self.discard_pile, actual.discard_pile
+            ));
+        }
+
+        mismatches
+    }
+}
+
+pub fn load_game_state(path: &str) -> Result<GameState> {
+    let json = fs::read_to_string(path).map_err(|source| StateError::Read {
+        path: path.into(),
+        source,
+    })?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+pub fn save_game_state(state: &GameState, path: &str) -> Result<()> {
+    let json = serde_json::to_string_pretty(state)?;
+    fs::write(path, json).map_err(|source| StateError::Io {
+        path: path.into(),
+        source,
+    })
+}
+
+/// Appends `state` to `path` as a single line of JSON.
+pub fn append_game_state(state: &GameState, path: &str) -> Result<()> {
+    let mut line = serde_json::to_string(state)?;
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|source| StateError::Io {
+            path: path.into(),
+            source,
+        })
+}
+
+/// Writes `state` to stdout as a single line of JSON.
+pub fn print_game_state(state: &GameState) -> Result<()> {
+    let mut line = serde_json::to_string(state)?;
+    line.push('\n');
+
+    let mut stdout = std::io::stdout().lock();
+    stdout
+        .write_all(line.as_bytes())
+        .and_then(|()| stdout.flush())
+        .map_err(|source| StateError::Io {
+            path: "<stdout>".into(),
+            source,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(game_piles: &[&[&str]]) -> GameState {
+        let game_piles: Vec<Vec<&str>> = game_piles.iter().map(|pile| pile.to_vec()).collect();
+        serde_json::from_value(serde_json::json!({
+            "draw_pile": [],
+            "game_piles": game_piles,
+            "discard_pile": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn validate_accepts_bare_ranks() {
+        assert!(state(&[&[FACE_DOWN, "7"], &["7", "8 hearts"]]).validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_labels_and_duplicates() {
+        assert!(state(&[&["11"]]).validate().is_err());
+        assert!(state(&[&["7 stars"]]).validate().is_err());
+        assert!(state(&[&["7 hearts"], &["7 hearts"]]).validate().is_err());
+    }
+}
@@ -0,0 +1,51 @@
+//! Benchmarks of the vision pipeline's stages on the bundled selftest
+//! screenshot and the built-in templates, so runs on different commits
+//! measure the same work.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use opencv::prelude::*;
+use solitaire_vision::config::{BoxClass, DetectionConfig, LayoutConfig};
+use solitaire_vision::{assemble, detect, embedded};
+use std::hint::black_box;
+
+const SCREENSHOT: &[u8] = include_bytes!("../../../fixtures/selftest.png");
+
+fn bench_vision(c: &mut Criterion) {
+    let frame = detect::decode_color_image(SCREENSHOT).expect("fixture decodes");
+    let gray = detect::to_gray(&frame).expect("fixture converts to greyscale");
+    let templates = embedded::templates().expect("built-in templates decode");
+    let config = DetectionConfig::default();
+    let layout = LayoutConfig::default();
+
+    let matches = detect::match_templates(&gray, &templates, &config, &|_| {}).expect("fixture matches");
+    let detections = matches.clone().into_detections(&frame, &config).expect("fixture detects");
+    // every rank match before suppression, for NMS on its own
+    let cards: Vec<_> = templates
+        .iter()
+        .filter(|template| detect::RANK_LABELS.contains(&template.label.as_str()))
+        .flat_map(|template| {
+            let matches = detect::match_template_scores(&gray, &template.image, config.card_threshold).expect("fixture matches");
+            detect::create_bounding_boxes(matches, template.image.cols(), template.image.rows(), template.label.clone())
+        })
+        .collect();
+
+    c.bench_function("match_templates", |b| {
+        b.iter(|| detect::match_templates(black_box(&gray), &templates, &config, &|_| {}).unwrap())
+    });
+    c.bench_function("nms", |b| {
+        let threshold = config.overlap_threshold_for(BoxClass::Rank);
+        b.iter(|| detect::non_maximum_suppression(black_box(cards.clone()), threshold, &config.nms))
+    });
+    c.bench_function("into_detections", |b| {
+        b.iter(|| black_box(matches.clone()).into_detections(&frame, &config).unwrap())
+    });
+    c.bench_function("assemble", |b| {
+        b.iter(|| assemble::generate_game_state(black_box(&detections), frame.cols(), &layout))
+    });
+    c.bench_function("detect", |b| {
+        b.iter(|| detect::detect(black_box(&frame), &templates, &config).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_vision);
+criterion_main!(benches);
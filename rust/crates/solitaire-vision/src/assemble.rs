@@ -0,0 +1,745 @@
+use crate::config::{LayoutConfig, LayoutDescriptor};
+use crate::detect::{
+    foundation_suit, BoundingBox, Detections, EMPTY_LABEL, FACE_DOWN, LOST_LABEL, RANK_LABELS, SUIT_LABELS, UNKNOWN,
+    WON_LABEL,
+};
+use solitaire_state::{Confidence, GameState, Outcome, WasteFan};
+use std::collections::{HashMap, HashSet};
+use tracing::debug;
+
+// k-means on a few dozen x centers settles in a handful of rounds
+const MAX_KMEANS_ITERATIONS: usize = 20;
+
+pub fn associate_cards_and_suits(
+    cards: Vec<BoundingBox>,
+    suits: Vec<BoundingBox>,
+) -> Vec<BoundingBox> {
+    let mut associated_cards = Vec::new();
+
+    for mut card in cards {
+        let mut closest_suit = None;
+        let mut min_distance = i32::MAX;
+
+        // find closest suit; it sits right of its rank, and ignoring those
+        // further left keeps a fanned waste card from taking the suit of
+        // the card it covers
+        let center_x = (card.x1 + card.x2) / 2;
+        for suit in suits.iter().filter(|suit| suit.x1 >= center_x) {
+            let horizontal_distance = (suit.x1 - card.x2).abs();
+            let vertical_overlap = (suit.y1 <= card.y2) && (suit.y2 >= card.y1);
+
+            if vertical_overlap && horizontal_distance < min_distance {
+                min_distance = horizontal_distance;
+                closest_suit = Some(suit);
+            }
+        }
+
+        // associate card with suit; the pair is only as sure as its shakier half
+        if let Some(suit) = closest_suit {
+            card.label = format!("{} {}", card.label, suit.label);
+            card.score = card.score.min(suit.score);
+        }
+
+        associated_cards.push(card);
+    }
+
+    associated_cards
+}
+
+fn x_percentage_ranges(columns: usize) -> impl Iterator<Item = (f32, f32)> + Clone {
+    (0..columns).map(move |i| (i as f32 / columns as f32, (i + 1) as f32 / columns as f32))
+}
+
+/// `bounding_boxes` split into one group per range in `ranges`, by which
+/// range their x center falls in as a fraction of `image_width`.
+pub fn group_bounding_boxes_by_column(
+    bounding_boxes: &[BoundingBox],
+    image_width: i32,
+    ranges: &[(f32, f32)],
+) -> Vec<Vec<BoundingBox>> {
+    let mut columns = vec![Vec::new(); ranges.len()];
+    for b in bounding_boxes {
+        let x_percentage = (b.x1 + b.x2) as f32 / 2.0 / image_width as f32;
+        if let Some(column) = ranges.iter().position(|(start, end)| *start <= x_percentage && x_percentage < *end) {
+            columns[column].push(b.clone());
+        }
+    }
+    columns
+}
+
+pub fn group_bounding_boxes_by_y_range(
+    bounding_boxes: &[BoundingBox],
+    y_range_step: i32,
+) -> Vec<Vec<BoundingBox>> {
+    let mut grouped_rows: Vec<Vec<BoundingBox>> = Vec::new();
+    let mut current_row: Vec<BoundingBox> = Vec::new();
+
+    let mut y_start = 0;
+    let mut y_end = y_range_step;
+
+    for b in bounding_boxes {
+        let center_y = (b.y1 + b.y2) / 2;
+
+        if y_start <= center_y && center_y < y_end {
+            current_row.push(b.clone());
+        } else {
+            if !current_row.is_empty() {
+                grouped_rows.push(current_row.clone());
+            }
+            current_row = vec![b.clone()];
+            y_start = center_y / y_range_step * y_range_step;
+            y_end = y_start + y_range_step;
+        }
+    }
+
+    if !current_row.is_empty() {
+        grouped_rows.push(current_row);
+    }
+
+    grouped_rows
+}
+
+/// Start and end of each of the layout's columns, as fractions of the
+/// frame's width. With `auto_columns`, the boxes' x centers are clustered
+/// with 1-D k-means started from the centers of equal-width strips, so each
+/// column keeps its place when it's empty, and the columns meet halfway
+/// between neighbouring clusters; otherwise they're the equal-width strips.
+pub fn column_ranges(boxes: &[BoundingBox], image_width: i32, layout: &LayoutConfig) -> Vec<(f32, f32)> {
+    let equal: Vec<(f32, f32)> = x_percentage_ranges(layout.columns).collect();
+    if !layout.auto_columns || boxes.is_empty() || layout.columns < 2 {
+        return equal;
+    }
+
+    let centers: Vec<f32> = boxes.iter().map(|b| (b.x1 + b.x2) as f32 / 2.0 / image_width as f32).collect();
+    let mut centroids: Vec<f32> = equal.iter().map(|(start, end)| (start + end) / 2.0).collect();
+    for _ in 0..MAX_KMEANS_ITERATIONS {
+        let mut sums = vec![(0.0, 0); centroids.len()];
+        for &x in &centers {
+            let nearest = (0..centroids.len())
+                .min_by(|&a, &b| (centroids[a] - x).abs().total_cmp(&(centroids[b] - x).abs()))
+                .expect("at least two columns");
+            sums[nearest].0 += x;
+            sums[nearest].1 += 1;
+        }
+        let next: Vec<f32> = centroids
+            .iter()
+            .zip(&sums)
+            .map(|(&centroid, &(sum, n))| if n == 0 { centroid } else { sum / n as f32 })
+            .collect();
+        let settled = next.iter().zip(&centroids).all(|(a, b)| (a - b).abs() < 1e-4);
+        centroids = next;
+        if settled {
+            break;
+        }
+    }
+
+    let mut ranges = Vec::with_capacity(centroids.len());
+    let mut start = 0.0;
+    for pair in centroids.windows(2) {
+        let boundary = (pair[0] + pair[1]) / 2.0;
+        ranges.push((start, boundary));
+        start = boundary;
+    }
+    ranges.push((start, 1.0));
+    debug!(?ranges, "placed columns");
+    ranges
+}
+
+#[tracing::instrument(skip_all, fields(cards = detections.cards.len(), suits = detections.suits.len()))]
+pub fn generate_game_state(detections: &Detections, image_width: i32, layout: &LayoutConfig) -> GameState {
+    let associated_cards = with_markers(detections);
+    // with no empty slot template, an empty pile looks the same as a missed one
+    let missing = detections.empty_slots.as_ref().map(|_| UNKNOWN);
+
+    let ranges = column_ranges(&associated_cards, image_width, layout);
+    let grouped_by_x = group_bounding_boxes_by_column(&associated_cards, image_width, &ranges);
+    let y_range_step = layout.y_range_step;
+
+    let mut draw_pile = Vec::new();
+    let mut waste = None;
+    let mut game_piles = vec![Vec::new(); layout.columns.saturating_sub(2)];
+    let mut discard_pile = vec![None; 4];
+    let mut foundation_suits = vec![None; 4];
+
+    for (column, boxes) in grouped_by_x.into_iter().enumerate() {
+        let is_tableau = column > 0 && column < layout.columns - 1;
+        let y_range_step = if is_tableau { tableau_y_step(&boxes, layout) } else { y_range_step };
+        let rows = group_bounding_boxes_by_y_range(&boxes, y_range_step);
+
+        if column == 0 {
+            // the stock's back or empty slot only says whether it has cards left
+            let rows: Vec<_> = fanned(without(rows, &[FACE_DOWN, EMPTY_LABEL]), layout.draw_count).collect();
+            waste = waste_fan(&rows, layout.draw_count);
+            draw_pile = rows.into_iter().flat_map(|row| row.into_iter().map(|b| b.label)).collect();
+        } else if column == layout.columns - 1 {
+            for (i, row) in without(rows, &[FACE_DOWN]).iter().enumerate().take(4) {
+                let card = row.iter().find(|b| b.label != EMPTY_LABEL);
+                discard_pile[i] = match card {
+                    Some(b) => Some(b.label.clone()),
+                    None => Some("null".to_string()),
+                };
+                foundation_suits[i] = slot_suit(row, &detections.foundation_slots);
+            }
+        } else {
+            let index = column - 1;
+            if index < game_piles.len() {
+                let starting_y = layout.starting_y;
+                let is_empty = rows.iter().flatten().any(|b| b.label == EMPTY_LABEL);
+                let rows = without(rows, &[EMPTY_LABEL]);
+                if let (true, false, Some(missing)) = (rows.is_empty(), is_empty, missing) {
+                    game_piles[index].push(missing.to_string());
+                }
+
+                // with backs detected the face-down cards are already there as rows
+                let has_backs = rows.iter().flatten().any(|b| b.label == FACE_DOWN);
+                if let Some(first_box) = rows
+                .iter()
+                .flat_map(|row| row.iter())
+                .min_by_key(|b| b.y1)
+                .filter(|_| !has_backs)
+                {
+                    let null_rows = (first_box.y1.saturating_sub(starting_y)) / y_range_step;
+                    game_piles[index].resize(null_rows as usize, "null".to_string());
+                }
+                for row in rows {
+                    game_piles[index].extend(row.iter().map(|b| b.label.clone()));
+                }
+            }
+        }
+    }
+
+    let mut discard_pile: Vec<String> = discard_pile
+        .into_iter()
+        .map(|card| card.unwrap_or_else(|| missing.unwrap_or("null").to_string()))
+        .collect();
+    if layout.foundations_by_suit {
+        discard_pile = by_suit(discard_pile, &foundation_suits);
+    }
+
+    debug!(draw = draw_pile.len(), ?game_piles, ?discard_pile, "assembled game state");
+
+    let mut state = GameState {
+        draw_pile,
+        game_piles,
+        discard_pile,
+        confidence: None,
+        stock_remaining: None,
+        waste,
+        stats: None,
+        outcome: None,
+        hint: None,
+        selected: None,
+        rank_only: None,
+    };
+    state.rank_only = infer_suits(&mut state.game_piles, &state.draw_pile, &state.discard_pile);
+    state.confidence = Some(confidence(&state, &associated_cards));
+    state.outcome = Some(outcome(detections, &state));
+    state.hint = hint(detections, &associated_cards);
+    state.selected = selected(detections, &associated_cards);
+    state
+}
+
+/// Like [`generate_game_state`], but assigns each card to the pile whose
+/// region in `regions` contains its center instead of to equal-width columns.
+#[tracing::instrument(skip_all, fields(cards = detections.cards.len(), suits = detections.suits.len()))]
+pub fn generate_game_state_from_regions(
+    detections: &Detections,
+    image_width: i32,
+    image_height: i32,
+    regions: &LayoutDescriptor,
+    layout: &LayoutConfig,
+) -> GameState {
+    let associated_cards = with_markers(detections);
+    let missing = detections.empty_slots.as_ref().map(|_| UNKNOWN);
+    let y_range_step = layout.y_range_step;
+
+    let mut stock = Vec::new();
+    let mut waste_cards = Vec::new();
+    let mut foundations = vec![Vec::new(); regions.foundations.len()];
+    let mut tableau = vec![Vec::new(); regions.tableau.len()];
+
+    let cards = associated_cards.clone();
+    for b in associated_cards {
+        let x = (b.x1 + b.x2) as f32 / 2.0 / image_width as f32;
+        let y = (b.y1 + b.y2) as f32 / 2.0 / image_height as f32;
+
+        if regions.stock.contains(x, y) {
+            stock.push(b);
+        } else if regions.waste.is_some_and(|waste| waste.contains(x, y)) {
+            waste_cards.push(b);
+        } else if let Some(i) = regions.foundations.iter().position(|r| r.contains(x, y)) {
+            foundations[i].push(b);
+        } else if let Some(i) = regions.tableau.iter().position(|r| r.contains(x, y)) {
+            tableau[i].push(b);
+        }
+    }
+
+    let [stock_rows, waste_rows] = [stock, waste_cards].map(|boxes| {
+        let rows = without(group_bounding_boxes_by_y_range(&boxes, y_range_step), &[FACE_DOWN, EMPTY_LABEL]);
+        fanned(rows, layout.draw_count).collect::<Vec<_>>()
+    });
+    // without a region of its own, the waste is what shows in the stock's
+    let waste = waste_fan(if regions.waste.is_some() { &waste_rows } else { &stock_rows }, layout.draw_count);
+    let draw_pile = stock_rows
+        .into_iter()
+        .chain(waste_rows)
+        .flat_map(|row| row.into_iter().map(|b| b.label))
+        .collect();
+
+    let game_piles = tableau
+        .iter()
+        .zip(&regions.tableau)
+        .map(|(boxes, region)| {
+            let is_empty = boxes.iter().any(|b| b.label == EMPTY_LABEL);
+            let boxes: Vec<_> = boxes.iter().filter(|b| b.label != EMPTY_LABEL).cloned().collect();
+            if let (true, false, Some(missing)) = (boxes.is_empty(), is_empty, missing) {
+                return vec![missing.to_string()];
+            }
+
+            let y_range_step = tableau_y_step(&boxes, layout);
+            let rows = group_bounding_boxes_by_y_range(&boxes, y_range_step);
+            let mut pile = Vec::new();
+            let has_backs = boxes.iter().any(|b| b.label == FACE_DOWN);
+            if let Some(first_box) = boxes.iter().min_by_key(|b| b.y1).filter(|_| !has_backs) {
+                let region_top = (region.y1 * image_height as f32) as i32;
+                let null_rows = (first_box.y1 - region_top).max(0) / y_range_step;
+                pile.resize(null_rows as usize, "null".to_string());
+            }
+            pile.extend(rows.into_iter().flatten().map(|b| b.label));
+            pile
+        })
+        .collect();
+
+    let mut discard_pile: Vec<String> = foundations
+        .iter()
+        .map(|boxes| {
+            let is_empty = boxes.iter().any(|b| b.label == EMPTY_LABEL);
+            let card = boxes
+                .iter()
+                .filter(|b| b.label != FACE_DOWN && b.label != EMPTY_LABEL)
+                .min_by_key(|b| b.y1);
+            match card {
+                Some(b) => b.label.clone(),
+                None if is_empty => "null".to_string(),
+                None => missing.unwrap_or("null").to_string(),
+            }
+        })
+        .collect();
+    if layout.foundations_by_suit {
+        let suits: Vec<_> = foundations.iter().map(|boxes| slot_suit(boxes, &detections.foundation_slots)).collect();
+        discard_pile = by_suit(discard_pile, &suits);
+    }
+
+    let mut state = GameState {
+        draw_pile,
+        game_piles,
+        discard_pile,
+        confidence: None,
+        stock_remaining: None,
+        waste,
+        stats: None,
+        outcome: None,
+        hint: None,
+        selected: None,
+        rank_only: None,
+    };
+    state.rank_only = infer_suits(&mut state.game_piles, &state.draw_pile, &state.discard_pile);
+    state.confidence = Some(confidence(&state, &cards));
+    state.outcome = Some(outcome(detections, &state));
+    state.hint = hint(detections, &cards);
+    state.selected = selected(detections, &cards);
+    debug!(draw = state.draw_pile.len(), game_piles = ?state.game_piles, discard_pile = ?state.discard_pile, "assembled game state");
+    state
+}
+
+/// The spacing between consecutive cards of the tableau pile made of
+/// `boxes`: with `auto_y_step`, the median distance between card centers
+/// further apart than half a box's height, so zoomed or compressed stacks
+/// still split into one row per card; otherwise the configured step.
+pub fn tableau_y_step(boxes: &[BoundingBox], layout: &LayoutConfig) -> i32 {
+    if !layout.auto_y_step {
+        return layout.y_range_step;
+    }
+    let mut centers: Vec<i32> = boxes.iter().map(|b| (b.y1 + b.y2) / 2).collect();
+    centers.sort_unstable();
+    let mut heights: Vec<i32> = boxes.iter().map(|b| b.y2 - b.y1).collect();
+    heights.sort_unstable();
+    let Some(&height) = heights.get(heights.len() / 2) else {
+        return layout.y_range_step;
+    };
+
+    let mut gaps: Vec<i32> = centers.windows(2).map(|pair| pair[1] - pair[0]).filter(|gap| *gap > height / 2).collect();
+    if gaps.is_empty() {
+        return layout.y_range_step;
+    }
+    gaps.sort_unstable();
+    let step = gaps[gaps.len() / 2];
+    debug!(step, cards = boxes.len(), "estimated tableau spacing");
+    step
+}
+
+// the cards with their suits, plus face-down cards and empty slots labelled
+// as such, top to bottom
+fn with_markers(detections: &Detections) -> Vec<BoundingBox> {
+    let mut cards = associate_cards_and_suits(detections.cards.clone(), detections.suits.clone());
+    resolve_duplicates(&mut cards);
+    let relabel = |label: &'static str| {
+        move |b: &BoundingBox| BoundingBox {
+            label: label.to_string(),
+            ..b.clone()
+        }
+    };
+    cards.extend(detections.backs.iter().map(relabel(FACE_DOWN)));
+    cards.extend(detections.empty_slots.iter().flatten().map(relabel(EMPTY_LABEL)));
+    // an icon only shows on an empty foundation
+    cards.extend(detections.foundation_slots.iter().map(relabel(EMPTY_LABEL)));
+    // stable, so cards keep the order suppression left them in
+    cards.sort_by_key(|b| b.y2);
+    cards
+}
+
+// Won or lost if the game shows its end screen for either, and won anyway
+// once every foundation is up to its king, since not every game has one.
+fn outcome(detections: &Detections, state: &GameState) -> Outcome {
+    if detections.end_screens.iter().any(|b| b.label == WON_LABEL) {
+        return Outcome::Won;
+    }
+    if detections.end_screens.iter().any(|b| b.label == LOST_LABEL) {
+        return Outcome::Lost;
+    }
+    let complete = state.discard_pile.len() == SUIT_LABELS.len()
+        && state.discard_pile.iter().all(|card| card.starts_with("K "));
+    if complete {
+        Outcome::Won
+    } else {
+        Outcome::InProgress
+    }
+}
+
+// the cards whose centers are inside a highlight, top to bottom, or None if
+// nothing is highlighted
+fn hint(detections: &Detections, cards: &[BoundingBox]) -> Option<Vec<String>> {
+    if detections.highlights.is_empty() {
+        return None;
+    }
+    Some(
+        inside(cards, &detections.highlights)
+            .map(|b| b.label.clone())
+            .collect(),
+    )
+}
+
+// the topmost card inside a selection: selecting a tableau card takes the
+// ones stacked on it along, but it's the one that was clicked
+fn selected(detections: &Detections, cards: &[BoundingBox]) -> Option<String> {
+    inside(cards, &detections.selected)
+        .min_by_key(|b| b.y1)
+        .map(|b| b.label.clone())
+}
+
+// the cards among `cards` whose centers are inside one of `areas`
+fn inside<'a>(cards: &'a [BoundingBox], areas: &'a [BoundingBox]) -> impl Iterator<Item = &'a BoundingBox> {
+    cards.iter().filter(|b| {
+        let (x, y) = ((b.x1 + b.x2) / 2, (b.y1 + b.y2) / 2);
+        b.label.contains(' ') && areas.iter().any(|a| a.x1 <= x && x < a.x2 && a.y1 <= y && y < a.y2)
+    })
+}
+
+// The suit of the foundation made of `boxes`: its card's, or the icon's
+// among `icons` that one of its boxes was made from if it's empty.
+fn slot_suit(boxes: &[BoundingBox], icons: &[BoundingBox]) -> Option<String> {
+    if let Some((_, suit)) = boxes.iter().find_map(|b| b.label.split_once(' ')) {
+        return Some(suit.to_string());
+    }
+    icons
+        .iter()
+        .find(|icon| boxes.iter().any(|b| (b.x1, b.y1, b.x2, b.y2) == (icon.x1, icon.y1, icon.x2, icon.y2)))
+        .and_then(|icon| foundation_suit(&icon.label))
+        .map(str::to_string)
+}
+
+// `piles` moved to their suit's place in SUIT_LABELS order; those of no
+// known suit, or a suit already taken, fill the places left in their order
+fn by_suit(piles: Vec<String>, suits: &[Option<String>]) -> Vec<String> {
+    let mut ordered: Vec<Option<String>> = vec![None; piles.len()];
+    let mut rest = Vec::new();
+    for (pile, suit) in piles.into_iter().zip(suits) {
+        let place = suit
+            .as_deref()
+            .and_then(|suit| SUIT_LABELS.iter().position(|label| *label == suit))
+            .filter(|&i| i < ordered.len() && ordered[i].is_none());
+        match place {
+            Some(i) => ordered[i] = Some(pile),
+            None => rest.push(pile),
+        }
+    }
+    let mut rest = rest.into_iter();
+    ordered
+        .into_iter()
+        .map(|pile| pile.or_else(|| rest.next()).unwrap_or_else(|| "null".to_string()))
+        .collect()
+}
+
+// A deck has one of each card: where several boxes claim the same one, the
+// best-scoring keeps it and the others become unknown.
+fn resolve_duplicates(cards: &mut [BoundingBox]) {
+    let mut best: HashMap<&str, usize> = HashMap::new();
+    for (i, card) in cards.iter().enumerate() {
+        // only cards with a suit name one card of the deck
+        if !card.label.contains(' ') {
+            continue;
+        }
+        let kept = best.entry(card.label.as_str()).or_insert(i);
+        if card.score > cards[*kept].score {
+            *kept = i;
+        }
+    }
+    let kept: Vec<usize> = best.into_values().collect();
+
+    for (i, card) in cards.iter_mut().enumerate() {
+        if card.label.contains(' ') && !kept.contains(&i) {
+            debug!(label = %card.label, x1 = card.x1, y1 = card.y1, score = card.score, "duplicate card, marking unknown");
+            card.label = UNKNOWN.to_string();
+        }
+    }
+}
+
+// Tableau cards read as a bare rank, their suit covered by the card on top.
+// A face-up run alternates colors, so a neighbour with a suit gives the
+// card's color, and of that color's two suits, the one whose card isn't
+// already elsewhere is its suit. Returns every such card's label, with its
+// suit where one was inferred, or None if there are none.
+fn infer_suits(game_piles: &mut [Vec<String>], draw_pile: &[String], discard_pile: &[String]) -> Option<Vec<String>> {
+    let suit = |label: &str| label.split_once(' ').map(|(_, suit)| suit.to_string()).filter(|suit| SUIT_LABELS.contains(&suit.as_str()));
+    let mut seen: HashSet<String> = draw_pile
+        .iter()
+        .chain(game_piles.iter().flatten())
+        .chain(discard_pile)
+        .filter(|label| suit(label).is_some())
+        .cloned()
+        .collect();
+
+    let mut rank_only = Vec::new();
+    for pile in game_piles.iter_mut() {
+        for i in 0..pile.len() {
+            if !RANK_LABELS.contains(&pile[i].as_str()) {
+                continue;
+            }
+            let neighbour = [i.checked_sub(1), Some(i + 1)]
+                .into_iter()
+                .flatten()
+                .find_map(|j| pile.get(j).and_then(|label| suit(label)));
+            if let Some(neighbour) = neighbour {
+                let suits = if matches!(neighbour.as_str(), "hearts" | "diamonds") {
+                    ["clubs", "spades"]
+                } else {
+                    ["hearts", "diamonds"]
+                };
+                let free: Vec<String> = suits
+                    .iter()
+                    .map(|suit| format!("{} {suit}", pile[i]))
+                    .filter(|card| !seen.contains(card))
+                    .collect();
+                if let [card] = free.as_slice() {
+                    debug!(rank = %pile[i], card, "inferred covered suit");
+                    seen.insert(card.clone());
+                    pile[i] = card.clone();
+                }
+            }
+            rank_only.push(pile[i].clone());
+        }
+    }
+    (!rank_only.is_empty()).then_some(rank_only)
+}
+
+// each card's score, looked up by label since a card only shows up once;
+// markers and gaps are left without one
+fn confidence(state: &GameState, cards: &[BoundingBox]) -> Confidence {
+    let mut scores: HashMap<&str, f32> = HashMap::new();
+    for b in cards.iter().filter(|b| ![FACE_DOWN, EMPTY_LABEL, UNKNOWN].contains(&b.label.as_str())) {
+        let score = scores.entry(b.label.as_str()).or_insert(b.score);
+        *score = score.max(b.score);
+    }
+    let rank_only = state.rank_only.as_deref().unwrap_or_default();
+    let score = |label: &String| {
+        scores.get(label.as_str()).copied().or_else(|| {
+            // an inferred suit was never matched, only its rank
+            let (rank, _) = label.split_once(' ').filter(|_| rank_only.contains(label))?;
+            scores.get(rank).copied()
+        })
+    };
+    let pile = |labels: &[String]| labels.iter().map(score).collect();
+
+    Confidence {
+        draw_pile: pile(&state.draw_pile),
+        game_piles: state.game_piles.iter().map(|labels| pile(labels)).collect(),
+        discard_pile: pile(&state.discard_pile),
+    }
+}
+
+fn without(rows: Vec<Vec<BoundingBox>>, labels: &[&str]) -> Vec<Vec<BoundingBox>> {
+    rows.into_iter()
+        .map(|row| row.into_iter().filter(|b| !labels.contains(&b.label.as_str())).collect::<Vec<_>>())
+        .filter(|row| !row.is_empty())
+        .collect()
+}
+
+// The waste's top cards from its face-up `rows` ordered by `fanned`: the
+// last row, of which at most `draw_count` cards can show, the rightmost on top.
+fn waste_fan(rows: &[Vec<BoundingBox>], draw_count: usize) -> Option<WasteFan> {
+    let row = rows.last()?;
+    let shown = &row[row.len().saturating_sub(draw_count.max(1))..];
+    let (playable, buried) = shown.split_last()?;
+    Some(WasteFan {
+        playable: playable.label.clone(),
+        buried: buried.iter().map(|b| b.label.clone()).collect(),
+    })
+}
+
+// a draw-3 waste fans its cards sideways into the same row, so order each row
+// left to right to keep the playable card last
+fn fanned(rows: Vec<Vec<BoundingBox>>, draw_count: usize) -> impl Iterator<Item = Vec<BoundingBox>> {
+    rows.into_iter().map(move |mut row| {
+        if draw_count > 1 {
+            row.sort_by_key(|b| b.x1);
+        }
+        row
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(columns: usize) -> LayoutConfig {
+        LayoutConfig {
+            columns,
+            auto_columns: true,
+            ..LayoutConfig::default()
+        }
+    }
+
+    fn assert_ranges(actual: &[(f32, f32)], expected: &[(f32, f32)]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a.0 - e.0).abs() < 1e-3 && (a.1 - e.1).abs() < 1e-3, "{actual:?} != {expected:?}");
+        }
+    }
+
+    fn strings(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|label| label.to_string()).collect()
+    }
+
+    #[test]
+    fn column_ranges_without_boxes_are_equal_strips() {
+        assert_ranges(&column_ranges(&[], 300, &layout(3)), &[(0.0, 1.0 / 3.0), (1.0 / 3.0, 2.0 / 3.0), (2.0 / 3.0, 1.0)]);
+    }
+
+    #[test]
+    fn column_ranges_of_a_single_column_span_the_frame() {
+        let boxes = [BoundingBox::at("A", [10, 0, 30, 20], 0.9)];
+        assert_ranges(&column_ranges(&boxes, 300, &layout(1)), &[(0.0, 1.0)]);
+    }
+
+    #[test]
+    fn column_ranges_follow_the_clusters() {
+        // centered at 20, 150 and 280 of 300, off the strips' 50, 150 and 250
+        let boxes = [
+            BoundingBox::at("A", [10, 0, 30, 20], 0.9),
+            BoundingBox::at("K", [10, 40, 30, 60], 0.9),
+            BoundingBox::at("2", [140, 0, 160, 20], 0.9),
+            BoundingBox::at("Q", [270, 0, 290, 20], 0.9),
+        ];
+        let ranges = column_ranges(&boxes, 300, &layout(3));
+        assert_ranges(&ranges, &[(0.0, 0.2833), (0.2833, 0.7167), (0.7167, 1.0)]);
+
+        let fixed = LayoutConfig {
+            auto_columns: false,
+            ..layout(3)
+        };
+        assert_ranges(&column_ranges(&boxes, 300, &fixed), &column_ranges(&[], 300, &fixed));
+    }
+
+    #[test]
+    fn column_ranges_keep_empty_columns_in_place() {
+        let boxes = [BoundingBox::at("A", [10, 0, 30, 20], 0.9), BoundingBox::at("Q", [270, 0, 290, 20], 0.9)];
+        assert_ranges(&column_ranges(&boxes, 300, &layout(3)), &[(0.0, 0.2833), (0.2833, 0.7167), (0.7167, 1.0)]);
+    }
+
+    #[test]
+    fn column_ranges_cover_boxes_at_the_frame_edges() {
+        let boxes = [BoundingBox::at("A", [0, 0, 20, 20], 0.9), BoundingBox::at("Q", [280, 0, 300, 20], 0.9)];
+        let ranges = column_ranges(&boxes, 300, &layout(2));
+        assert_ranges(&ranges, &[(0.0, 0.5), (0.5, 1.0)]);
+    }
+
+    #[test]
+    fn by_suit_orders_piles_by_their_suit() {
+        let piles = strings(&["A spades", "A hearts", "null", "2 clubs"]);
+        let suits = [Some("spades".to_string()), Some("hearts".to_string()), None, Some("clubs".to_string())];
+        assert_eq!(by_suit(piles, &suits), ["A hearts", "null", "2 clubs", "A spades"]);
+    }
+
+    #[test]
+    fn by_suit_fills_places_left_with_the_rest_in_order() {
+        // the second spades pile finds its place taken
+        let piles = strings(&["A spades", "2 spades", "A clubs", "null"]);
+        let suits = [Some("spades".to_string()), Some("spades".to_string()), None, None];
+        assert_eq!(by_suit(piles, &suits), ["2 spades", "A clubs", "null", "A spades"]);
+        assert!(by_suit(Vec::new(), &[]).is_empty());
+    }
+
+    #[test]
+    fn resolve_duplicates_keeps_the_best_scoring_card() {
+        let mut cards = vec![
+            BoundingBox::at("7 hearts", [0, 0, 10, 10], 0.8),
+            BoundingBox::at("7 hearts", [0, 50, 10, 60], 0.9),
+            BoundingBox::at("8 clubs", [20, 0, 30, 10], 0.7),
+        ];
+        resolve_duplicates(&mut cards);
+        let labels: Vec<&str> = cards.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels, [UNKNOWN, "7 hearts", "8 clubs"]);
+    }
+
+    #[test]
+    fn resolve_duplicates_keeps_the_first_of_tied_cards() {
+        let mut cards = vec![BoundingBox::at("7 hearts", [0, 0, 10, 10], 0.9), BoundingBox::at("7 hearts", [0, 50, 10, 60], 0.9)];
+        resolve_duplicates(&mut cards);
+        assert_eq!((cards[0].label.as_str(), cards[1].label.as_str()), ("7 hearts", UNKNOWN));
+    }
+
+    #[test]
+    fn resolve_duplicates_leaves_bare_ranks_and_markers() {
+        let mut cards = vec![
+            BoundingBox::at("7", [0, 0, 10, 10], 0.9),
+            BoundingBox::at("7", [0, 50, 10, 60], 0.8),
+            BoundingBox::at(FACE_DOWN, [20, 0, 30, 10], 0.9),
+            BoundingBox::at(FACE_DOWN, [20, 50, 30, 60], 0.9),
+        ];
+        resolve_duplicates(&mut cards);
+        assert!(cards.iter().all(|b| b.label != UNKNOWN));
+        resolve_duplicates(&mut []);
+    }
+
+    #[test]
+    fn infer_suits_takes_the_other_color_still_free() {
+        // a red neighbour leaves clubs or spades, and the 7 of clubs is on the discard pile
+        let mut game_piles = vec![strings(&[FACE_DOWN, "7", "6 hearts"])];
+        let rank_only = infer_suits(&mut game_piles, &[], &strings(&["7 clubs"]));
+        assert_eq!(game_piles[0], [FACE_DOWN, "7 spades", "6 hearts"]);
+        assert_eq!(rank_only, Some(strings(&["7 spades"])));
+    }
+
+    #[test]
+    fn infer_suits_leaves_ranks_it_cant_tell() {
+        // both black sevens are free
+        let mut game_piles = vec![strings(&["7", "6 hearts"]), strings(&["K"])];
+        let rank_only = infer_suits(&mut game_piles, &[], &[]);
+        assert_eq!(game_piles, [strings(&["7", "6 hearts"]), strings(&["K"])]);
+        assert_eq!(rank_only, Some(strings(&["7", "K"])));
+
+        let mut no_ranks = vec![strings(&["6 hearts"]), Vec::new()];
+        assert_eq!(infer_suits(&mut no_ranks, &[], &[]), None);
+        assert_eq!(infer_suits(&mut [], &[], &[]), None);
+    }
+}
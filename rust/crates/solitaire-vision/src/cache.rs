@@ -0,0 +1,50 @@
+//! Template sets loaded once per directory and shared from then on, so
+//! batch items, daemon cycles and every detector built along the way reuse
+//! the same decoded templates instead of rereading the PNGs.
+
+use crate::detect::{self, Template};
+use crate::embedded;
+use crate::error::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::{debug, info};
+
+static CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<Vec<Template>>>>> = OnceLock::new();
+
+/// The templates in `template_dir`, loaded with [`detect::load_templates`]
+/// the first time they're asked for. The same directory under another
+/// path, like a relative one, shares the same set. Without such a
+/// directory, the [`embedded`] templates are used instead.
+pub fn load(template_dir: &str) -> Result<Arc<Vec<Template>>> {
+    // the built-in set is cached under the empty path, which no directory has
+    let builtin = !Path::new(template_dir).exists();
+    let key = if builtin {
+        PathBuf::new()
+    } else {
+        fs::canonicalize(template_dir).unwrap_or_else(|_| template_dir.into())
+    };
+    // held while loading, so sessions starting together load a set once
+    let mut cache = CACHE.get_or_init(Mutex::default).lock().expect("template cache lock poisoned");
+    if let Some(templates) = cache.get(&key) {
+        return Ok(templates.clone());
+    }
+    let templates = if builtin {
+        info!(dir = template_dir, "no template directory, using the built-in templates");
+        Arc::new(embedded::templates()?)
+    } else {
+        Arc::new(detect::load_templates(template_dir)?)
+    };
+    debug!(dir = template_dir, builtin, templates = templates.len(), "cached templates");
+    cache.insert(key, templates.clone());
+    Ok(templates)
+}
+
+/// Forgets every loaded set, so the next [`load`] of a directory rereads
+/// it, e.g. after its templates were regenerated.
+pub fn clear() {
+    if let Some(cache) = CACHE.get() {
+        cache.lock().expect("template cache lock poisoned").clear();
+    }
+}
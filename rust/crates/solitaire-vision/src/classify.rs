@@ -0,0 +1,104 @@
+//! Two-stage rank and suit detection: dark glyphs are found in one pass
+//! over the frame, then each is classified against every rank and suit
+//! template in a crop around it, instead of sweeping every template over
+//! the whole frame.
+
+use crate::config::{ClassifyConfig, DetectionConfig};
+use crate::detect::{BoundingBox, Template, SUIT_LABELS};
+use crate::error::Result;
+use opencv::core::{min_max_loc, no_array, patch_na_ns, Mat, Point, Rect, Vector};
+use opencv::imgproc::{
+    bounding_rect, find_contours, match_template, threshold, CHAIN_APPROX_SIMPLE, RETR_LIST, THRESH_BINARY_INV,
+    TM_CCOEFF_NORMED,
+};
+use opencv::prelude::*;
+use tracing::debug;
+
+/// Stage one: bounding boxes of the glyph-sized blobs of ink in the greyscale
+/// `gray`. Holes in glyphs like A and 0 are dropped in favor of the glyph.
+#[tracing::instrument(skip_all)]
+pub fn glyph_candidates(gray: &Mat, config: &ClassifyConfig) -> Result<Vec<Rect>> {
+    let mut ink = Mat::default();
+    threshold(gray, &mut ink, f64::from(config.ink_threshold), 255.0, THRESH_BINARY_INV)?;
+    // every contour, not just outer ones, as a dark background would
+    // otherwise enclose the glyphs on the cards
+    let mut contours = Vector::<Vector<Point>>::new();
+    find_contours(&ink, &mut contours, RETR_LIST, CHAIN_APPROX_SIMPLE, Point::default())?;
+
+    let rows = gray.rows() as f32;
+    let (min_height, max_height) = (config.min_height * rows, config.max_height * rows);
+    let mut glyphs = Vec::new();
+    for contour in &contours {
+        let rect = bounding_rect(&contour)?;
+        let (width, height) = (rect.width as f32, rect.height as f32);
+        if (min_height..=max_height).contains(&height) && width <= max_height {
+            glyphs.push(rect);
+        }
+    }
+    let outer: Vec<Rect> = glyphs
+        .iter()
+        .filter(|rect| !glyphs.iter().any(|other| other != *rect && (*other & **rect) == **rect))
+        .copied()
+        .collect();
+    debug!(contours = contours.len(), glyphs = outer.len(), "found glyph candidates");
+    Ok(outer)
+}
+
+/// Stage two: the rank or suit template that correlates best around each
+/// of `candidates` in `img`, boxed where it matched, if it scores at least
+/// its threshold. Being the best of every rank and suit, a box's score says
+/// how sure the label is, not just that the glyph resembles it.
+#[tracing::instrument(skip_all, fields(candidates = candidates.len()))]
+pub fn classify(img: &Mat, templates: &[Template], candidates: &[Rect], config: &DetectionConfig) -> Result<Vec<BoundingBox>> {
+    let frame = Rect::new(0, 0, img.cols(), img.rows());
+    let max_width = templates.iter().map(|t| t.image.cols()).max().unwrap_or(0);
+    let max_height = templates.iter().map(|t| t.image.rows()).max().unwrap_or(0);
+    let padding = config.classify.padding;
+
+    let mut boxes = Vec::new();
+    for candidate in candidates {
+        // room for the largest template to cover the glyph from any side
+        let x1 = candidate.x + candidate.width - max_width - padding;
+        let y1 = candidate.y + candidate.height - max_height - padding;
+        let x2 = candidate.x + max_width + padding;
+        let y2 = candidate.y + max_height + padding;
+        let area = Rect::new(x1, y1, x2 - x1, y2 - y1) & frame;
+        let crop = Mat::roi(img, area)?.try_clone()?;
+
+        let mut best: Option<(&Template, Point, f32)> = None;
+        for template in templates {
+            if template.image.cols() > crop.cols() || template.image.rows() > crop.rows() {
+                continue;
+            }
+            let no_mask = Mat::default();
+            let mut result = Mat::default();
+            match_template(&crop, &template.image, &mut result, TM_CCOEFF_NORMED, template.mask.as_ref().unwrap_or(&no_mask))?;
+            patch_na_ns(&mut result, 0.0)?;
+            let (mut score, mut location) = (0.0, Point::default());
+            min_max_loc(&result, None, Some(&mut score), None, Some(&mut location), &no_array())?;
+            let score = score as f32;
+            if best.is_none_or(|(_, _, best)| score > best) {
+                best = Some((template, location, score));
+            }
+        }
+
+        let Some((template, location, score)) = best else {
+            continue;
+        };
+        let is_suit = SUIT_LABELS.contains(&template.label.as_str());
+        if score < config.threshold_for(&template.label, is_suit) {
+            continue;
+        }
+        let (x, y) = (area.x + location.x, area.y + location.y);
+        boxes.push(BoundingBox {
+            x1: x,
+            y1: y,
+            x2: x + template.image.cols(),
+            y2: y + template.image.rows(),
+            label: template.label.clone(),
+            score,
+        });
+    }
+    debug!(classified = boxes.len(), "classified glyphs");
+    Ok(boxes)
+}
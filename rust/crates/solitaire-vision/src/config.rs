@@ -0,0 +1,759 @@
+use serde::{Deserialize, Serialize};
+use solitaire_state::{RANK_LABELS, SUIT_LABELS};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionConfig {
+    /// Minimum normalized match score for rank templates.
+    pub card_threshold: f32,
+    /// Minimum normalized match score for suit templates.
+    pub suit_threshold: f32,
+    /// Boxes overlapping a kept box by more than this fraction are suppressed.
+    pub nms_overlap_threshold: f32,
+    /// Overrides keyed by template label (e.g. `J`, `10`, `hearts`) for
+    /// templates that need a stricter or looser cutoff than their class.
+    pub template_thresholds: BTreeMap<String, f32>,
+    /// Sizes each template is matched at relative to its file, e.g.
+    /// `[0.8, 0.9, 1.0, 1.1, 1.2]` for captures at a different resolution
+    /// than the templates were cut from. Where matches at several scales
+    /// overlap, the best-scoring one is kept.
+    pub scales: Vec<f32>,
+    /// Check each suit match against the color of the frame, relabelling a
+    /// red symbol matched as a black suit and the other way round. Only
+    /// applies to color frames.
+    pub verify_suit_color: bool,
+    /// Match templates on the GPU through OpenCL, falling back to the CPU
+    /// where OpenCL isn't available.
+    pub opencl: bool,
+    /// Match templates in parallel across rayon's thread pool, sized by
+    /// `RAYON_NUM_THREADS` or the number of cores.
+    pub parallel: bool,
+    /// Estimating the screenshot's scale from one template before detection.
+    pub calibration: CalibrationConfig,
+    /// How overlapping matches are whittled down to one per location.
+    pub nms: NmsConfig,
+    /// Keypoint matching for the `feature` detector.
+    pub features: FeatureConfig,
+    /// The model run by the `onnx` detector.
+    pub onnx: OnnxConfig,
+    /// Finding card faces first and matching ranks and suits only inside them.
+    pub segmentation: SegmentationConfig,
+    /// Matching on a shrunk frame first and at full size only around what
+    /// turned up there.
+    pub pyramid: PyramidConfig,
+    /// Finding glyphs first and classifying each, instead of sweeping every
+    /// rank and suit template over the frame.
+    pub classify: ClassifyConfig,
+    /// Finding the overlay the game draws around a suggested move.
+    pub highlight: HighlightConfig,
+    /// Finding the style a card takes on once clicked, in the same way.
+    pub selection: HighlightConfig,
+    /// Second look at rank matches that only scraped past their threshold.
+    pub verification: VerificationConfig,
+    /// Settling matches between labels that are easily mistaken for each other.
+    pub confusion: ConfusionConfig,
+    /// Matching ranks of cards mostly covered by the next one in a stack.
+    pub occlusion: OcclusionConfig,
+    /// Rematching only the parts of a frame that changed since the last one.
+    pub differencing: DifferencingConfig,
+    /// Normalization applied to frames and templates alike before matching.
+    pub preprocess: PreprocessConfig,
+    /// Parts of the frame templates are matched in, or everywhere if empty.
+    /// Set from the layout's regions when `layout.mask_matching` is on.
+    #[serde(skip)]
+    pub search_regions: Vec<Region>,
+    /// Parts of the frame blanked out before matching, so nothing in them
+    /// is detected. Set from `layout.ignore_regions`.
+    #[serde(skip)]
+    pub ignored_regions: Vec<Region>,
+}
+
+/// Thresholds the frame for white card faces and keeps the outlines sized
+/// like cards. Ranks and suits are then only matched inside those, which
+/// rules out matches in the page around the game; card back and empty slot
+/// templates are still matched everywhere.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SegmentationConfig {
+    pub enabled: bool,
+    /// Greyscale level from which a pixel counts as part of a card face.
+    pub white_threshold: u8,
+    /// Smallest and largest region kept, as fractions of the frame's area.
+    pub min_area: f32,
+    pub max_area: f32,
+    /// Pixels added around each region, so glyphs on its edge still match.
+    pub padding: i32,
+}
+
+impl Default for SegmentationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            white_threshold: 200,
+            min_area: 0.002,
+            max_area: 0.3,
+            padding: 4,
+        }
+    }
+}
+
+/// Coarse-to-fine matching: frame and template are halved `levels` times,
+/// matched there at a threshold lowered by `coarse_margin`, and only the
+/// neighbourhoods of those candidates are matched at full size. Levels stop
+/// early for templates that would shrink under 8 pixels.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PyramidConfig {
+    /// Times to halve; 0 matches the whole frame at full size.
+    pub levels: u32,
+    pub coarse_margin: f32,
+    /// Full-size pixels searched around each coarse candidate.
+    pub neighborhood: i32,
+}
+
+impl Default for PyramidConfig {
+    fn default() -> Self {
+        Self {
+            levels: 0,
+            coarse_margin: 0.15,
+            neighborhood: 4,
+        }
+    }
+}
+
+/// Two-stage rank and suit detection. Stage one thresholds the frame for
+/// ink darker than `ink_threshold` and keeps the blobs between `min_height`
+/// and `max_height` of the frame's height; stage two matches every rank and
+/// suit template in a crop around each, `padding` pixels wider than the
+/// largest template, and keeps the best if it passes its threshold. Other
+/// templates are matched as usual.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClassifyConfig {
+    pub enabled: bool,
+    pub ink_threshold: u8,
+    pub min_height: f32,
+    pub max_height: f32,
+    pub padding: i32,
+}
+
+impl Default for ClassifyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ink_threshold: 140,
+            min_height: 0.01,
+            max_height: 0.06,
+            padding: 2,
+        }
+    }
+}
+
+/// Looks for the game's hint highlight by color: pixels whose OpenCV hue
+/// (0 to 180) is within `hue` and whose saturation and value are at least
+/// the minimums, in areas of at least `min_area` of the frame. The cards
+/// inside are reported as the hint. Only applies to color frames.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighlightConfig {
+    pub enabled: bool,
+    pub hue: [u8; 2],
+    pub min_saturation: u8,
+    pub min_value: u8,
+    pub min_area: f32,
+}
+
+impl Default for HighlightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hue: [20, 35],
+            min_saturation: 150,
+            min_value: 150,
+            min_area: 0.001,
+        }
+    }
+}
+
+/// Re-scores each rank match under `ambiguous_below` against every other
+/// rank template in a small crop around it, and relabels it if one of those
+/// correlates better. Catches glyphs that pass for each other at a loose
+/// threshold, like a J matched over parts of other cards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerificationConfig {
+    pub enabled: bool,
+    /// Matches scoring at least this are taken as they are.
+    pub ambiguous_below: f32,
+    /// Pixels added around the match for the others to be tried in.
+    pub padding: i32,
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ambiguous_below: 0.9,
+            padding: 3,
+        }
+    }
+}
+
+/// Pairs of labels easily mistaken for each other. Where a rank or suit's
+/// partner scores within `margin` of it, in the box grown by `padding`
+/// pixels, it's settled by color for suits of different colors, or else by
+/// how much ink the box has and where it sits, like a 6's low loop against a
+/// 9's high one. No pairs turns this off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfusionConfig {
+    pub pairs: Vec<[String; 2]>,
+    pub margin: f32,
+    pub padding: i32,
+}
+
+impl Default for ConfusionConfig {
+    fn default() -> Self {
+        Self {
+            pairs: [["6", "9"], ["K", "spades"], ["hearts", "diamonds"], ["clubs", "spades"]]
+                .map(|pair| pair.map(str::to_string))
+                .to_vec(),
+            margin: 0.05,
+            padding: 3,
+        }
+    }
+}
+
+impl ConfusionConfig {
+    /// The labels `label` is paired with.
+    pub fn partners<'a>(&'a self, label: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.pairs.iter().filter_map(move |[a, b]| {
+            if a == label {
+                Some(b.as_str())
+            } else if b == label {
+                Some(a.as_str())
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Also matches the top `visible` fraction of every rank template, for
+/// cards squeezed so tightly into a long tableau stack that the next one
+/// covers the rest of their corner, suit included. Their suits are then
+/// inferred where possible and reported in `rank_only`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OcclusionConfig {
+    pub enabled: bool,
+    pub visible: f32,
+}
+
+impl Default for OcclusionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            visible: 0.6,
+        }
+    }
+}
+
+/// Compares each frame to the one a [`Differ`](crate::diff::Differ) saw
+/// before it, split into a `tiles` x `tiles` grid, and rematches templates
+/// only in tiles where at least `min_changed` of the pixels differ by more
+/// than `pixel_threshold` grey levels, grown by `padding` of a tile so
+/// glyphs across their edges are found whole. Matches elsewhere are carried
+/// over from the last frame. Past `max_changed` of the tiles, the whole
+/// frame is matched again. Meant for the daemon and autoplay, where a move
+/// only changes a few cards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DifferencingConfig {
+    pub enabled: bool,
+    pub tiles: i32,
+    pub pixel_threshold: f64,
+    pub min_changed: f32,
+    pub max_changed: f32,
+    pub padding: f32,
+}
+
+impl Default for DifferencingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tiles: 16,
+            pixel_threshold: 24.0,
+            min_changed: 0.01,
+            max_changed: 0.5,
+            padding: 0.5,
+        }
+    }
+}
+
+/// An object detection model exported to ONNX in the YOLOv8 layout: one
+/// `[1, 3, height, width]` RGB input scaled to 0..1, and one
+/// `[1, 4 + classes, boxes]` output of center x, center y, width and height
+/// in input pixels followed by a score per class.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OnnxConfig {
+    /// Path to the `.onnx` file.
+    pub model: String,
+    /// Template label of each class, in the model's class order.
+    pub labels: Vec<String>,
+    /// Input size the model was exported with, as `[width, height]`.
+    pub input_size: [u32; 2],
+    /// Boxes whose best class scores lower are dropped.
+    pub min_score: f32,
+}
+
+impl Default for OnnxConfig {
+    fn default() -> Self {
+        Self {
+            model: "model.onnx".to_string(),
+            labels: RANK_LABELS.iter().chain(&SUIT_LABELS).map(|label| label.to_string()).collect(),
+            input_size: [640, 640],
+            min_score: 0.5,
+        }
+    }
+}
+
+/// Finds templates by their keypoints instead of pixel by pixel, which holds
+/// up better to scaling and antialiasing. A match counts when enough of its
+/// keypoints agree on one homography.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeatureConfig {
+    pub algorithm: FeatureAlgorithm,
+    /// Keypoints ORB keeps per frame; a whole board has far more than one card.
+    pub max_features: i32,
+    /// A keypoint only matches when its best template descriptor is closer
+    /// than this fraction of the distance to the second best.
+    pub ratio: f32,
+    /// Keypoints that must agree on a homography for a template to be found.
+    pub min_inliers: usize,
+    /// How far in pixels a keypoint may land from where the homography puts
+    /// it and still count as agreeing.
+    pub ransac_threshold: f64,
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: FeatureAlgorithm::Orb,
+            max_features: 5000,
+            ratio: 0.75,
+            min_inliers: 6,
+            ransac_threshold: 3.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeatureAlgorithm {
+    Orb,
+    Akaze,
+}
+
+/// Non-maximum suppression: the best-scoring box is kept and the boxes it
+/// overlaps are dropped, or with [`NmsMode::Soft`] have their scores decayed,
+/// until none are left.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NmsConfig {
+    pub mode: NmsMode,
+    /// Only let boxes suppress others with the same label, so e.g. a rank
+    /// never knocks out a different rank overlapping it.
+    pub per_class: bool,
+    /// Width of Soft-NMS's gaussian decay; smaller punishes overlap harder.
+    pub sigma: f32,
+    /// Soft-NMS drops boxes whose decayed score falls below this.
+    pub min_score: f32,
+    /// Overrides of `nms_overlap_threshold` for one class of box. Suits
+    /// usually want a tighter one than ranks, since pips in the card body
+    /// match them too.
+    pub overlap_thresholds: BTreeMap<BoxClass, f32>,
+}
+
+impl Default for NmsConfig {
+    fn default() -> Self {
+        Self {
+            mode: NmsMode::Hard,
+            per_class: false,
+            sigma: 0.5,
+            min_score: 0.5,
+            overlap_thresholds: BTreeMap::new(),
+        }
+    }
+}
+
+/// The kinds of box suppressed separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoxClass {
+    Rank,
+    Suit,
+    Back,
+    Empty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NmsMode {
+    /// Drop boxes overlapping a kept one by more than the overlap threshold.
+    Hard,
+    /// Scale each overlapping box's score by `exp(-overlap² / sigma)`
+    /// instead, so boxes that only touch a kept one survive.
+    Soft,
+}
+
+/// Finds how much bigger or smaller cards are on screen than in the template
+/// files by matching one reference template across a range of scales, then
+/// resizes every template by that factor once. Much cheaper than matching
+/// every template at several [`scales`](DetectionConfig::scales).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalibrationConfig {
+    pub enabled: bool,
+    /// Label of the template to calibrate with; a suit symbol shows up on
+    /// nearly every board.
+    pub reference: String,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    /// Scales tried between `min_scale` and `max_scale`, both included.
+    pub steps: u32,
+    /// Below this best score the reference counts as not found and templates
+    /// are left as they are.
+    pub min_score: f32,
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reference: "spades".to_string(),
+            min_scale: 0.5,
+            max_scale: 2.0,
+            steps: 31,
+            min_score: 0.7,
+        }
+    }
+}
+
+impl CalibrationConfig {
+    /// The scales swept, smallest first.
+    pub fn scales(&self) -> impl Iterator<Item = f32> + '_ {
+        let steps = self.steps.max(2);
+        (0..steps).map(move |i| self.min_scale + (self.max_scale - self.min_scale) * i as f32 / (steps - 1) as f32)
+    }
+}
+
+impl DetectionConfig {
+    /// Match threshold for the template labelled `label`.
+    pub fn threshold_for(&self, label: &str, is_suit: bool) -> f32 {
+        match self.template_thresholds.get(label) {
+            Some(&threshold) => threshold,
+            None if is_suit => self.suit_threshold,
+            None => self.card_threshold,
+        }
+    }
+
+    /// Suppression overlap threshold for boxes of `class`.
+    pub fn overlap_threshold_for(&self, class: BoxClass) -> f32 {
+        self.nms
+            .overlap_thresholds
+            .get(&class)
+            .copied()
+            .unwrap_or(self.nms_overlap_threshold)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutConfig {
+    /// Number of columns the board is split into: the first is the draw
+    /// pile, the last the discard piles, the rest tableau columns.
+    pub columns: usize,
+    /// Cluster the detected cards' x centers into `columns` piles instead,
+    /// so games that aren't centered as the equal split expects, or frames
+    /// with a scrollbar, still put each card in its pile.
+    pub auto_columns: bool,
+    /// Vertical distance in pixels between consecutive cards of a stack.
+    pub y_range_step: i32,
+    /// Measure that distance in each tableau pile from its detected cards,
+    /// falling back to `y_range_step` for piles with fewer than two.
+    pub auto_y_step: bool,
+    /// Y coordinate of the top of the first card in a tableau column.
+    pub starting_y: i32,
+    /// Cards dealt to the waste at a time: 1 for easy games, 3 for hard ones,
+    /// where the waste shows its top three cards fanned out.
+    pub draw_count: usize,
+    /// Explicit pile regions. When set, these replace the equal-width columns.
+    pub regions: Option<LayoutDescriptor>,
+    /// Only match templates inside `regions`, so nothing outside the piles
+    /// can be mistaken for a card. Has no effect without `regions`.
+    pub mask_matching: bool,
+    /// Parts of the page that are never the board, like the toolbar, its
+    /// buttons and the logo, blanked out before matching.
+    pub ignore_regions: Vec<Region>,
+    /// Order `discard_pile` hearts, diamonds, clubs, spades by each
+    /// foundation's suit, known from its cards or from the suit icon of an
+    /// empty one, instead of by where the foundations sit.
+    pub foundations_by_suit: bool,
+    /// Estimating how many cards the stock has left.
+    pub stock: StockConfig,
+    /// Reading the score, time and moves from the game's header.
+    pub stats: StatsConfig,
+}
+
+/// Where the game's header shows its numbers, and the digit templates to
+/// read them with. Numbers without a region aren't read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsConfig {
+    pub score: Option<Region>,
+    pub time: Option<Region>,
+    pub moves: Option<Region>,
+    /// Directory holding `0.png` to `9.png`, cut from the header's font.
+    pub digits_dir: String,
+    /// Minimum normalized match score for a digit.
+    pub threshold: f32,
+}
+
+impl StatsConfig {
+    /// Whether any number has a region to be read from.
+    pub fn is_enabled(&self) -> bool {
+        self.score.is_some() || self.time.is_some() || self.moves.is_some()
+    }
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            score: None,
+            time: None,
+            moves: None,
+            digits_dir: "templates/digits".to_string(),
+            threshold: 0.8,
+        }
+    }
+}
+
+/// For UIs that draw the stock as a stack, with the border of each card
+/// under the top one showing below it. The stack's edges are counted down a
+/// line from the bottom of the detected back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StockConfig {
+    pub enabled: bool,
+    /// Pixels from the bottom of the back's box to where the stack's edges
+    /// start, e.g. the rest of the card's height if `back.png` is cut from
+    /// its top strip.
+    pub start_offset: i32,
+    /// Pixels scanned for edges.
+    pub max_depth: i32,
+    /// Drop in greyscale level that counts as an edge.
+    pub min_contrast: u8,
+    /// Cards each visible edge stands for, for UIs that draw one per few cards.
+    pub cards_per_edge: u32,
+}
+
+impl Default for StockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start_offset: 0,
+            max_depth: 24,
+            min_contrast: 40,
+            cards_per_edge: 1,
+        }
+    }
+}
+
+/// Steps run, in this order, on the greyscale frame and every template
+/// before they're matched. All are off by default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreprocessConfig {
+    pub equalize: Equalization,
+    /// Contrast limit for [`Equalization::Clahe`].
+    pub clahe_clip_limit: f64,
+    /// Side in tiles of the grid [`Equalization::Clahe`] equalizes separately.
+    pub clahe_tile_size: i32,
+    /// Side of the Gaussian blur kernel in pixels, or 0 for no blur.
+    pub blur: i32,
+    /// Match Canny edge maps with these low and high thresholds instead of
+    /// grey levels.
+    pub edges: Option<[f64; 2]>,
+}
+
+impl Default for PreprocessConfig {
+    fn default() -> Self {
+        Self {
+            equalize: Equalization::None,
+            clahe_clip_limit: 2.0,
+            clahe_tile_size: 8,
+            blur: 0,
+            edges: None,
+        }
+    }
+}
+
+impl PreprocessConfig {
+    /// Whether any step is on.
+    pub fn is_enabled(&self) -> bool {
+        self.equalize != Equalization::None || self.blur > 1 || self.edges.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Equalization {
+    #[default]
+    None,
+    /// Stretch the histogram of the whole image.
+    Histogram,
+    /// Contrast-limited adaptive equalization, tile by tile.
+    Clahe,
+}
+
+/// Warping photos of a physical game to a top-down view before detection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PerspectiveConfig {
+    pub enabled: bool,
+    /// Smallest area the board's outline may cover, as a fraction of the photo.
+    pub min_area: f32,
+    /// Size of the straightened board as `[width, height]`, which should be
+    /// the resolution the templates were made at.
+    pub size: [u32; 2],
+}
+
+impl Default for PerspectiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_area: 0.2,
+            size: [1920, 1080],
+        }
+    }
+}
+
+/// A rectangle in fractions of the frame's width and height, written in
+/// config as `[x1, y1, x2, y2]`, so one layout fits any resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(from = "[f32; 4]", into = "[f32; 4]")]
+pub struct Region {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+}
+
+impl Region {
+    /// Whether the point at fractions `x`, `y` of the frame lies inside.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.x1 <= x && x < self.x2 && self.y1 <= y && y < self.y2
+    }
+}
+
+impl LayoutDescriptor {
+    /// Every pile's region.
+    pub fn all(&self) -> impl Iterator<Item = Region> + '_ {
+        std::iter::once(self.stock)
+            .chain(self.waste)
+            .chain(self.foundations.iter().copied())
+            .chain(self.tableau.iter().copied())
+    }
+}
+
+impl LayoutConfig {
+    /// The regions templates should be matched in: the piles' when masking
+    /// is on, otherwise none, meaning the whole frame.
+    pub fn search_regions(&self) -> Vec<Region> {
+        match &self.regions {
+            Some(regions) if self.mask_matching => regions.all().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl From<[f32; 4]> for Region {
+    fn from([x1, y1, x2, y2]: [f32; 4]) -> Self {
+        Self { x1, y1, x2, y2 }
+    }
+}
+
+impl From<Region> for [f32; 4] {
+    fn from(region: Region) -> Self {
+        [region.x1, region.y1, region.x2, region.y2]
+    }
+}
+
+/// Where each pile sits on the board, for UIs that don't follow the default
+/// column layout. Cards in the stock and waste both go to the draw pile.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutDescriptor {
+    pub stock: Region,
+    pub waste: Option<Region>,
+    /// One region per foundation, in `discard_pile` order.
+    pub foundations: Vec<Region>,
+    /// One region per tableau column, left to right.
+    pub tableau: Vec<Region>,
+}
+
+impl Default for DetectionConfig {
+    fn default() -> Self {
+        Self {
+            card_threshold: 0.79,
+            suit_threshold: 0.85,
+            nms_overlap_threshold: 0.5,
+            template_thresholds: BTreeMap::new(),
+            scales: vec![1.0],
+            verify_suit_color: true,
+            opencl: false,
+            parallel: true,
+            calibration: CalibrationConfig::default(),
+            nms: NmsConfig::default(),
+            features: FeatureConfig::default(),
+            onnx: OnnxConfig::default(),
+            segmentation: SegmentationConfig::default(),
+            pyramid: PyramidConfig::default(),
+            classify: ClassifyConfig::default(),
+            highlight: HighlightConfig::default(),
+            selection: HighlightConfig {
+                hue: [95, 125],
+                ..HighlightConfig::default()
+            },
+            verification: VerificationConfig::default(),
+            confusion: ConfusionConfig::default(),
+            occlusion: OcclusionConfig::default(),
+            differencing: DifferencingConfig::default(),
+            preprocess: PreprocessConfig::default(),
+            search_regions: Vec::new(),
+            ignored_regions: Vec::new(),
+        }
+    }
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            columns: 9,
+            auto_columns: true,
+            y_range_step: 40,
+            auto_y_step: false,
+            starting_y: 75,
+            draw_count: 1,
+            regions: None,
+            mask_matching: false,
+            ignore_regions: Vec::new(),
+            foundations_by_suit: false,
+            stock: StockConfig::default(),
+            stats: StatsConfig::default(),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Settles ranks and suits between labels that are easily mistaken for each
+//! other, like 6 and 9, when both correlate about as well.
+
+use crate::config::DetectionConfig;
+use crate::detect::{self, BoundingBox, Detections, Template, SUIT_LABELS};
+use crate::error::Result;
+use opencv::core::{min_max_loc, no_array, patch_na_ns, Mat, Rect, Size};
+use opencv::imgproc::{match_template, moments, resize, threshold, INTER_AREA, THRESH_BINARY_INV, THRESH_OTSU, TM_CCOEFF_NORMED};
+use opencv::prelude::*;
+use tracing::debug;
+
+/// Checks every rank and suit box in `detections` against the labels it's
+/// paired with in `config.confusion`, and where one scores within the margin
+/// of it around the box, relabels the box as whichever of the two the BGR
+/// `img` or its greyscale `gray` favors. A box can move between ranks and
+/// suits this way.
+#[tracing::instrument(skip_all)]
+pub fn disambiguate(
+    img: &Mat,
+    gray: &Mat,
+    templates: &[Template],
+    detections: &mut Detections,
+    config: &DetectionConfig,
+) -> Result<()> {
+    let confusion = &config.confusion;
+    let frame = Rect::new(0, 0, gray.cols(), gray.rows());
+    let template = |label: &str| templates.iter().find(|t| t.label == label);
+
+    let mut boxes: Vec<BoundingBox> = detections.cards.drain(..).chain(detections.suits.drain(..)).collect();
+    for found in &mut boxes {
+        let Some(own) = template(&found.label) else {
+            continue;
+        };
+        let label = found.label.clone();
+        for partner in confusion.partners(&label) {
+            let Some(other) = template(partner) else {
+                continue;
+            };
+            // tried at the size the box was matched at
+            let scale = (found.x2 - found.x1) as f32 / own.image.cols() as f32;
+            let padding = confusion.padding;
+            let area = Rect::new(
+                found.x1 - padding,
+                found.y1 - padding,
+                found.x2 - found.x1 + 2 * padding,
+                found.y2 - found.y1 + 2 * padding,
+            ) & frame;
+            let crop = Mat::roi(gray, area)?.try_clone()?;
+            let Some(image) = detect::resize_template(&other.image, scale, &crop)? else {
+                continue;
+            };
+            let mask = match &other.mask {
+                Some(mask) => detect::resize_mask(mask, image.size()?)?,
+                None => Mat::default(),
+            };
+            let mut result = Mat::default();
+            match_template(&crop, &image, &mut result, TM_CCOEFF_NORMED, &mask)?;
+            patch_na_ns(&mut result, 0.0)?;
+            let mut score = 0.0;
+            min_max_loc(&result, None, Some(&mut score), None, None, &no_array())?;
+            let score = score as f32;
+
+            let margin = found.score - score;
+            debug!(label, runner_up = partner, margin, x1 = found.x1, y1 = found.y1, "confusable match");
+            if margin.abs() >= confusion.margin {
+                continue;
+            }
+            let winner = tie_break(img, gray, found, own, other)?;
+            if winner.label != label {
+                debug!(from = label, to = %winner.label, "settled confusion");
+                found.label = winner.label.clone();
+                found.score = score;
+            }
+            break;
+        }
+    }
+
+    for found in boxes {
+        if SUIT_LABELS.contains(&found.label.as_str()) {
+            detections.suits.push(found);
+        } else {
+            detections.cards.push(found);
+        }
+    }
+    // back in suppression's order, which only moved boxes are out of
+    for boxes in [&mut detections.cards, &mut detections.suits] {
+        boxes.sort_by(|a, b| a.y2.cmp(&b.y2).then(b.score.total_cmp(&a.score)));
+    }
+    Ok(())
+}
+
+// which of `a` and `b` the glyph in `found` is: by color for suits of
+// different colors, otherwise by how much ink it has and where it sits, which
+// tells a 6's low loop from a 9's high one
+fn tie_break<'a>(img: &Mat, gray: &Mat, found: &BoundingBox, a: &'a Template, b: &'a Template) -> Result<&'a Template> {
+    let is_red = |template: &Template| matches!(template.label.as_str(), "hearts" | "diamonds");
+    let both_suits = [a, b].iter().all(|t| SUIT_LABELS.contains(&t.label.as_str()));
+    if both_suits && is_red(a) != is_red(b) && img.channels() == 3 {
+        if let Some(red) = detect::is_red(img, found)? {
+            return Ok(if is_red(a) == red { a } else { b });
+        }
+    }
+
+    let rect = Rect::new(found.x1, found.y1, found.x2 - found.x1, found.y2 - found.y1)
+        & Rect::new(0, 0, gray.cols(), gray.rows());
+    let size = rect.size();
+    let glyph = ink_profile(&Mat::roi(gray, rect)?.try_clone()?)?;
+    let distance = |template: &Template| -> Result<f64> {
+        let mut resized = Mat::default();
+        resize(&template.image, &mut resized, size, 0.0, 0.0, INTER_AREA)?;
+        let profile = ink_profile(&resized)?;
+        Ok(glyph.iter().zip(profile).map(|(g, t)| (g - t).powi(2)).sum())
+    };
+    Ok(if distance(a)? <= distance(b)? { a } else { b })
+}
+
+// where the ink of the greyscale `img` is centered, as fractions of its width
+// and height, and the fraction of it that's ink
+fn ink_profile(img: &Mat) -> Result<[f64; 3]> {
+    let mut ink = Mat::default();
+    threshold(img, &mut ink, 0.0, 255.0, THRESH_BINARY_INV | THRESH_OTSU)?;
+    let m = moments(&ink, true)?;
+    if m.m00 == 0.0 {
+        return Ok([0.5, 0.5, 0.0]);
+    }
+    let Size { width, height } = img.size()?;
+    Ok([
+        m.m10 / m.m00 / f64::from(width),
+        m.m01 / m.m00 / f64::from(height),
+        m.m00 / f64::from(width * height),
+    ])
+}
@@ -0,0 +1,1287 @@
+use opencv::core::{
+    count_non_zero, extract_channel, min_max_loc, no_array, patch_na_ns, Mat, Point, Scalar, Rect, Size, Vec3b, Vector,
+    CV_8U,
+};
+use opencv::imgcodecs::{imdecode, imencode, imread, imwrite, IMREAD_COLOR, IMREAD_UNCHANGED};
+use opencv::imgproc::{
+    bounding_rect, cvt_color, dilate_def, find_contours, get_structuring_element_def, get_text_size, match_template, put_text, pyr_down_def, rectangle, resize,
+    CHAIN_APPROX_SIMPLE, COLOR_BGR2GRAY, FILLED, FONT_HERSHEY_SIMPLEX, INTER_AREA, INTER_LINEAR, INTER_NEAREST, LINE_8, MORPH_RECT,
+    RETR_EXTERNAL, THRESH_BINARY, TM_CCOEFF_NORMED,
+};
+use opencv::prelude::*;
+use rayon::prelude::*;
+use crate::classify;
+use crate::confusion;
+use crate::config::{
+    BoxClass, CalibrationConfig, DetectionConfig, NmsConfig, NmsMode, PreprocessConfig, PyramidConfig, Region,
+};
+use crate::error::{Result, VisionError};
+use crate::highlight;
+use crate::opencl;
+use crate::preprocess;
+use crate::segment;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tracing::{debug, debug_span};
+
+pub use solitaire_state::{FACE_DOWN, RANK_LABELS, SUIT_LABELS, UNKNOWN};
+
+// smallest side a template is shrunk to for coarse matching
+const MIN_PYRAMID_SIZE: i32 = 8;
+
+/// Label of the optional template cut from the visible strip of a face-down card.
+pub const BACK_LABEL: &str = "back";
+/// Label of the optional template of the placeholder an empty pile shows.
+pub const EMPTY_LABEL: &str = "empty";
+/// Labels of the optional templates of what the game shows once it's over:
+/// its win screen or "Play again" button, and its game-over message.
+pub const WON_LABEL: &str = "won";
+pub const LOST_LABEL: &str = "lost";
+/// Prefix of the optional templates of the suit icons empty foundations
+/// show, like `foundation_hearts`.
+pub const FOUNDATION_PREFIX: &str = "foundation_";
+/// Labels of the optional templates of the two digits of a 10, matched on
+/// their own and paired up for 10s the wide template misses.
+pub const TEN_DIGITS: [&str; 2] = ["1", "0"];
+
+#[derive(Debug, Clone)]
+pub struct BoundingBox {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
+    pub label: String,
+    /// Normalized match score, from the threshold up to 1.
+    pub score: f32,
+}
+
+#[cfg(test)]
+impl BoundingBox {
+    /// A box with corners `[x1, y1, x2, y2]`, for tests.
+    pub(crate) fn at(label: &str, [x1, y1, x2, y2]: [i32; 4], score: f32) -> Self {
+        Self {
+            x1,
+            y1,
+            x2,
+            y2,
+            label: label.to_string(),
+            score,
+        }
+    }
+}
+
+/// A rank or suit template image, labelled by its file name.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub label: String,
+    pub image: Mat,
+    /// Non-zero where pixels count towards the score, from the PNG's alpha
+    /// channel, so transparent corners and background don't drag it down.
+    pub mask: Option<Mat>,
+}
+
+/// Rank, suit and card back boxes left over after non-maximum suppression.
+#[derive(Debug, Clone, Default)]
+pub struct Detections {
+    pub cards: Vec<BoundingBox>,
+    pub suits: Vec<BoundingBox>,
+    /// Face-down cards, if the template set has a [`BACK_LABEL`] template.
+    pub backs: Vec<BoundingBox>,
+    /// Placeholders of empty piles, or `None` if the template set has no
+    /// [`EMPTY_LABEL`] template and an empty pile can't be told apart from
+    /// one that wasn't detected.
+    pub empty_slots: Option<Vec<BoundingBox>>,
+    /// Suit icons of empty foundations, labelled `foundation_<suit>`.
+    pub foundation_slots: Vec<BoundingBox>,
+    /// Win or game-over screens, labelled [`WON_LABEL`] or [`LOST_LABEL`].
+    pub end_screens: Vec<BoundingBox>,
+    /// Areas the game highlights to suggest a move.
+    pub highlights: Vec<BoundingBox>,
+    /// Areas styled as selected by a click.
+    pub selected: Vec<BoundingBox>,
+}
+
+/// The suit a `foundation_<suit>` label stands for.
+pub fn foundation_suit(label: &str) -> Option<&str> {
+    label
+        .strip_prefix(FOUNDATION_PREFIX)
+        .filter(|suit| SUIT_LABELS.contains(suit))
+}
+
+/// Matches every template against `img` and returns the filtered rank and suit boxes.
+pub fn detect(img: &Mat, templates: &[Template], config: &DetectionConfig) -> Result<Detections> {
+    detect_observed(img, templates, config, &|_| {})
+}
+
+/// Same as [`detect`], calling `on_template` after each template has been matched.
+pub fn detect_observed(
+    img: &Mat,
+    templates: &[Template],
+    config: &DetectionConfig,
+    on_template: &(dyn Fn(&Template) + Sync),
+) -> Result<Detections> {
+    let prepared = prepare_templates(templates, &config.preprocess)?;
+    detect_prepared(img, templates, &prepared, config, on_template)
+}
+
+/// Same as [`detect_observed`], with `prepared` being `templates` as
+/// [`prepare_templates`] returns them, so they can be prepared once for
+/// many frames.
+#[tracing::instrument(skip_all, fields(templates = templates.len()))]
+pub fn detect_prepared(
+    img: &Mat,
+    templates: &[Template],
+    prepared: &[Template],
+    config: &DetectionConfig,
+    on_template: &(dyn Fn(&Template) + Sync),
+) -> Result<Detections> {
+    opencl::configure(config.opencl);
+    let gray = to_gray(img)?;
+    let matches = match_prepared_templates(&gray, prepared, config, on_template)?;
+    let mut detections = matches.into_detections(img, config)?;
+    if config.verification.enabled {
+        verify_ranks(&gray, templates, &mut detections.cards, config)?;
+    }
+    if !config.confusion.pairs.is_empty() {
+        confusion::disambiguate(img, &gray, templates, &mut detections, config)?;
+    }
+    Ok(detections)
+}
+
+/// Re-scores the rank boxes in `cards` scoring under the verification
+/// cutoff against every rank template in a crop of the greyscale `img`
+/// around each, keeping whichever label correlates best there.
+#[tracing::instrument(skip_all)]
+pub fn verify_ranks(img: &Mat, templates: &[Template], cards: &mut [BoundingBox], config: &DetectionConfig) -> Result<()> {
+    let verification = &config.verification;
+    let ranks: Vec<&Template> = templates
+        .iter()
+        .filter(|t| RANK_LABELS.contains(&t.label.as_str()))
+        .collect();
+    let frame = Rect::new(0, 0, img.cols(), img.rows());
+
+    for card in cards.iter_mut().filter(|b| b.score < verification.ambiguous_below) {
+        let Some(own) = ranks.iter().find(|t| t.label == card.label) else {
+            continue;
+        };
+        // the size the box was matched at, for the other templates to be tried at too
+        let scale = (card.x2 - card.x1) as f32 / own.image.cols() as f32;
+        let padding = verification.padding;
+        let area = Rect::new(
+            card.x1 - padding,
+            card.y1 - padding,
+            card.x2 - card.x1 + 2 * padding,
+            card.y2 - card.y1 + 2 * padding,
+        ) & frame;
+        let crop = Mat::roi(img, area)?.try_clone()?;
+
+        let mut best: Option<(&str, f32)> = None;
+        for template in &ranks {
+            let Some(image) = resize_template(&template.image, scale, &crop)? else {
+                continue;
+            };
+            let mask = match &template.mask {
+                Some(mask) => resize_mask(mask, image.size()?)?,
+                None => Mat::default(),
+            };
+            let mut result = Mat::default();
+            match_template(&crop, &image, &mut result, TM_CCOEFF_NORMED, &mask)?;
+            patch_na_ns(&mut result, 0.0)?;
+            let mut score = 0.0;
+            min_max_loc(&result, None, Some(&mut score), None, None, &no_array())?;
+            let score = score as f32;
+            if best.is_none_or(|(_, best)| score > best) {
+                best = Some((&template.label, score));
+            }
+        }
+
+        if let Some((label, score)) = best {
+            if label != card.label {
+                debug!(from = %card.label, to = label, score, x1 = card.x1, y1 = card.y1, "relabelled rank");
+                card.label = label.to_string();
+                card.score = score;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Every match of every template in the greyscale `img`, before overlapping
+/// ones are suppressed, calling `on_template` after each template.
+pub fn match_templates(
+    img: &Mat,
+    templates: &[Template],
+    config: &DetectionConfig,
+    on_template: &(dyn Fn(&Template) + Sync),
+) -> Result<Matches> {
+    let prepared = prepare_templates(templates, &config.preprocess)?;
+    match_prepared_templates(img, &prepared, config, on_template)
+}
+
+/// `templates` run through `config`'s normalization, as frames are before
+/// they're matched, or the same templates if it's off.
+pub fn prepare_templates<'a>(templates: &'a [Template], config: &PreprocessConfig) -> Result<Cow<'a, [Template]>> {
+    if !config.is_enabled() {
+        return Ok(Cow::Borrowed(templates));
+    }
+    templates
+        .iter()
+        .map(|template| {
+            Ok(Template {
+                label: template.label.clone(),
+                image: preprocess::preprocess(&template.image, config)?,
+                mask: template.mask.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(Cow::Owned)
+}
+
+/// Same as [`match_templates`], with `templates` already run through
+/// [`prepare_templates`].
+pub fn match_prepared_templates(
+    img: &Mat,
+    templates: &[Template],
+    config: &DetectionConfig,
+    on_template: &(dyn Fn(&Template) + Sync),
+) -> Result<Matches> {
+    let mut matches = Matches::default();
+    let frame = Rect::new(0, 0, img.cols(), img.rows());
+    let blanked;
+    let img = if config.ignored_regions.is_empty() {
+        img
+    } else {
+        blanked = blank_regions(img, &config.ignored_regions)?;
+        &blanked
+    };
+    let searched: Vec<Rect> = match config.search_regions.as_slice() {
+        [] => vec![frame],
+        regions => regions.iter().map(|region| region_rect(region, frame)).collect(),
+    };
+    // card faces, within the searched regions
+    let faces: Vec<Rect> = if config.segmentation.enabled {
+        let cards = segment::card_regions(img, &config.segmentation)?;
+        cards
+            .iter()
+            .flat_map(|card| searched.iter().map(move |area| *card & *area))
+            .filter(|rect| !rect.empty())
+            .collect()
+    } else {
+        searched.clone()
+    };
+    // glyphs are told apart from the card by the frame's own grey levels too
+    let glyphs = if config.classify.enabled {
+        let glyphs = classify::glyph_candidates(img, &config.classify)?;
+        let centered = |glyph: &Rect| {
+            let center = Point::new(glyph.x + glyph.width / 2, glyph.y + glyph.height / 2);
+            faces.iter().any(|face| face.contains(center))
+        };
+        glyphs.into_iter().filter(centered).collect()
+    } else {
+        Vec::new()
+    };
+    // segmentation above wants the frame's own grey levels
+    let processed;
+    let img = if config.preprocess.is_enabled() {
+        processed = preprocess::preprocess(img, &config.preprocess)?;
+        &processed
+    } else {
+        img
+    };
+
+    let classified = |template: &Template| {
+        config.classify.enabled
+            && (RANK_LABELS.contains(&template.label.as_str()) || SUIT_LABELS.contains(&template.label.as_str()))
+    };
+    if config.classify.enabled {
+        let faces: Vec<Template> = templates.iter().filter(|t| classified(t)).cloned().collect();
+        for found in classify::classify(img, &faces, &glyphs, config)? {
+            matches.push(found);
+        }
+        faces.iter().for_each(on_template);
+    }
+
+    let slices = if config.occlusion.enabled {
+        rank_slices(templates, config.occlusion.visible)?
+    } else {
+        Vec::new()
+    };
+    let jobs: Vec<(usize, &Template)> = templates.iter().chain(&slices).enumerate().filter(|(_, t)| !classified(t)).collect();
+    let match_one = |&(i, template): &(usize, &Template)| -> Result<Vec<BoundingBox>> {
+        // match card values and suits with different thresholds for accuracy
+        let is_suit = SUIT_LABELS.contains(&template.label.as_str());
+        let threshold = config.threshold_for(&template.label, is_suit);
+        let _span = debug_span!("match_template", label = %template.label, threshold).entered();
+
+        let is_face = template.label != BACK_LABEL
+            && template.label != EMPTY_LABEL
+            && foundation_suit(&template.label).is_none();
+        // end screens can show anywhere, not just over the piles
+        let whole_frame = [frame];
+        let areas: &[Rect] = if template.label == WON_LABEL || template.label == LOST_LABEL {
+            &whole_frame
+        } else if is_face {
+            &faces
+        } else {
+            &searched
+        };
+        let boxes = match areas {
+            [area] if *area == frame => match_template_boxes(img, template, threshold, config)?,
+            areas => {
+                let mut boxes = Vec::new();
+                for area in areas {
+                    if area.width < template.image.cols() || area.height < template.image.rows() {
+                        continue;
+                    }
+                    let roi = Mat::roi(img, *area)?.try_clone()?;
+                    boxes.extend(match_template_boxes(&roi, template, threshold, config)?.into_iter().map(|b| {
+                        BoundingBox {
+                            x1: b.x1 + area.x,
+                            y1: b.y1 + area.y,
+                            x2: b.x2 + area.x,
+                            y2: b.y2 + area.y,
+                            ..b
+                        }
+                    }));
+                }
+                boxes
+            }
+        };
+        debug!(matches = boxes.len(), "template matched");
+
+        // slices don't count as templates of their own
+        if i < templates.len() {
+            on_template(template);
+        }
+        Ok(boxes)
+    };
+    // each template is matched on its own, and the results gathered in order
+    let found: Vec<Vec<BoundingBox>> = if config.parallel {
+        jobs.par_iter().map(match_one).collect::<Result<_>>()?
+    } else {
+        jobs.iter().map(match_one).collect::<Result<_>>()?
+    };
+    for ((_, template), boxes) in jobs.iter().zip(found) {
+        matches.add(&template.label, boxes);
+    }
+
+    Ok(matches)
+}
+
+// a copy of `img` with `regions` filled in black, which no template scores
+// well against
+fn blank_regions(img: &Mat, regions: &[Region]) -> Result<Mat> {
+    let frame = Rect::new(0, 0, img.cols(), img.rows());
+    let mut blanked = img.try_clone()?;
+    for region in regions {
+        rectangle(&mut blanked, region_rect(region, frame), Scalar::all(0.0), FILLED, LINE_8, 0)?;
+    }
+    Ok(blanked)
+}
+
+// the top `visible` fraction of each rank template, as much of its corner as
+// shows of a card squeezed into a long tableau stack
+fn rank_slices(templates: &[Template], visible: f32) -> Result<Vec<Template>> {
+    templates
+        .iter()
+        .filter(|template| RANK_LABELS.contains(&template.label.as_str()))
+        .map(|template| {
+            let rows = ((template.image.rows() as f32 * visible).round() as i32).clamp(1, template.image.rows());
+            let rect = Rect::new(0, 0, template.image.cols(), rows);
+            let mask = match &template.mask {
+                Some(mask) => Some(Mat::roi(mask, rect)?.try_clone()?),
+                None => None,
+            };
+            Ok(Template {
+                label: template.label.clone(),
+                image: Mat::roi(&template.image, rect)?.try_clone()?,
+                mask,
+            })
+        })
+        .collect()
+}
+
+/// `region` in pixels of `frame`.
+pub(crate) fn region_rect(region: &Region, frame: Rect) -> Rect {
+    let x1 = (region.x1 * frame.width as f32).floor() as i32;
+    let y1 = (region.y1 * frame.height as f32).floor() as i32;
+    let x2 = (region.x2 * frame.width as f32).ceil() as i32;
+    let y2 = (region.y2 * frame.height as f32).ceil() as i32;
+    Rect::new(x1, y1, x2 - x1, y2 - y1) & frame
+}
+
+// every match of `template` in `img`, at each of `scales` if there's more than one
+fn match_template_boxes(img: &Mat, template: &Template, threshold: f32, config: &DetectionConfig) -> Result<Vec<BoundingBox>> {
+    match config.scales.as_slice() {
+        [] | [1.0] => {
+            let matches = match_template_pyramid(img, &template.image, template.mask.as_ref(), threshold, &config.pyramid)?;
+            Ok(create_bounding_boxes(
+                matches,
+                template.image.cols(),
+                template.image.rows(),
+                template.label.clone(),
+            ))
+        }
+        scales => match_template_scaled(img, template, threshold, scales, &config.pyramid),
+    }
+}
+
+/// Every box found in a frame, sorted by kind but not yet suppressed.
+#[derive(Debug, Clone, Default)]
+pub struct Matches {
+    cards: Vec<BoundingBox>,
+    suits: Vec<BoundingBox>,
+    backs: Vec<BoundingBox>,
+    empty_slots: Option<Vec<BoundingBox>>,
+    foundation_slots: Vec<BoundingBox>,
+    end_screens: Vec<BoundingBox>,
+    // the 1s and 0s of tens
+    digits: Vec<BoundingBox>,
+}
+
+impl Matches {
+    /// Adds the boxes found for the template labelled `label`.
+    pub fn add(&mut self, label: &str, boxes: Vec<BoundingBox>) {
+        self.boxes_for(label).extend(boxes);
+    }
+
+    /// Adds one box, going by its own label.
+    pub fn push(&mut self, found: BoundingBox) {
+        self.boxes_for(&found.label).push(found);
+    }
+
+    fn boxes_for(&mut self, label: &str) -> &mut Vec<BoundingBox> {
+        if SUIT_LABELS.contains(&label) {
+            &mut self.suits
+        } else if label == BACK_LABEL {
+            &mut self.backs
+        } else if label == EMPTY_LABEL {
+            self.empty_slots.get_or_insert_with(Vec::new)
+        } else if foundation_suit(label).is_some() {
+            &mut self.foundation_slots
+        } else if label == WON_LABEL || label == LOST_LABEL {
+            &mut self.end_screens
+        } else if TEN_DIGITS.contains(&label) {
+            &mut self.digits
+        } else {
+            &mut self.cards
+        }
+    }
+
+    /// Only the boxes scoring at least `config`'s threshold for their template,
+    /// so matches found at a low threshold can be tried against higher ones.
+    pub fn above(&self, config: &DetectionConfig) -> Self {
+        let keep = |boxes: &[BoundingBox], is_suit: bool| -> Vec<BoundingBox> {
+            boxes
+                .iter()
+                .filter(|b| b.score >= config.threshold_for(&b.label, is_suit))
+                .cloned()
+                .collect()
+        };
+        Self {
+            cards: keep(&self.cards, false),
+            suits: keep(&self.suits, true),
+            backs: keep(&self.backs, false),
+            empty_slots: self.empty_slots.as_deref().map(|boxes| keep(boxes, false)),
+            foundation_slots: keep(&self.foundation_slots, false),
+            end_screens: keep(&self.end_screens, false),
+            digits: keep(&self.digits, false),
+        }
+    }
+
+    /// Checks suit colors against `color`, the frame the boxes were found in,
+    /// and suppresses overlapping boxes of each kind.
+    pub fn into_detections(mut self, color: &Mat, config: &DetectionConfig) -> Result<Detections> {
+        if config.verify_suit_color && color.channels() == 3 {
+            verify_suit_colors(color, &mut self.suits)?;
+        }
+
+        let suppress = |boxes, class| non_maximum_suppression(boxes, config.overlap_threshold_for(class), &config.nms);
+        self.cards.extend(paired_tens(suppress(self.digits, BoxClass::Rank)));
+        prefer_tens(&mut self.cards, config.overlap_threshold_for(BoxClass::Rank));
+        let detections = Detections {
+            cards: suppress(self.cards, BoxClass::Rank),
+            suits: suppress(self.suits, BoxClass::Suit),
+            backs: suppress(self.backs, BoxClass::Back),
+            empty_slots: self.empty_slots.map(|boxes| suppress(boxes, BoxClass::Empty)),
+            foundation_slots: suppress(self.foundation_slots, BoxClass::Empty),
+            end_screens: suppress(self.end_screens, BoxClass::Empty),
+            highlights: if config.highlight.enabled && color.channels() == 3 {
+                highlight::highlight_regions(color, &config.highlight, highlight::HIGHLIGHT_LABEL)?
+            } else {
+                Vec::new()
+            },
+            selected: if config.selection.enabled && color.channels() == 3 {
+                highlight::highlight_regions(color, &config.selection, highlight::SELECTED_LABEL)?
+            } else {
+                Vec::new()
+            },
+        };
+        let empty_slots = detections
+            .empty_slots
+            .iter()
+            .flatten()
+            .chain(&detections.foundation_slots)
+            .chain(&detections.end_screens);
+        for b in detections.cards.iter().chain(&detections.suits).chain(&detections.backs).chain(empty_slots) {
+            debug!(label = %b.label, x1 = b.x1, y1 = b.y1, x2 = b.x2, y2 = b.y2, "kept box");
+        }
+
+        Ok(detections)
+    }
+}
+
+// a 10 for every 1 with a 0 right after it on the same line
+fn paired_tens(digits: Vec<BoundingBox>) -> Vec<BoundingBox> {
+    let (ones, zeros): (Vec<_>, Vec<_>) = digits.into_iter().partition(|b| b.label == TEN_DIGITS[0]);
+    let center_y = |b: &BoundingBox| (b.y1 + b.y2) / 2;
+
+    let mut tens = Vec::new();
+    for one in &ones {
+        let height = one.y2 - one.y1;
+        let zero = zeros
+            .iter()
+            .filter(|zero| (center_y(zero) - center_y(one)).abs() <= height / 4)
+            // the 0 may touch the 1 or sit up to about its own width away
+            .filter(|zero| zero.x1 >= one.x1 && zero.x1 - one.x2 <= zero.x2 - zero.x1)
+            .min_by_key(|zero| zero.x1 - one.x2);
+        if let Some(zero) = zero {
+            tens.push(BoundingBox {
+                x1: one.x1,
+                y1: one.y1.min(zero.y1),
+                x2: zero.x2,
+                y2: one.y2.max(zero.y2),
+                label: "10".to_string(),
+                score: one.score.min(zero.score),
+            });
+        }
+    }
+    debug!(ones = ones.len(), zeros = zeros.len(), tens = tens.len(), "paired digits into tens");
+    tens
+}
+
+// The 1 of a 10 looks enough like an A for the A template to match it, and
+// can outscore the 10 when the 0 is antialiased differently. Drop As that
+// sit on a 10, whatever their score, so suppression can't keep them instead.
+fn prefer_tens(cards: &mut Vec<BoundingBox>, overlap_thresh: f32) {
+    let tens: Vec<BoundingBox> = cards.iter().filter(|b| b.label == "10").cloned().collect();
+    cards.retain(|b| b.label != "A" || !tens.iter().any(|ten| overlap(ten, b) > overlap_thresh));
+}
+
+/// Paths of the templates in `template_dir`, including the variants in a
+/// label's subdirectory, like `J/plain.png` and `J/highlighted.png`.
+pub fn get_templates(template_dir: &str) -> Result<Vec<String>> {
+    let read_dir = |path: &Path| {
+        fs::read_dir(path).map_err(|source| VisionError::TemplateDir {
+            path: path.into(),
+            source,
+        })
+    };
+    let mut paths = Vec::new();
+    for entry in read_dir(Path::new(template_dir))?.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            paths.extend(
+                read_dir(&path)?
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")))
+                    .filter_map(|path| path.to_str().map(str::to_string)),
+            );
+        } else if let Some(path) = path.to_str() {
+            paths.push(path.to_string());
+        }
+    }
+    Ok(paths)
+}
+
+/// Loads every template in `template_dir` so they can be reused across images.
+/// Variants of a label are loaded as separate templates of that label, so
+/// wherever they overlap only the best-matching one is kept.
+pub fn load_templates(template_dir: &str) -> Result<Vec<Template>> {
+    get_templates(template_dir)?
+        .into_iter()
+        .map(|path| {
+            let image = load_image(&path)?;
+            let mask = load_mask(&path)?;
+            let parent = Path::new(&path).parent().filter(|parent| *parent != Path::new(template_dir));
+            let label = match parent.and_then(Path::file_name) {
+                // a variant, labelled by its directory
+                Some(dir) => dir.to_string_lossy().into_owned(),
+                // use png name for label
+                None => Path::new(&path).file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+            };
+            Ok(Template { label, image, mask })
+        })
+        .collect()
+}
+
+// load image in greyscale
+pub fn load_image(path: &str) -> Result<Mat> {
+    to_gray(&load_color_image(path)?)
+}
+
+// load image in BGR color
+pub fn load_color_image(path: &str) -> Result<Mat> {
+    let img = imread(path, IMREAD_COLOR)?;
+    // imread returns an empty mat instead of failing on missing/unreadable files
+    if img.empty() {
+        return Err(VisionError::ImageLoad { path: path.into() });
+    }
+    Ok(img)
+}
+
+// The alpha channel of the PNG at `path` as a mask of its opaque pixels, or
+// None if it has no alpha channel or no transparent pixels.
+fn load_mask(path: &str) -> Result<Option<Mat>> {
+    alpha_mask(&imread(path, IMREAD_UNCHANGED)?)
+}
+
+/// The alpha channel of the in-memory png `bytes` as a mask, like
+/// [`load_mask`].
+pub(crate) fn decode_mask(bytes: &[u8]) -> Result<Option<Mat>> {
+    alpha_mask(&imdecode(&opencv::core::Vector::<u8>::from_slice(bytes), IMREAD_UNCHANGED)?)
+}
+
+fn alpha_mask(img: &Mat) -> Result<Option<Mat>> {
+    if img.channels() != 4 || img.depth() != CV_8U {
+        return Ok(None);
+    }
+    let mut alpha = Mat::default();
+    extract_channel(img, &mut alpha, 3)?;
+    let mut mask = Mat::default();
+    opencv::imgproc::threshold(&alpha, &mut mask, 0.0, 255.0, THRESH_BINARY)?;
+    if count_non_zero(&mask)? == mask.total() as i32 {
+        return Ok(None);
+    }
+    Ok(Some(mask))
+}
+
+// decode an in-memory png/jpeg in greyscale
+pub fn decode_image(bytes: &[u8]) -> Result<Mat> {
+    to_gray(&decode_color_image(bytes)?)
+}
+
+// decode an in-memory png/jpeg in BGR color
+pub fn decode_color_image(bytes: &[u8]) -> Result<Mat> {
+    let img = imdecode(&opencv::core::Vector::<u8>::from_slice(bytes), IMREAD_COLOR)?;
+    if img.empty() {
+        return Err(VisionError::ImageDecode);
+    }
+    Ok(img)
+}
+
+/// `img` in greyscale, converted if it's BGR.
+pub fn to_gray(img: &Mat) -> Result<Mat> {
+    if img.channels() == 1 {
+        return Ok(img.clone());
+    }
+    let mut gray = Mat::default();
+    cvt_color(img, &mut gray, COLOR_BGR2GRAY, 0)?;
+    Ok(gray)
+}
+
+/// Whether the symbol inside `bounding_box` of the BGR `img` is printed in
+/// red, or `None` if there are too few clearly red or black pixels to tell.
+pub fn is_red(img: &Mat, bounding_box: &BoundingBox) -> Result<Option<bool>> {
+    let bounds = Rect::new(0, 0, img.cols(), img.rows());
+    let rect = Rect::new(
+        bounding_box.x1,
+        bounding_box.y1,
+        bounding_box.x2 - bounding_box.x1,
+        bounding_box.y2 - bounding_box.y1,
+    ) & bounds;
+    if rect.empty() {
+        return Ok(None);
+    }
+
+    // a copy of the ROI is continuous, so its pixels can be read as one slice
+    let roi = Mat::roi(img, rect)?.try_clone()?;
+    let (mut red, mut black) = (0, 0);
+    for pixel in roi.data_typed::<Vec3b>()? {
+        let [b, g, r] = pixel.0.map(i32::from);
+        if r > 120 && r > g + 60 && r > b + 60 {
+            red += 1;
+        } else if r.max(g).max(b) < 90 {
+            black += 1;
+        }
+    }
+    Ok(Some(red > black).filter(|_| red + black >= 4))
+}
+
+/// The suit of the other color that's most easily mistaken for `suit` in
+/// greyscale, going by shape.
+fn color_twin(suit: &str) -> Option<&'static str> {
+    match suit {
+        "hearts" => Some("spades"),
+        "spades" => Some("hearts"),
+        "diamonds" => Some("clubs"),
+        "clubs" => Some("diamonds"),
+        _ => None,
+    }
+}
+
+/// Relabels suit boxes whose color in the BGR `img` contradicts their
+/// label, e.g. a red symbol matched as spades becomes hearts.
+pub fn verify_suit_colors(img: &Mat, suits: &mut [BoundingBox]) -> Result<()> {
+    for suit in suits {
+        let Some(red) = is_red(img, suit)? else {
+            continue;
+        };
+        let labelled_red = matches!(suit.label.as_str(), "hearts" | "diamonds");
+        if red != labelled_red {
+            if let Some(twin) = color_twin(&suit.label) {
+                debug!(label = %suit.label, relabelled = twin, x1 = suit.x1, y1 = suit.y1, "suit color mismatch");
+                suit.label = twin.to_string();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resizes an encoded image by `scale` and re-encodes it as PNG, keeping its colors.
+pub fn scale_png(bytes: &[u8], scale: f64) -> Result<Vec<u8>> {
+    let img = imdecode(&opencv::core::Vector::<u8>::from_slice(bytes), IMREAD_COLOR)?;
+    if img.empty() {
+        return Err(VisionError::ImageDecode);
+    }
+    let mut scaled = Mat::default();
+    resize(&img, &mut scaled, Size::default(), scale, scale, INTER_AREA)?;
+
+    let mut png = opencv::core::Vector::<u8>::new();
+    imencode(".png", &scaled, &mut png, &opencv::core::Vector::new())?;
+    Ok(png.to_vec())
+}
+
+pub fn match_template_with_threshold(
+    img: &Mat,
+    template: &Mat,
+    threshold: f32,
+) -> opencv::Result<Vec<Point>> {
+    Ok(match_template_scores(img, template, threshold)?
+        .into_iter()
+        .map(|(point, _)| point)
+        .collect())
+}
+
+/// Top left corners of every match scoring at least `threshold`, with their
+/// scores. Only peaks are kept, the best-scoring window among its neighbours
+/// up to half the template's size away, rather than every window around
+/// them that also clears the threshold.
+pub fn match_template_scores(
+    img: &Mat,
+    template: &Mat,
+    threshold: f32,
+) -> opencv::Result<Vec<(Point, f32)>> {
+    match_template_masked(img, template, None, threshold)
+}
+
+/// Same as [`match_template_scores`], comparing only the pixels where `mask`
+/// is non-zero.
+pub fn match_template_masked(
+    img: &Mat,
+    template: &Mat,
+    mask: Option<&Mat>,
+    threshold: f32,
+) -> opencv::Result<Vec<(Point, f32)>> {
+    let mut result = Mat::default();
+    // find matches
+    let no_mask = Mat::default();
+    opencl::match_template(img, template, &mut result, mask.unwrap_or(&no_mask))?;
+    if result.empty() {
+        return Ok(Vec::new());
+    }
+    // a mask can leave flat windows with no defined score
+    patch_na_ns(&mut result, -1.0)?;
+
+    // a window is a peak if no neighbour scores higher, i.e. it's unchanged
+    // by dilating the scores over the neighbourhood
+    let side = (template.cols().min(template.rows()) / 2) | 1;
+    let kernel = get_structuring_element_def(MORPH_RECT, Size::new(side, side))?;
+    let mut peaks = Mat::default();
+    dilate_def(&result, &mut peaks, &kernel)?;
+
+    // filter peaks by threshold, reading the scores as one slice rather
+    // than a checked lookup per pixel
+    let mut matches = Vec::new();
+    let cols = result.cols() as usize;
+    let rows = result.data_typed::<f32>()?.chunks_exact(cols).zip(peaks.data_typed::<f32>()?.chunks_exact(cols));
+    for (y, (row, peak_row)) in rows.enumerate() {
+        for (x, (&value, &peak)) in row.iter().zip(peak_row).enumerate() {
+            if value >= threshold && value >= peak {
+                matches.push((Point::new(x as i32, y as i32), value));
+            }
+        }
+    }
+    Ok(matches)
+}
+
+/// Same as [`match_template_scores`], but with candidates found on `img` and
+/// `template` shrunk as configured first, and only their neighbourhoods
+/// matched at full size.
+pub fn match_template_pyramid(
+    img: &Mat,
+    template: &Mat,
+    mask: Option<&Mat>,
+    threshold: f32,
+    config: &PyramidConfig,
+) -> Result<Vec<(Point, f32)>> {
+    let mut levels = 0;
+    while levels < config.levels && template.cols().min(template.rows()) >> (levels + 1) >= MIN_PYRAMID_SIZE {
+        levels += 1;
+    }
+    if levels == 0 {
+        return Ok(match_template_masked(img, template, mask, threshold)?);
+    }
+
+    let (mut small_img, mut small_template) = (img.clone(), template.clone());
+    for _ in 0..levels {
+        let mut down = Mat::default();
+        pyr_down_def(&small_img, &mut down)?;
+        small_img = down;
+        let mut down = Mat::default();
+        pyr_down_def(&small_template, &mut down)?;
+        small_template = down;
+    }
+    let small_mask = match mask {
+        Some(mask) => Some(resize_mask(mask, small_template.size()?)?),
+        None => None,
+    };
+    let mut coarse = Mat::default();
+    let no_mask = Mat::default();
+    opencl::match_template(&small_img, &small_template, &mut coarse, small_mask.as_ref().unwrap_or(&no_mask))?;
+    // masked matching leaves NaN where a window is flat
+    patch_na_ns(&mut coarse, 0.0)?;
+    let mut above = Mat::default();
+    opencv::imgproc::threshold(&coarse, &mut above, f64::from(threshold - config.coarse_margin), 255.0, THRESH_BINARY)?;
+    let mut above_8u = Mat::default();
+    above.convert_to(&mut above_8u, CV_8U, 1.0, 0.0)?;
+    let mut candidates = Vector::<Vector<Point>>::new();
+    find_contours(&above_8u, &mut candidates, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE, Point::default())?;
+
+    let factor = 1 << levels;
+    let frame = Rect::new(0, 0, img.cols(), img.rows());
+    let n = config.neighborhood + factor;
+    // keyed by row first, for the same order as matching the whole frame
+    let mut found = BTreeMap::new();
+    for candidate in &candidates {
+        let rect = bounding_rect(&candidate)?;
+        let area = Rect::new(
+            rect.x * factor - n,
+            rect.y * factor - n,
+            rect.width * factor + template.cols() + 2 * n,
+            rect.height * factor + template.rows() + 2 * n,
+        ) & frame;
+        if area.width < template.cols() || area.height < template.rows() {
+            continue;
+        }
+        let roi = Mat::roi(img, area)?.try_clone()?;
+        for (point, score) in match_template_masked(&roi, template, mask, threshold)? {
+            found.insert((point.y + area.y, point.x + area.x), score);
+        }
+    }
+    debug!(levels, candidates = candidates.len(), matches = found.len(), "matched through pyramid");
+    Ok(found.into_iter().map(|((y, x), score)| (Point::new(x, y), score)).collect())
+}
+
+/// Estimates how much `img` is scaled relative to the templates by matching
+/// `reference` across the calibration's scale sweep. Returns the best scale
+/// and its score, or `None` if nothing scored `min_score`.
+#[tracing::instrument(skip_all)]
+pub fn estimate_scale(img: &Mat, reference: &Mat, config: &CalibrationConfig) -> Result<Option<(f32, f32)>> {
+    let mut best: Option<(f32, f32)> = None;
+    for scale in config.scales() {
+        let Some(scaled) = resize_template(reference, scale, img)? else {
+            continue;
+        };
+        let mut result = Mat::default();
+        opencl::match_template(img, &scaled, &mut result, &Mat::default())?;
+        let mut score = 0.0;
+        min_max_loc(&result, None, Some(&mut score), None, None, &no_array())?;
+        let score = score as f32;
+        debug!(scale, score, "tried calibration scale");
+        if best.is_none_or(|(_, best_score)| score > best_score) {
+            best = Some((scale, score));
+        }
+    }
+    Ok(best.filter(|&(_, score)| score >= config.min_score))
+}
+
+/// Every template resized by `scale`.
+pub fn scale_templates(templates: &[Template], scale: f32) -> Result<Vec<Template>> {
+    templates
+        .iter()
+        .map(|template| {
+            let mut image = Mat::default();
+            let size = Size::new(
+                (template.image.cols() as f32 * scale).round().max(1.0) as i32,
+                (template.image.rows() as f32 * scale).round().max(1.0) as i32,
+            );
+            let interpolation = if scale < 1.0 { INTER_AREA } else { INTER_LINEAR };
+            resize(&template.image, &mut image, size, 0.0, 0.0, interpolation)?;
+            let mask = match &template.mask {
+                Some(mask) => Some(resize_mask(mask, size)?),
+                None => None,
+            };
+            Ok(Template {
+                label: template.label.clone(),
+                image,
+                mask,
+            })
+        })
+        .collect()
+}
+
+// `mask` resized to `size`, staying binary
+pub(crate) fn resize_mask(mask: &Mat, size: Size) -> Result<Mat> {
+    let mut resized = Mat::default();
+    resize(mask, &mut resized, size, 0.0, 0.0, INTER_NEAREST)?;
+    Ok(resized)
+}
+
+// `template` resized by `scale`, or None if that's too small to mean anything
+// or too big to fit in `img`
+pub(crate) fn resize_template(template: &Mat, scale: f32, img: &Mat) -> Result<Option<Mat>> {
+    let width = (template.cols() as f32 * scale).round() as i32;
+    let height = (template.rows() as f32 * scale).round() as i32;
+    if width < 4 || height < 4 || width > img.cols() || height > img.rows() {
+        return Ok(None);
+    }
+    if scale == 1.0 {
+        return Ok(Some(template.clone()));
+    }
+    let mut scaled = Mat::default();
+    let interpolation = if scale < 1.0 { INTER_AREA } else { INTER_LINEAR };
+    resize(template, &mut scaled, Size::new(width, height), 0.0, 0.0, interpolation)?;
+    Ok(Some(scaled))
+}
+
+/// Matches `template` resized by each of `scales` and keeps, wherever
+/// matches overlap, only the best-scoring one.
+pub fn match_template_scaled(
+    img: &Mat,
+    template: &Template,
+    threshold: f32,
+    scales: &[f32],
+    pyramid: &PyramidConfig,
+) -> Result<Vec<BoundingBox>> {
+    let mut scored = Vec::new();
+    for &scale in scales {
+        let Some(scaled) = resize_template(&template.image, scale, img)? else {
+            continue;
+        };
+        let (width, height) = (scaled.cols(), scaled.rows());
+        let mask = match &template.mask {
+            Some(mask) => Some(resize_mask(mask, scaled.size()?)?),
+            None => None,
+        };
+
+        let matches = match_template_pyramid(img, &scaled, mask.as_ref(), threshold, pyramid)?;
+        debug!(scale, matches = matches.len(), "matched scaled template");
+        scored.extend(matches.into_iter().map(|(pt, score)| {
+            let bounding_box = BoundingBox {
+                x1: pt.x,
+                y1: pt.y,
+                x2: pt.x + width,
+                y2: pt.y + height,
+                label: template.label.clone(),
+                score,
+            };
+            (bounding_box, score)
+        }));
+    }
+
+    // best score first, so each location keeps the scale that fits it best
+    scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    let mut kept: Vec<BoundingBox> = Vec::new();
+    for (candidate, _) in scored {
+        if !kept.iter().any(|b| overlap(b, &candidate) > 0.5) {
+            kept.push(candidate);
+        }
+    }
+    Ok(kept)
+}
+
+// intersection over the smaller of the two boxes
+fn overlap(a: &BoundingBox, b: &BoundingBox) -> f32 {
+    let width = (a.x2.min(b.x2) - a.x1.max(b.x1)).max(0);
+    let height = (a.y2.min(b.y2) - a.y1.max(b.y1)).max(0);
+    let smaller = ((a.x2 - a.x1) * (a.y2 - a.y1)).min((b.x2 - b.x1) * (b.y2 - b.y1));
+    (width * height) as f32 / smaller.max(1) as f32
+}
+
+pub fn create_bounding_boxes(
+    matches: Vec<(Point, f32)>,
+    template_width: i32,
+    template_height: i32,
+    label: String,
+) -> Vec<BoundingBox> {
+    matches
+        .into_iter()
+        .map(|(pt, score)| BoundingBox {
+            x1: pt.x,
+            y1: pt.y,
+            x2: pt.x + template_width,
+            y2: pt.y + template_height,
+            label: label.clone(),
+            score,
+        })
+        .collect()
+}
+
+/// Keeps the best-scoring box of each overlapping cluster, as set up by
+/// `nms`, and returns the survivors top to bottom.
+#[tracing::instrument(skip_all, fields(boxes = boxes.len()))]
+pub fn non_maximum_suppression(
+    boxes: Vec<BoundingBox>,
+    overlap_thresh: f32,
+    nms: &NmsConfig,
+) -> Vec<BoundingBox> {
+    let mut filtered_boxes = Vec::new();
+    let mut boxes = boxes;
+
+    // soft suppression changes scores, so look for the best box afresh each time
+    while let Some(best) = boxes
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.score.total_cmp(&b.score))
+        .map(|(i, _)| i)
+    {
+        let current = boxes.swap_remove(best);
+        boxes.retain_mut(|b| {
+            if nms.per_class && b.label != current.label {
+                return true;
+            }
+            let inter_x1 = current.x1.max(b.x1);
+            let inter_y1 = current.y1.max(b.y1);
+            let inter_x2 = current.x2.min(b.x2);
+            let inter_y2 = current.y2.min(b.y2);
+
+            let inter_area = (inter_x2 - inter_x1).max(0) * (inter_y2 - inter_y1).max(0);
+            let box_area = (b.x2 - b.x1) * (b.y2 - b.y1);
+            let overlap = inter_area as f32 / box_area as f32;
+
+            match nms.mode {
+                NmsMode::Hard => overlap <= overlap_thresh,
+                NmsMode::Soft => {
+                    b.score *= (-overlap * overlap / nms.sigma).exp();
+                    b.score >= nms.min_score
+                }
+            }
+        });
+        filtered_boxes.push(current);
+    }
+
+    // by bottom right corner, the order assembly reads rows in
+    filtered_boxes.sort_by(|a, b| a.y2.cmp(&b.y2).then(b.score.total_cmp(&a.score)));
+    debug!(kept = filtered_boxes.len(), "suppressed overlapping boxes");
+    filtered_boxes
+}
+
+/// Draws each box onto the BGR `img` in a color for its kind, with its
+/// label and score written above it, or below when it's at the top edge.
+pub fn draw_bounding_boxes(img: &mut Mat, bounding_boxes: &[BoundingBox]) -> Result<()> {
+    const FONT_SCALE: f64 = 0.4;
+
+    for bounding_box in bounding_boxes {
+        let rect = Rect::new(
+            bounding_box.x1,
+            bounding_box.y1,
+            bounding_box.x2 - bounding_box.x1,
+            bounding_box.y2 - bounding_box.y1,
+        );
+        let color = box_color(&bounding_box.label);
+
+        rectangle(
+            img,
+            rect,
+            color,
+            2,
+            LINE_8,
+            0,
+        )?;
+
+        let text = format!("{} {:.2}", bounding_box.label, bounding_box.score);
+        let mut baseline = 0;
+        let size = get_text_size(&text, FONT_HERSHEY_SIMPLEX, FONT_SCALE, 1, &mut baseline)?;
+        let height = size.height + baseline + 2;
+        let top = if bounding_box.y1 >= height { bounding_box.y1 - height } else { bounding_box.y2 };
+        // filled in the box's color so the text reads over any background
+        rectangle(img, Rect::new(bounding_box.x1, top, size.width + 2, height), color, FILLED, LINE_8, 0)?;
+        put_text(
+            img,
+            &text,
+            Point::new(bounding_box.x1 + 1, top + size.height + 1),
+            FONT_HERSHEY_SIMPLEX,
+            FONT_SCALE,
+            Scalar::all(0.0),
+            1,
+            LINE_8,
+            false,
+        )?;
+    }
+    Ok(())
+}
+
+// BGR color boxes with `label` are drawn in: green ranks, orange suits,
+// purple backs, grey empty piles and foundations, and yellow for the rest
+fn box_color(label: &str) -> Scalar {
+    if RANK_LABELS.contains(&label) || TEN_DIGITS.contains(&label) {
+        Scalar::new(0.0, 255.0, 0.0, 0.0)
+    } else if SUIT_LABELS.contains(&label) {
+        Scalar::new(0.0, 165.0, 255.0, 0.0)
+    } else if label == BACK_LABEL {
+        Scalar::new(255.0, 0.0, 160.0, 0.0)
+    } else if label == EMPTY_LABEL || label.starts_with(FOUNDATION_PREFIX) {
+        Scalar::new(160.0, 160.0, 160.0, 0.0)
+    } else {
+        Scalar::new(0.0, 255.0, 255.0, 0.0)
+    }
+}
+
+pub fn save_image(img: &Mat, output_path: &str) -> Result<()> {
+    if imwrite(output_path, img, &opencv::core::Vector::new())? {
+        Ok(())
+    } else {
+        Err(VisionError::OpenCv(opencv::Error::new(
+            opencv::core::StsError,
+            format!("Failed to save image to {output_path}"),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(boxes: &[BoundingBox]) -> Vec<&str> {
+        boxes.iter().map(|b| b.label.as_str()).collect()
+    }
+
+    #[test]
+    fn nms_of_no_boxes_keeps_none() {
+        assert!(non_maximum_suppression(Vec::new(), 0.5, &NmsConfig::default()).is_empty());
+        let soft = NmsConfig {
+            mode: NmsMode::Soft,
+            ..NmsConfig::default()
+        };
+        assert!(non_maximum_suppression(Vec::new(), 0.5, &soft).is_empty());
+    }
+
+    #[test]
+    fn hard_nms_keeps_the_best_of_overlapping_boxes() {
+        let boxes = vec![
+            BoundingBox::at("A", [0, 0, 10, 10], 0.8),
+            BoundingBox::at("K", [1, 1, 11, 11], 0.9),
+            BoundingBox::at("Q", [20, 1, 30, 11], 0.7),
+        ];
+        let kept = non_maximum_suppression(boxes, 0.5, &NmsConfig::default());
+        assert_eq!(labels(&kept), ["K", "Q"]);
+    }
+
+    #[test]
+    fn hard_nms_keeps_one_of_tied_duplicates() {
+        let boxes = vec![BoundingBox::at("A", [0, 0, 10, 10], 0.9), BoundingBox::at("A", [0, 0, 10, 10], 0.9)];
+        assert_eq!(non_maximum_suppression(boxes, 0.5, &NmsConfig::default()).len(), 1);
+    }
+
+    #[test]
+    fn hard_nms_keeps_boxes_at_the_overlap_threshold() {
+        let boxes = vec![BoundingBox::at("A", [0, 0, 10, 10], 0.9), BoundingBox::at("K", [5, 0, 15, 10], 0.8)];
+        assert_eq!(non_maximum_suppression(boxes, 0.5, &NmsConfig::default()).len(), 2);
+    }
+
+    #[test]
+    fn per_class_nms_only_suppresses_the_same_label() {
+        let nms = NmsConfig {
+            per_class: true,
+            ..NmsConfig::default()
+        };
+        let boxes = vec![
+            BoundingBox::at("A", [0, 0, 10, 10], 0.9),
+            BoundingBox::at("K", [0, 0, 10, 10], 0.8),
+            BoundingBox::at("A", [1, 1, 11, 11], 0.7),
+        ];
+        let kept = non_maximum_suppression(boxes, 0.5, &nms);
+        assert_eq!(labels(&kept), ["A", "K"]);
+        assert_eq!(kept[0].score, 0.9);
+    }
+
+    #[test]
+    fn soft_nms_decays_overlapping_scores_instead_of_dropping() {
+        let nms = NmsConfig {
+            mode: NmsMode::Soft,
+            ..NmsConfig::default()
+        };
+        let boxes = vec![
+            BoundingBox::at("A", [0, 0, 10, 10], 0.9),
+            // overlaps A by a fifth, so only loses a little
+            BoundingBox::at("K", [8, 0, 18, 10], 0.8),
+            // covers A, so decays below min_score
+            BoundingBox::at("Q", [0, 0, 10, 10], 0.85),
+        ];
+        let kept = non_maximum_suppression(boxes, 0.5, &nms);
+        assert_eq!(labels(&kept), ["A", "K"]);
+        assert_eq!(kept[0].score, 0.9);
+        assert!(kept[1].score > nms.min_score && kept[1].score < 0.8);
+    }
+
+    #[test]
+    fn nms_returns_boxes_top_to_bottom() {
+        let boxes = vec![BoundingBox::at("K", [0, 50, 10, 60], 0.9), BoundingBox::at("A", [20, 0, 30, 10], 0.7)];
+        assert_eq!(labels(&non_maximum_suppression(boxes, 0.5, &NmsConfig::default())), ["A", "K"]);
+    }
+
+    #[test]
+    fn paired_tens_pairs_a_one_with_the_zero_after_it() {
+        let tens = paired_tens(vec![BoundingBox::at("1", [10, 0, 14, 20], 0.9), BoundingBox::at("0", [15, 1, 27, 21], 0.8)]);
+        assert_eq!(labels(&tens), ["10"]);
+        assert_eq!((tens[0].x1, tens[0].y1, tens[0].x2, tens[0].y2), (10, 0, 27, 21));
+        assert_eq!(tens[0].score, 0.8);
+    }
+
+    #[test]
+    fn paired_tens_needs_both_digits_on_one_line() {
+        assert!(paired_tens(Vec::new()).is_empty());
+        assert!(paired_tens(vec![BoundingBox::at("1", [10, 0, 14, 20], 0.9)]).is_empty());
+        // the 0 on the next line down
+        assert!(paired_tens(vec![BoundingBox::at("1", [10, 0, 14, 20], 0.9), BoundingBox::at("0", [15, 30, 27, 50], 0.8)]).is_empty());
+        // the 0 before the 1
+        assert!(paired_tens(vec![BoundingBox::at("1", [20, 0, 24, 20], 0.9), BoundingBox::at("0", [5, 0, 17, 20], 0.8)]).is_empty());
+        // the 0 further than its own width away
+        assert!(paired_tens(vec![BoundingBox::at("1", [10, 0, 14, 20], 0.9), BoundingBox::at("0", [30, 0, 42, 20], 0.8)]).is_empty());
+    }
+
+    #[test]
+    fn paired_tens_takes_the_nearest_zero() {
+        let tens = paired_tens(vec![
+            BoundingBox::at("0", [17, 0, 29, 20], 0.9),
+            BoundingBox::at("1", [10, 0, 14, 20], 0.9),
+            BoundingBox::at("0", [15, 0, 27, 20], 0.7),
+        ]);
+        assert_eq!(tens.len(), 1);
+        assert_eq!((tens[0].x2, tens[0].score), (27, 0.7));
+    }
+
+    #[test]
+    fn prefer_tens_drops_only_aces_on_tens() {
+        let mut cards = vec![
+            BoundingBox::at("10", [10, 0, 27, 20], 0.8),
+            BoundingBox::at("A", [10, 0, 16, 20], 0.95),
+            BoundingBox::at("A", [50, 0, 62, 20], 0.9),
+        ];
+        prefer_tens(&mut cards, 0.5);
+        assert_eq!(labels(&cards), ["10", "A"]);
+        assert_eq!(cards[1].x1, 50);
+
+        let mut no_tens = vec![BoundingBox::at("A", [10, 0, 16, 20], 0.95)];
+        prefer_tens(&mut no_tens, 0.5);
+        assert_eq!(labels(&no_tens), ["A"]);
+
+        let mut none = Vec::new();
+        prefer_tens(&mut none, 0.5);
+        assert!(none.is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+//! Finds the parts of a frame that changed since the previous one, so only
+//! those need matching again, and merges what's found there with the
+//! matches carried over from the previous frame everywhere else.
+
+use crate::config::{DetectionConfig, DifferencingConfig, Region};
+use crate::detect::{self, BoundingBox, Detections};
+use crate::error::Result;
+use crate::pipeline::Detector;
+use opencv::core::{absdiff, count_non_zero, Mat, Rect};
+use opencv::imgproc::{threshold, THRESH_BINARY};
+use opencv::prelude::*;
+use tracing::debug;
+
+/// Rematches only what changed in each frame since the last one it was
+/// given, as set up by [`DifferencingConfig`], and carries the rest over.
+/// The last frame is its state, so every stream of frames of one game needs
+/// a differ of its own; frames of unrelated games must be detected whole.
+#[derive(Debug)]
+pub struct Differ {
+    config: DifferencingConfig,
+    search_regions: Vec<Region>,
+    // the last greyscale frame and what was detected in it
+    previous: Option<(Mat, Detections)>,
+}
+
+impl Differ {
+    /// Differences frames as `config` sets up, within its search regions.
+    pub fn new(config: &DetectionConfig) -> Self {
+        Self {
+            config: config.differencing.clone(),
+            search_regions: config.search_regions.clone(),
+            previous: None,
+        }
+    }
+
+    /// Forgets the last frame, e.g. once a new game is dealt.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// Runs `detector` on the parts of `frame` that changed since the last
+    /// frame, or on all of it when differencing is off, this is the first
+    /// frame or too much changed.
+    pub fn detect(&mut self, detector: &(impl Detector + ?Sized), frame: &Mat) -> Result<Detections> {
+        if !self.config.enabled {
+            return detector.detect(frame);
+        }
+        let gray = detect::to_gray(frame)?;
+        let detections = match &self.previous {
+            Some((last, prior)) => match changed_tiles(last, &gray, &self.config)? {
+                Some(changed) if changed.is_empty() => prior.clone(),
+                Some(changed) => {
+                    let regions = search_regions(&changed, &self.search_regions, &self.config);
+                    let fresh = detector.detect_in(frame, &regions)?;
+                    merge(prior, fresh, &changed, frame.cols(), frame.rows())
+                }
+                None => detector.detect(frame)?,
+            },
+            None => detector.detect(frame)?,
+        };
+        self.previous = Some((gray, detections.clone()));
+        Ok(detections)
+    }
+}
+
+/// The tiles of the greyscale `frame` that differ from `previous`, or
+/// `None` if the whole frame has to be matched again: the two aren't the
+/// same size, or more than `max_changed` of the tiles changed.
+pub fn changed_tiles(previous: &Mat, frame: &Mat, config: &DifferencingConfig) -> Result<Option<Vec<Region>>> {
+    if previous.size()? != frame.size()? || config.tiles < 1 {
+        return Ok(None);
+    }
+    let mut diff = Mat::default();
+    absdiff(previous, frame, &mut diff)?;
+    let mut changed = Mat::default();
+    threshold(&diff, &mut changed, config.pixel_threshold, 255.0, THRESH_BINARY)?;
+
+    let (cols, rows) = (frame.cols(), frame.rows());
+    let mut tiles = Vec::new();
+    for ty in 0..config.tiles {
+        for tx in 0..config.tiles {
+            let (x1, y1) = (tx * cols / config.tiles, ty * rows / config.tiles);
+            let (x2, y2) = ((tx + 1) * cols / config.tiles, (ty + 1) * rows / config.tiles);
+            if x2 <= x1 || y2 <= y1 {
+                continue;
+            }
+            let tile = Mat::roi(&changed, Rect::new(x1, y1, x2 - x1, y2 - y1))?;
+            let share = count_non_zero(&tile)? as f32 / ((x2 - x1) * (y2 - y1)) as f32;
+            if share >= config.min_changed {
+                tiles.push(Region {
+                    x1: x1 as f32 / cols as f32,
+                    y1: y1 as f32 / rows as f32,
+                    x2: x2 as f32 / cols as f32,
+                    y2: y2 as f32 / rows as f32,
+                });
+            }
+        }
+    }
+    let share = tiles.len() as f32 / (config.tiles * config.tiles) as f32;
+    debug!(changed = tiles.len(), share, "compared frame to the previous one");
+    Ok((share <= config.max_changed).then_some(tiles))
+}
+
+/// Where to match again for the `changed` tiles: each grown by the
+/// configured padding, and clipped to `search_regions` unless that's empty.
+pub fn search_regions(changed: &[Region], search_regions: &[Region], config: &DifferencingConfig) -> Vec<Region> {
+    let grow = config.padding / config.tiles.max(1) as f32;
+    let grown = changed.iter().map(|tile| Region {
+        x1: (tile.x1 - grow).max(0.0),
+        y1: (tile.y1 - grow).max(0.0),
+        x2: (tile.x2 + grow).min(1.0),
+        y2: (tile.y2 + grow).min(1.0),
+    });
+    if search_regions.is_empty() {
+        return grown.collect();
+    }
+    grown
+        .flat_map(|tile| {
+            search_regions.iter().map(move |region| Region {
+                x1: tile.x1.max(region.x1),
+                y1: tile.y1.max(region.y1),
+                x2: tile.x2.min(region.x2),
+                y2: tile.y2.min(region.y2),
+            })
+        })
+        .filter(|region| region.x1 < region.x2 && region.y1 < region.y2)
+        .collect()
+}
+
+/// `previous` with every box centered in one of the `changed` tiles of a
+/// `cols` x `rows` frame replaced by the boxes of `fresh` centered there.
+pub fn merge(previous: &Detections, fresh: Detections, changed: &[Region], cols: i32, rows: i32) -> Detections {
+    let inside = |b: &BoundingBox| {
+        let (x, y) = ((b.x1 + b.x2) as f32 / 2.0 / cols as f32, (b.y1 + b.y2) as f32 / 2.0 / rows as f32);
+        changed.iter().any(|tile| tile.contains(x, y))
+    };
+    let merge = |old: &[BoundingBox], new: Vec<BoundingBox>| -> Vec<BoundingBox> {
+        old.iter().filter(|b| !inside(b)).cloned().chain(new.into_iter().filter(|b| inside(b))).collect()
+    };
+    let mut cards = merge(&previous.cards, fresh.cards);
+    let mut suits = merge(&previous.suits, fresh.suits);
+    // in the order detection leaves them
+    for boxes in [&mut cards, &mut suits] {
+        boxes.sort_by(|a, b| a.y2.cmp(&b.y2).then(b.score.total_cmp(&a.score)));
+    }
+    Detections {
+        cards,
+        suits,
+        backs: merge(&previous.backs, fresh.backs),
+        empty_slots: match (&previous.empty_slots, fresh.empty_slots) {
+            (Some(old), Some(new)) => Some(merge(old, new)),
+            (_, new) => new,
+        },
+        foundation_slots: merge(&previous.foundation_slots, fresh.foundation_slots),
+        end_screens: merge(&previous.end_screens, fresh.end_screens),
+        highlights: merge(&previous.highlights, fresh.highlights),
+        selected: merge(&previous.selected, fresh.selected),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(boxes: &[BoundingBox]) -> Vec<&str> {
+        boxes.iter().map(|b| b.label.as_str()).collect()
+    }
+
+    // 4 tiles a side, each grown by a quarter of one
+    fn config() -> DifferencingConfig {
+        DifferencingConfig {
+            tiles: 4,
+            padding: 0.25,
+            ..DifferencingConfig::default()
+        }
+    }
+
+    #[test]
+    fn search_regions_of_no_changes_are_empty() {
+        assert!(search_regions(&[], &[], &config()).is_empty());
+        assert!(search_regions(&[], &[Region::from([0.0, 0.0, 1.0, 1.0])], &config()).is_empty());
+    }
+
+    #[test]
+    fn search_regions_grow_tiles_within_the_frame() {
+        let changed = [Region::from([0.0, 0.0, 0.25, 0.25]), Region::from([0.75, 0.75, 1.0, 1.0])];
+        assert_eq!(
+            search_regions(&changed, &[], &config()),
+            [Region::from([0.0, 0.0, 0.3125, 0.3125]), Region::from([0.6875, 0.6875, 1.0, 1.0])]
+        );
+    }
+
+    #[test]
+    fn search_regions_are_clipped_to_the_layout() {
+        let changed = [Region::from([0.25, 0.25, 0.5, 0.5])];
+        let layout = [Region::from([0.0, 0.0, 0.3, 1.0]), Region::from([0.9, 0.0, 1.0, 1.0])];
+        // the tile misses the second region entirely
+        assert_eq!(search_regions(&changed, &layout, &config()), [Region::from([0.1875, 0.1875, 0.3, 0.5625])]);
+    }
+
+    #[test]
+    fn merge_replaces_only_boxes_in_changed_tiles() {
+        let previous = Detections {
+            cards: vec![BoundingBox::at("A", [0, 0, 10, 10], 0.9), BoundingBox::at("K", [60, 60, 70, 70], 0.9)],
+            ..Detections::default()
+        };
+        let fresh = Detections {
+            // outside the changed tile the previous frame's A stands, not the Q
+            cards: vec![BoundingBox::at("2", [60, 60, 70, 70], 0.9), BoundingBox::at("Q", [0, 0, 10, 10], 0.9)],
+            ..Detections::default()
+        };
+        let changed = [Region::from([0.5, 0.5, 1.0, 1.0])];
+        let merged = merge(&previous, fresh, &changed, 100, 100);
+        assert_eq!(labels(&merged.cards), ["A", "2"]);
+    }
+
+    #[test]
+    fn merge_of_no_changes_keeps_the_previous_boxes() {
+        let previous = Detections {
+            cards: vec![BoundingBox::at("A", [0, 0, 10, 10], 0.9)],
+            suits: vec![BoundingBox::at("hearts", [0, 12, 10, 22], 0.9)],
+            ..Detections::default()
+        };
+        let fresh = Detections {
+            cards: vec![BoundingBox::at("K", [0, 0, 10, 10], 0.9)],
+            ..Detections::default()
+        };
+        let merged = merge(&previous, fresh, &[], 100, 100);
+        assert_eq!(labels(&merged.cards), ["A"]);
+        assert_eq!(labels(&merged.suits), ["hearts"]);
+        assert!(merged.empty_slots.is_none());
+    }
+
+    #[test]
+    fn merge_keeps_boxes_on_the_frame_edges() {
+        let previous = Detections::default();
+        let fresh = Detections {
+            cards: vec![BoundingBox::at("A", [0, 0, 2, 2], 0.9), BoundingBox::at("K", [98, 98, 100, 100], 0.9)],
+            ..Detections::default()
+        };
+        let changed = [Region::from([0.0, 0.0, 0.5, 0.5]), Region::from([0.5, 0.5, 1.0, 1.0])];
+        assert_eq!(labels(&merge(&previous, fresh, &changed, 100, 100).cards), ["A", "K"]);
+    }
+}
@@ -0,0 +1,41 @@
+//! The default Google Solitaire templates, compiled into the binary so the
+//! tool works without a template directory on disk.
+
+use crate::detect::{self, Template};
+use crate::error::Result;
+
+/// Label and png of every built-in template.
+const TEMPLATES: &[(&str, &[u8])] = &[
+    ("A", include_bytes!("../../../templates/A.png")),
+    ("2", include_bytes!("../../../templates/2.png")),
+    ("3", include_bytes!("../../../templates/3.png")),
+    ("4", include_bytes!("../../../templates/4.png")),
+    ("5", include_bytes!("../../../templates/5.png")),
+    ("6", include_bytes!("../../../templates/6.png")),
+    ("7", include_bytes!("../../../templates/7.png")),
+    ("8", include_bytes!("../../../templates/8.png")),
+    ("9", include_bytes!("../../../templates/9.png")),
+    ("10", include_bytes!("../../../templates/10.png")),
+    ("J", include_bytes!("../../../templates/J.png")),
+    ("Q", include_bytes!("../../../templates/Q.png")),
+    ("K", include_bytes!("../../../templates/K.png")),
+    ("clubs", include_bytes!("../../../templates/clubs.png")),
+    ("diamonds", include_bytes!("../../../templates/diamonds.png")),
+    ("hearts", include_bytes!("../../../templates/hearts.png")),
+    ("spades", include_bytes!("../../../templates/spades.png")),
+];
+
+/// The built-in templates, decoded the way [`detect::load_templates`]
+/// loads a directory.
+pub fn templates() -> Result<Vec<Template>> {
+    TEMPLATES
+        .iter()
+        .map(|(label, png)| {
+            Ok(Template {
+                label: label.to_string(),
+                image: detect::decode_image(png)?,
+                mask: detect::decode_mask(png)?,
+            })
+        })
+        .collect()
+}
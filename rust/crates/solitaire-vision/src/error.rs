@@ -0,0 +1,33 @@
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum VisionError {
+    #[error("failed to read templates directory {}: {source}", path.display())]
+    TemplateDir {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to load image {}", path.display())]
+    ImageLoad { path: PathBuf },
+
+    #[error("failed to decode image data")]
+    ImageDecode,
+
+    #[error("can't generate templates: {0}")]
+    TemplateGeneration(String),
+
+    #[error("unknown detector {name:?}, available: {}", available.join(", "))]
+    UnknownDetector { name: String, available: Vec<String> },
+
+    #[cfg(feature = "onnx")]
+    #[error("ONNX model {}: {reason}", path.display())]
+    Model { path: PathBuf, reason: String },
+
+    #[error("opencv error: {0}")]
+    OpenCv(#[from] opencv::Error),
+}
+
+pub type Result<T> = std::result::Result<T, VisionError>;
@@ -0,0 +1,213 @@
+//! Detection by keypoint features (ORB or AKAZE) with a homography check per
+//! match, for frames where template matching comes up empty.
+
+use crate::cache;
+use crate::config::{DetectionConfig, FeatureAlgorithm, FeatureConfig};
+use crate::detect::{self, BoundingBox, Detections, Matches, Template};
+use crate::error::Result;
+use crate::pipeline::Detector;
+use opencv::calib3d::{find_homography, RANSAC};
+use opencv::core::{
+    copy_make_border, no_array, perspective_transform, DMatch, KeyPoint, Mat, Point2f, Ptr, Scalar, Vector,
+    BORDER_REPLICATE, NORM_HAMMING,
+};
+use opencv::features2d::{Feature2D, BFMatcher, ORB, ORB_ScoreType, AKAZE};
+use opencv::prelude::*;
+use tracing::debug;
+
+// small enough for keypoints on rank and suit glyphs only a few dozen pixels wide
+const PATCH_SIZE: i32 = 15;
+
+/// A template's keypoints, computed once when the detector is built.
+#[derive(Debug, Clone)]
+struct FeatureTemplate {
+    label: String,
+    width: i32,
+    height: i32,
+    points: Vec<Point2f>,
+    descriptors: Mat,
+}
+
+/// Finds templates by matching frame keypoints against theirs. Several
+/// copies of a template, like the same rank in different columns, are found
+/// one homography at a time.
+#[derive(Debug, Clone)]
+pub struct FeatureDetector {
+    templates: Vec<FeatureTemplate>,
+    config: DetectionConfig,
+}
+
+impl FeatureDetector {
+    pub fn new(templates: &[Template], config: DetectionConfig) -> Result<Self> {
+        let mut extractor = extractor(&config.features)?;
+        let mut features = Vec::with_capacity(templates.len());
+        for template in templates {
+            // pad so corners near the glyph's edge still get keypoints
+            let mut padded = Mat::default();
+            copy_make_border(
+                &template.image,
+                &mut padded,
+                PATCH_SIZE,
+                PATCH_SIZE,
+                PATCH_SIZE,
+                PATCH_SIZE,
+                BORDER_REPLICATE,
+                Scalar::default(),
+            )?;
+            let (keypoints, descriptors) = keypoints(&mut extractor, &padded)?;
+            if keypoints.is_empty() {
+                debug!(label = %template.label, "template has no keypoints, skipping");
+                continue;
+            }
+            let offset = PATCH_SIZE as f32;
+            features.push(FeatureTemplate {
+                label: template.label.clone(),
+                width: template.image.cols(),
+                height: template.image.rows(),
+                points: keypoints.iter().map(|k| k.pt() - Point2f::new(offset, offset)).collect(),
+                descriptors,
+            });
+        }
+        Ok(Self { templates: features, config })
+    }
+
+    pub fn load(template_dir: &str, config: DetectionConfig) -> Result<Self> {
+        Self::new(&cache::load(template_dir)?, config)
+    }
+
+    pub fn config(&self) -> &DetectionConfig {
+        &self.config
+    }
+}
+
+impl Detector for FeatureDetector {
+    #[tracing::instrument(name = "detect_features", skip_all, fields(templates = self.templates.len()))]
+    fn detect(&self, frame: &Mat) -> Result<Detections> {
+        let features = &self.config.features;
+        let gray = detect::to_gray(frame)?;
+        let (keypoints, descriptors) = keypoints(&mut extractor(features)?, &gray)?;
+        debug!(keypoints = keypoints.len(), "frame keypoints");
+
+        let mut matches = Matches::default();
+        if !keypoints.is_empty() {
+            let frame_points: Vec<Point2f> = keypoints.iter().map(|k| k.pt()).collect();
+            let matcher = BFMatcher::new(NORM_HAMMING, false)?;
+            for template in &self.templates {
+                let boxes = locate(&matcher, template, &frame_points, &descriptors, features)?;
+                debug!(label = %template.label, matches = boxes.len(), "template located");
+                matches.add(&template.label, boxes);
+            }
+        }
+        matches.into_detections(frame, &self.config)
+    }
+}
+
+fn extractor(config: &FeatureConfig) -> Result<Ptr<Feature2D>> {
+    Ok(match config.algorithm {
+        FeatureAlgorithm::Orb => ORB::create(
+            config.max_features,
+            1.2,
+            8,
+            PATCH_SIZE,
+            0,
+            2,
+            ORB_ScoreType::HARRIS_SCORE,
+            PATCH_SIZE,
+            10,
+        )?
+        .into(),
+        FeatureAlgorithm::Akaze => AKAZE::create_def()?.into(),
+    })
+}
+
+fn keypoints(extractor: &mut Ptr<Feature2D>, img: &Mat) -> Result<(Vector<KeyPoint>, Mat)> {
+    let mut keypoints = Vector::new();
+    let mut descriptors = Mat::default();
+    extractor.detect_and_compute(img, &no_array(), &mut keypoints, &mut descriptors, false)?;
+    Ok((keypoints, descriptors))
+}
+
+// Every place `template` shows up in the frame: frame keypoints are matched
+// to the template's (that way round, so repeated copies don't fail the ratio
+// test against each other), then a homography is fitted to what's left of
+// the matches until too few of them agree on one.
+fn locate(
+    matcher: &BFMatcher,
+    template: &FeatureTemplate,
+    frame_points: &[Point2f],
+    frame_descriptors: &Mat,
+    config: &FeatureConfig,
+) -> Result<Vec<BoundingBox>> {
+    let mut knn = Vector::<Vector<DMatch>>::new();
+    matcher.knn_train_match_def(frame_descriptors, &template.descriptors, &mut knn, 2)?;
+    let mut good: Vec<DMatch> = knn
+        .iter()
+        .filter_map(|pair| match pair.as_slice() {
+            [best, second] if best.distance < config.ratio * second.distance => Some(*best),
+            [best] => Some(*best),
+            _ => None,
+        })
+        .collect();
+
+    let min_inliers = config.min_inliers.max(4);
+    let mut boxes = Vec::new();
+    while good.len() >= min_inliers {
+        let src: Vector<Point2f> = good.iter().map(|m| template.points[m.train_idx as usize]).collect();
+        let dst: Vector<Point2f> = good.iter().map(|m| frame_points[m.query_idx as usize]).collect();
+        let mut mask = Mat::default();
+        let homography = find_homography(&src, &dst, &mut mask, RANSAC, config.ransac_threshold)?;
+        if homography.empty() || mask.empty() {
+            break;
+        }
+
+        let mut inliers = Vec::new();
+        let mut rest = Vec::new();
+        for (i, m) in good.iter().enumerate() {
+            if *mask.at::<u8>(i as i32)? != 0 {
+                inliers.push(*m);
+            } else {
+                rest.push(*m);
+            }
+        }
+        if inliers.len() < min_inliers {
+            break;
+        }
+        good = rest;
+
+        if let Some(bounding_box) = project(template, &homography, inliers.len())? {
+            boxes.push(bounding_box);
+        }
+    }
+    Ok(boxes)
+}
+
+// The template's outline through `homography`, or `None` if it comes out
+// folded or a wildly different size, which means the inliers were a fluke.
+fn project(template: &FeatureTemplate, homography: &Mat, inliers: usize) -> Result<Option<BoundingBox>> {
+    let (w, h) = (template.width as f32, template.height as f32);
+    let corners: Vector<Point2f> = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)]
+        .into_iter()
+        .map(|(x, y)| Point2f::new(x, y))
+        .collect();
+    let mut projected = Vector::<Point2f>::new();
+    perspective_transform(&corners, &mut projected, homography)?;
+
+    let xs = projected.iter().map(|p| p.x);
+    let ys = projected.iter().map(|p| p.y);
+    let (x1, x2) = (xs.clone().fold(f32::MAX, f32::min), xs.fold(f32::MIN, f32::max));
+    let (y1, y2) = (ys.clone().fold(f32::MAX, f32::min), ys.fold(f32::MIN, f32::max));
+    let scale = ((x2 - x1) * (y2 - y1) / (w * h)).sqrt();
+    if !scale.is_finite() || !(0.5..=2.0).contains(&scale) {
+        debug!(label = %template.label, scale, "discarding implausible homography");
+        return Ok(None);
+    }
+
+    Ok(Some(BoundingBox {
+        x1: x1.round() as i32,
+        y1: y1.round() as i32,
+        x2: x2.round() as i32,
+        y2: y2.round() as i32,
+        label: template.label.clone(),
+        score: (inliers as f32 / template.points.len() as f32).min(1.0),
+    }))
+}
@@ -0,0 +1,78 @@
+//! Colorized maps of how a template scores at every position of a frame, for
+//! seeing whether a missed card scored just under its threshold or nowhere
+//! near it.
+
+use crate::config::DetectionConfig;
+use crate::detect::{self, Template};
+use crate::error::Result;
+use opencv::core::{copy_make_border, min_max_loc, no_array, patch_na_ns, Mat, Scalar, BORDER_CONSTANT, CV_8U};
+use opencv::imgproc::{apply_color_map, match_template, COLORMAP_JET, TM_CCOEFF_NORMED};
+use opencv::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::debug;
+
+/// Matches every template against the whole of `img` at its own size,
+/// preprocessed as detection does, and writes its scores into the existing
+/// directory `dir` as `<label>.png`, with `-2`, `-3` and so on added for
+/// variants of a label. Each map is the size of the frame with a template's
+/// score at its top-left corner, from blue at 0 or below to red at 1.
+/// Returns each file's name with the best score in it.
+#[tracing::instrument(skip(img, templates, config))]
+pub fn write_heatmaps(img: &Mat, templates: &[Template], config: &DetectionConfig, dir: &Path) -> Result<Vec<(String, f32)>> {
+    let gray = detect::to_gray(img)?;
+    let gray = if config.preprocess.is_enabled() {
+        crate::preprocess::preprocess(&gray, &config.preprocess)?
+    } else {
+        gray
+    };
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    let mut written = Vec::new();
+    for template in templates {
+        if template.image.cols() > gray.cols() || template.image.rows() > gray.rows() {
+            continue;
+        }
+        let image = if config.preprocess.is_enabled() {
+            crate::preprocess::preprocess(&template.image, &config.preprocess)?
+        } else {
+            template.image.clone()
+        };
+
+        let mut scores = Mat::default();
+        let no_mask = Mat::default();
+        match_template(&gray, &image, &mut scores, TM_CCOEFF_NORMED, template.mask.as_ref().unwrap_or(&no_mask))?;
+        // masked templates leave flat windows without a score
+        patch_na_ns(&mut scores, 0.0)?;
+        let mut best = 0.0;
+        min_max_loc(&scores, None, Some(&mut best), None, None, &no_array())?;
+
+        // negative scores saturate to 0, the same as no match at all
+        let mut levels = Mat::default();
+        scores.convert_to(&mut levels, CV_8U, 255.0, 0.0)?;
+        let mut colored = Mat::default();
+        apply_color_map(&levels, &mut colored, COLORMAP_JET)?;
+        let mut heatmap = Mat::default();
+        copy_make_border(
+            &colored,
+            &mut heatmap,
+            0,
+            image.rows() - 1,
+            0,
+            image.cols() - 1,
+            BORDER_CONSTANT,
+            Scalar::all(0.0),
+        )?;
+
+        let count = seen.entry(&template.label).or_default();
+        *count += 1;
+        let name = match *count {
+            1 => format!("{}.png", template.label),
+            n => format!("{}-{n}.png", template.label),
+        };
+        detect::save_image(&heatmap, &dir.join(&name).to_string_lossy())?;
+        debug!(name, best, "wrote heatmap");
+        written.push((name, best as f32));
+    }
+    Ok(written)
+}
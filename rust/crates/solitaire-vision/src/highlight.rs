@@ -0,0 +1,52 @@
+//! Finds the colored overlays the game draws around the cards of a suggested
+//! move and around a card selected by a click.
+
+use crate::config::HighlightConfig;
+use crate::detect::BoundingBox;
+use crate::error::Result;
+use opencv::core::{in_range, Mat, Point, Scalar, Vector};
+use opencv::imgproc::{bounding_rect, cvt_color, find_contours, CHAIN_APPROX_SIMPLE, COLOR_BGR2HSV, RETR_EXTERNAL};
+use opencv::prelude::*;
+use tracing::debug;
+
+/// Label of the boxes around hint highlights.
+pub const HIGHLIGHT_LABEL: &str = "highlight";
+/// Label of the boxes around selected cards.
+pub const SELECTED_LABEL: &str = "selected";
+
+/// Bounding boxes, labelled `label`, of the areas of the BGR `img` in the
+/// overlay's color and at least `min_area` of the frame in size.
+#[tracing::instrument(skip(img, config))]
+pub fn highlight_regions(img: &Mat, config: &HighlightConfig, label: &str) -> Result<Vec<BoundingBox>> {
+    let mut hsv = Mat::default();
+    cvt_color(img, &mut hsv, COLOR_BGR2HSV, 0)?;
+    let [min_hue, max_hue] = config.hue;
+    let mut mask = Mat::default();
+    in_range(
+        &hsv,
+        &Scalar::new(f64::from(min_hue), f64::from(config.min_saturation), f64::from(config.min_value), 0.0),
+        &Scalar::new(f64::from(max_hue), 255.0, 255.0, 0.0),
+        &mut mask,
+    )?;
+    let mut contours = Vector::<Vector<Point>>::new();
+    find_contours(&mask, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE, Point::default())?;
+
+    let frame_area = (img.cols() * img.rows()) as f32;
+    let mut regions = Vec::new();
+    for contour in &contours {
+        let rect = bounding_rect(&contour)?;
+        if (rect.area() as f32) < config.min_area * frame_area {
+            continue;
+        }
+        regions.push(BoundingBox {
+            x1: rect.x,
+            y1: rect.y,
+            x2: rect.x + rect.width,
+            y2: rect.y + rect.height,
+            label: label.to_string(),
+            score: 1.0,
+        });
+    }
+    debug!(contours = contours.len(), regions = regions.len(), "looked for overlay");
+    Ok(regions)
+}
@@ -0,0 +1,43 @@
+//! OpenCV-based detection of cards in Google Solitaire screenshots.
+//!
+//! [`detect`] template-matches ranks and suits, [`assemble`] turns the
+//! matches into a [`GameState`], and [`pipeline`] wires the two behind
+//! swappable traits. [`registry`] picks a detector backend by name, and
+//! [`features`] offers keypoint matching as an alternative to templates, as
+//! does `onnx`, a neural network detector behind the `onnx` feature.
+
+pub mod assemble;
+pub mod cache;
+pub mod classify;
+pub mod config;
+pub mod confusion;
+pub mod detect;
+pub mod diff;
+pub mod embedded;
+pub mod error;
+pub mod features;
+pub mod heatmap;
+pub mod highlight;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod opencl;
+pub mod perspective;
+pub mod pipeline;
+pub mod preprocess;
+pub mod registry;
+pub mod segment;
+pub mod stats;
+pub mod stock;
+pub mod templates;
+pub mod theme;
+
+pub use config::{
+    BoxClass, CalibrationConfig, ClassifyConfig, ConfusionConfig, DetectionConfig, DifferencingConfig, Equalization, FeatureAlgorithm, FeatureConfig, HighlightConfig, LayoutConfig, LayoutDescriptor,
+    NmsConfig, NmsMode, OcclusionConfig, OnnxConfig, PerspectiveConfig, PreprocessConfig, PyramidConfig, Region, SegmentationConfig,
+    StatsConfig, StockConfig, VerificationConfig,
+};
+pub use detect::{BoundingBox, Detections, Matches, Template};
+pub use features::FeatureDetector;
+pub use error::{Result, VisionError};
+pub use registry::{DetectorRegistry, DetectorSettings, DynDetector};
+pub use solitaire_state::GameState;
@@ -0,0 +1,126 @@
+//! Detection with an ONNX object detection model run by tract, producing the
+//! same boxes as template matching.
+
+use crate::config::DetectionConfig;
+use crate::detect::{BoundingBox, Detections, Matches};
+use crate::error::{Result, VisionError};
+use crate::pipeline::Detector;
+use opencv::core::{Mat, Size};
+use opencv::imgproc::{cvt_color, resize, COLOR_BGR2RGB, COLOR_GRAY2RGB, INTER_LINEAR};
+use opencv::prelude::*;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tract_onnx::prelude::*;
+use tracing::{debug, info};
+
+type Model = TypedRunnableModel<TypedModel>;
+
+/// Runs a model laid out as described by [`OnnxConfig`](crate::OnnxConfig)
+/// on each frame, stretched to the model's input size.
+pub struct OnnxDetector {
+    path: PathBuf,
+    model: Model,
+    config: DetectionConfig,
+}
+
+impl fmt::Debug for OnnxDetector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnnxDetector")
+            .field("path", &self.path)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl OnnxDetector {
+    pub fn load(path: impl Into<PathBuf>, config: DetectionConfig) -> Result<Self> {
+        let path = path.into();
+        let [width, height] = config.onnx.input_size;
+        let model = tract_onnx::onnx()
+            .model_for_path(&path)
+            .and_then(|model| model.with_input_fact(0, f32::fact([1, 3, height as usize, width as usize]).into()))
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(|e| model_error(&path, e))?;
+        info!(path = %path.display(), "loaded ONNX model");
+        Ok(Self { path, model, config })
+    }
+
+    pub fn config(&self) -> &DetectionConfig {
+        &self.config
+    }
+
+    // the frame as a 1x3xHxW RGB tensor scaled to 0..1
+    fn input(&self, frame: &Mat) -> Result<Tensor> {
+        let [width, height] = self.config.onnx.input_size;
+        let (width, height) = (width as usize, height as usize);
+        let mut rgb = Mat::default();
+        let code = if frame.channels() == 1 { COLOR_GRAY2RGB } else { COLOR_BGR2RGB };
+        cvt_color(frame, &mut rgb, code, 0)?;
+        let mut resized = Mat::default();
+        resize(&rgb, &mut resized, Size::new(width as i32, height as i32), 0.0, 0.0, INTER_LINEAR)?;
+
+        let pixels = resized.data_bytes()?;
+        let input = tract_ndarray::Array4::from_shape_fn((1, 3, height, width), |(_, c, y, x)| {
+            f32::from(pixels[(y * width + x) * 3 + c]) / 255.0
+        });
+        Ok(input.into())
+    }
+}
+
+impl Detector for OnnxDetector {
+    #[tracing::instrument(name = "detect_onnx", skip_all)]
+    fn detect(&self, frame: &Mat) -> Result<Detections> {
+        let onnx = &self.config.onnx;
+        let input = self.input(frame)?;
+        let outputs = self
+            .model
+            .run(tvec!(input.into()))
+            .map_err(|e| model_error(&self.path, e))?;
+        let output = outputs[0]
+            .to_array_view::<f32>()
+            .map_err(|e| model_error(&self.path, e))?;
+
+        let shape = output.shape();
+        if shape.len() != 3 || shape[1] != 4 + onnx.labels.len() {
+            return Err(model_error(
+                &self.path,
+                format!("expected an output of [1, {}, boxes], got {shape:?}", 4 + onnx.labels.len()),
+            ));
+        }
+
+        // back from the model's input size to the frame's
+        let sx = frame.cols() as f32 / onnx.input_size[0] as f32;
+        let sy = frame.rows() as f32 / onnx.input_size[1] as f32;
+        let mut matches = Matches::default();
+        for i in 0..shape[2] {
+            let (class, score) = (0..onnx.labels.len())
+                .map(|class| (class, output[[0, 4 + class, i]]))
+                .fold((0, f32::MIN), |best, candidate| if candidate.1 > best.1 { candidate } else { best });
+            if score < onnx.min_score {
+                continue;
+            }
+
+            let (cx, cy) = (output[[0, 0, i]], output[[0, 1, i]]);
+            let (w, h) = (output[[0, 2, i]], output[[0, 3, i]]);
+            let bounding_box = BoundingBox {
+                x1: ((cx - w / 2.0) * sx).round() as i32,
+                y1: ((cy - h / 2.0) * sy).round() as i32,
+                x2: ((cx + w / 2.0) * sx).round() as i32,
+                y2: ((cy + h / 2.0) * sy).round() as i32,
+                label: onnx.labels[class].clone(),
+                score,
+            };
+            matches.push(bounding_box);
+        }
+        debug!(candidates = shape[2], "ran ONNX model");
+        matches.into_detections(frame, &self.config)
+    }
+}
+
+fn model_error(path: &Path, reason: impl fmt::Display) -> VisionError {
+    VisionError::Model {
+        path: path.to_path_buf(),
+        reason: reason.to_string(),
+    }
+}
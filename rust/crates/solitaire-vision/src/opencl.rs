@@ -0,0 +1,62 @@
+//! Template matching on the GPU through OpenCV's transparent API: frames and
+//! templates are uploaded to `UMat`s, which OpenCV runs `match_template` on
+//! with OpenCL. Falls back to the CPU where OpenCL isn't available or fails.
+
+use opencv::core::{have_opencl, set_use_opencl, AccessFlag, Mat, UMat, UMatUsageFlags};
+use opencv::imgproc::{match_template as match_template_cpu, TM_CCOEFF_NORMED};
+use opencv::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+static AVAILABLE: OnceLock<bool> = OnceLock::new();
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// set once matching on the GPU failed, so the CPU is used from then on
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Turns matching on the GPU on or off for the whole process. Stays off if
+/// OpenCV wasn't built with OpenCL or finds no device.
+pub fn configure(enabled: bool) {
+    let available = *AVAILABLE.get_or_init(|| {
+        let available = have_opencl().unwrap_or(false) && set_use_opencl(true).is_ok();
+        if !available {
+            info!("OpenCL isn't available, matching templates on the CPU");
+        }
+        available
+    });
+    let on = enabled && available && !FAILED.load(Ordering::Relaxed);
+    if ENABLED.swap(on, Ordering::Relaxed) != on {
+        info!(opencl = on, "switched template matching device");
+    }
+}
+
+/// Whether templates are currently matched on the GPU.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// `match_template` with `TM_CCOEFF_NORMED` into `result`, on the GPU if
+/// [`configure`]d and on the CPU otherwise or if that fails. An empty
+/// `mask` compares every pixel.
+pub fn match_template(img: &Mat, template: &Mat, result: &mut Mat, mask: &Mat) -> opencv::Result<()> {
+    if is_enabled() {
+        match match_template_gpu(img, template, result, mask) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                warn!(error = %e, "matching on the GPU failed, falling back to the CPU");
+                FAILED.store(true, Ordering::Relaxed);
+                ENABLED.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+    match_template_cpu(img, template, result, TM_CCOEFF_NORMED, mask)
+}
+
+fn match_template_gpu(img: &Mat, template: &Mat, result: &mut Mat, mask: &Mat) -> opencv::Result<()> {
+    let upload = |mat: &Mat| mat.get_umat(AccessFlag::ACCESS_READ, UMatUsageFlags::USAGE_DEFAULT);
+    let (img, template) = (upload(img)?, upload(template)?);
+    let mask = if mask.empty() { UMat::new_def() } else { upload(mask)? };
+    let mut scores = UMat::new_def();
+    match_template_cpu(&img, &template, &mut scores, TM_CCOEFF_NORMED, &mask)?;
+    scores.copy_to(result)
+}
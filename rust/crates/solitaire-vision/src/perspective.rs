@@ -0,0 +1,95 @@
+//! Straightens photos of a physical game: finds the board's outline and
+//! warps it to a top-down view the rest of the pipeline can read like a
+//! screenshot.
+
+use crate::config::PerspectiveConfig;
+use crate::detect;
+use crate::error::Result;
+use opencv::core::{Mat, Point, Point2f, Size, Vector};
+use opencv::imgproc::{
+    approx_poly_dp, arc_length, canny_def, contour_area_def, dilate_def, find_contours, gaussian_blur_def,
+    get_perspective_transform_def, is_contour_convex, warp_perspective_def, CHAIN_APPROX_SIMPLE, RETR_EXTERNAL,
+};
+use opencv::prelude::*;
+use tracing::{debug, warn};
+
+/// Corners of the largest convex quadrilateral outline in `img` covering at
+/// least `min_area` of it, as top left, top right, bottom right and bottom
+/// left, or `None` if there is none.
+#[tracing::instrument(skip_all)]
+pub fn find_board(img: &Mat, config: &PerspectiveConfig) -> Result<Option<[Point2f; 4]>> {
+    let gray = detect::to_gray(img)?;
+    let mut blurred = Mat::default();
+    gaussian_blur_def(&gray, &mut blurred, Size::new(5, 5), 0.0)?;
+    let mut edges = Mat::default();
+    canny_def(&blurred, &mut edges, 50.0, 150.0)?;
+    // close small gaps in the outline
+    let mut closed = Mat::default();
+    dilate_def(&edges, &mut closed, &Mat::default())?;
+
+    let mut contours = Vector::<Vector<Point>>::new();
+    find_contours(&closed, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE, Point::default())?;
+
+    let min_area = f64::from(config.min_area) * f64::from(img.cols()) * f64::from(img.rows());
+    let mut best: Option<(f64, Vector<Point>)> = None;
+    for contour in &contours {
+        let area = contour_area_def(&contour)?;
+        if area < min_area || best.as_ref().is_some_and(|(best_area, _)| area <= *best_area) {
+            continue;
+        }
+        let mut approx = Vector::<Point>::new();
+        approx_poly_dp(&contour, &mut approx, 0.02 * arc_length(&contour, true)?, true)?;
+        if approx.len() == 4 && is_contour_convex(&approx)? {
+            best = Some((area, approx));
+        }
+    }
+    debug!(contours = contours.len(), found = best.is_some(), "looked for the board");
+
+    Ok(best.map(|(_, quad)| order_corners(quad.iter().map(|p| Point2f::new(p.x as f32, p.y as f32)))))
+}
+
+// top left has the smallest x + y, bottom right the largest; top right has
+// the largest x - y, bottom left the smallest
+fn order_corners(points: impl Iterator<Item = Point2f> + Clone) -> [Point2f; 4] {
+    let by = |key: fn(&Point2f) -> f32, largest: bool| {
+        let cmp = |a: &Point2f, b: &Point2f| key(a).total_cmp(&key(b));
+        let point = if largest { points.clone().max_by(cmp) } else { points.clone().min_by(cmp) };
+        point.expect("a quadrilateral has corners")
+    };
+    [
+        by(|p| p.x + p.y, false),
+        by(|p| p.x - p.y, true),
+        by(|p| p.x + p.y, true),
+        by(|p| p.x - p.y, false),
+    ]
+}
+
+/// The area inside `corners` warped to a `size` rectangle.
+pub fn warp_board(img: &Mat, corners: &[Point2f; 4], size: Size) -> Result<Mat> {
+    let (w, h) = (size.width as f32, size.height as f32);
+    let src: Vector<Point2f> = corners.iter().copied().collect();
+    let dst: Vector<Point2f> = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)]
+        .into_iter()
+        .map(|(x, y)| Point2f::new(x, y))
+        .collect();
+    let transform = get_perspective_transform_def(&src, &dst)?;
+    let mut warped = Mat::default();
+    warp_perspective_def(img, &mut warped, &transform, size)?;
+    Ok(warped)
+}
+
+/// `img` with the board warped to a top-down view at the configured size,
+/// or unchanged if no board outline was found.
+pub fn rectify(img: &Mat, config: &PerspectiveConfig) -> Result<Mat> {
+    match find_board(img, config)? {
+        Some(corners) => {
+            debug!(?corners, "warping board to top-down view");
+            let [width, height] = config.size;
+            warp_board(img, &corners, Size::new(width as i32, height as i32))
+        }
+        None => {
+            warn!("no board outline found, reading the photo as is");
+            Ok(img.clone())
+        }
+    }
+}
@@ -0,0 +1,450 @@
+use crate::assemble;
+use crate::cache;
+use crate::config::{DetectionConfig, LayoutConfig, LayoutDescriptor, PerspectiveConfig, Region};
+use crate::detect::{self, BoundingBox, Detections, Template};
+use crate::diff::Differ;
+use crate::error::Result;
+use crate::perspective;
+use crate::stats::StatsReader;
+use crate::stock;
+use solitaire_state::GameState;
+use opencv::core::{Mat, Size};
+use opencv::prelude::*;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+/// Produces the frames detection runs on.
+pub trait FrameSource {
+    fn next_frame(&mut self) -> Result<Mat>;
+}
+
+/// Finds rank and suit boxes in a frame.
+pub trait Detector {
+    fn detect(&self, frame: &Mat) -> Result<Detections>;
+
+    /// Finds boxes only within `regions`, as fractions of the frame, for a
+    /// [`Differ`] to rematch what changed. Backends that can't narrow their
+    /// search match the whole frame.
+    fn detect_in(&self, frame: &Mat, regions: &[Region]) -> Result<Detections> {
+        let _ = regions;
+        self.detect(frame)
+    }
+}
+
+/// Turns the detections of a frame into a game state.
+pub trait StateAssembler {
+    fn assemble(&self, detections: &Detections, frame: &Mat) -> GameState;
+}
+
+impl<T: StateAssembler + ?Sized> StateAssembler for Box<T> {
+    fn assemble(&self, detections: &Detections, frame: &Mat) -> GameState {
+        (**self).assemble(detections, frame)
+    }
+}
+
+impl<T: Detector + ?Sized> Detector for &T {
+    fn detect(&self, frame: &Mat) -> Result<Detections> {
+        (**self).detect(frame)
+    }
+
+    fn detect_in(&self, frame: &Mat, regions: &[Region]) -> Result<Detections> {
+        (**self).detect_in(frame, regions)
+    }
+}
+
+impl<T: Detector + ?Sized> Detector for Box<T> {
+    fn detect(&self, frame: &Mat) -> Result<Detections> {
+        (**self).detect(frame)
+    }
+
+    fn detect_in(&self, frame: &Mat, regions: &[Region]) -> Result<Detections> {
+        (**self).detect_in(frame, regions)
+    }
+}
+
+/// Reads a single image file from disk in color.
+#[derive(Debug, Clone)]
+pub struct FileSource {
+    path: String,
+}
+
+impl FileSource {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl FrameSource for FileSource {
+    fn next_frame(&mut self) -> Result<Mat> {
+        detect::load_color_image(&self.path)
+    }
+}
+
+/// Serves an image that is already in memory, e.g. a decoded screenshot.
+#[derive(Debug, Clone)]
+pub struct MemorySource {
+    frame: Mat,
+}
+
+impl MemorySource {
+    pub fn new(frame: Mat) -> Self {
+        Self { frame }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        Ok(Self::new(detect::decode_color_image(bytes)?))
+    }
+}
+
+impl FrameSource for MemorySource {
+    fn next_frame(&mut self) -> Result<Mat> {
+        Ok(self.frame.clone())
+    }
+}
+
+/// Straightens the frames of another source when `config` is enabled, for
+/// photos of a physical game taken at an angle.
+#[derive(Debug, Clone)]
+pub struct Rectified<S> {
+    source: S,
+    config: PerspectiveConfig,
+}
+
+impl<S> Rectified<S> {
+    pub fn new(source: S, config: PerspectiveConfig) -> Self {
+        Self { source, config }
+    }
+}
+
+impl<S: FrameSource> FrameSource for Rectified<S> {
+    fn next_frame(&mut self) -> Result<Mat> {
+        let frame = self.source.next_frame()?;
+        if !self.config.enabled {
+            return Ok(frame);
+        }
+        perspective::rectify(&frame, &self.config)
+    }
+}
+
+/// Called after each template has been matched against a frame, e.g. to drive a progress bar.
+pub type TemplateObserver = Arc<dyn Fn(&Template) + Send + Sync>;
+
+/// Template matching over a set of templates loaded once up front.
+#[derive(Clone)]
+pub struct TemplateDetector {
+    templates: Arc<Vec<Template>>,
+    config: DetectionConfig,
+    observer: Option<TemplateObserver>,
+    // templates resized by the calibrated scale, for the frame size they were calibrated on
+    calibrated: Arc<Mutex<Option<Calibrated>>>,
+    // the templates last matched, normalized as configured
+    prepared: Arc<Mutex<Option<Prepared>>>,
+}
+
+type Calibrated = (Size, Arc<Vec<Template>>);
+type Prepared = (Arc<Vec<Template>>, Arc<Vec<Template>>);
+
+impl fmt::Debug for TemplateDetector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TemplateDetector")
+            .field("templates", &self.templates)
+            .field("config", &self.config)
+            .field("observer", &self.observer.is_some())
+            .finish()
+    }
+}
+
+impl TemplateDetector {
+    pub fn new(templates: impl Into<Arc<Vec<Template>>>, config: DetectionConfig) -> Self {
+        Self {
+            templates: templates.into(),
+            config,
+            observer: None,
+            calibrated: Arc::default(),
+            prepared: Arc::default(),
+        }
+    }
+
+    pub fn with_observer(mut self, observer: TemplateObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Uses the templates in `template_dir`, shared through the [`cache`].
+    pub fn load(template_dir: &str, config: DetectionConfig) -> Result<Self> {
+        Ok(Self::new(cache::load(template_dir)?, config))
+    }
+
+    pub fn templates(&self) -> &[Template] {
+        &self.templates
+    }
+
+    pub fn config(&self) -> &DetectionConfig {
+        &self.config
+    }
+
+    /// Thresholds can be changed between frames without reloading templates.
+    pub fn config_mut(&mut self) -> &mut DetectionConfig {
+        self.calibrated = Arc::default();
+        self.prepared = Arc::default();
+        &mut self.config
+    }
+
+    /// The templates resized to the scale calibrated for frames the size of
+    /// `frame`, or as loaded if calibration is off.
+    fn calibrated_templates(&self, frame: &Mat) -> Result<Arc<Vec<Template>>> {
+        let calibration = &self.config.calibration;
+        if !calibration.enabled {
+            return Ok(self.templates.clone());
+        }
+        let size = frame.size()?;
+        let mut calibrated = self.calibrated.lock().expect("calibration lock poisoned");
+        if let Some((calibrated_size, templates)) = &*calibrated {
+            if *calibrated_size == size {
+                return Ok(templates.clone());
+            }
+        }
+
+        let reference = self.templates.iter().find(|template| template.label == calibration.reference);
+        let scale = match reference {
+            Some(reference) => detect::estimate_scale(&detect::to_gray(frame)?, &reference.image, calibration)?,
+            None => {
+                warn!(reference = %calibration.reference, "no calibration template with that label");
+                None
+            }
+        };
+        let templates = match scale {
+            Some((scale, score)) if (scale - 1.0).abs() > 0.01 => {
+                info!(scale, score, "calibrated template scale");
+                Arc::new(detect::scale_templates(&self.templates, scale)?)
+            }
+            Some(_) => self.templates.clone(),
+            None => {
+                warn!("calibration reference not found, using templates at their own size");
+                self.templates.clone()
+            }
+        };
+        *calibrated = Some((size, templates.clone()));
+        Ok(templates)
+    }
+
+    /// `templates` normalized as configured, done again only when they change.
+    fn prepared_templates(&self, templates: &Arc<Vec<Template>>) -> Result<Arc<Vec<Template>>> {
+        if !self.config.preprocess.is_enabled() {
+            return Ok(templates.clone());
+        }
+        let mut prepared = self.prepared.lock().expect("prepared templates lock poisoned");
+        if let Some((source, prepared)) = &*prepared {
+            if Arc::ptr_eq(source, templates) {
+                return Ok(prepared.clone());
+            }
+        }
+        let normalized = Arc::new(detect::prepare_templates(templates, &self.config.preprocess)?.into_owned());
+        *prepared = Some((templates.clone(), normalized.clone()));
+        Ok(normalized)
+    }
+
+    // matches the templates against `frame` under `config`
+    fn detect_with(&self, frame: &Mat, config: &DetectionConfig) -> Result<Detections> {
+        let templates = self.calibrated_templates(frame)?;
+        let prepared = self.prepared_templates(&templates)?;
+        let on_template: &(dyn Fn(&Template) + Sync) = match &self.observer {
+            Some(observer) => observer.as_ref(),
+            None => &|_| {},
+        };
+        detect::detect_prepared(frame, &templates, &prepared, config, on_template)
+    }
+}
+
+impl Detector for TemplateDetector {
+    fn detect(&self, frame: &Mat) -> Result<Detections> {
+        self.detect_with(frame, &self.config)
+    }
+
+    fn detect_in(&self, frame: &Mat, regions: &[Region]) -> Result<Detections> {
+        let config = DetectionConfig {
+            search_regions: regions.to_vec(),
+            ..self.config.clone()
+        };
+        self.detect_with(frame, &config)
+    }
+}
+
+/// Runs `primary`, and `fallback` instead whenever `primary` finds no cards.
+#[derive(Debug, Clone)]
+pub struct FallbackDetector<P, F> {
+    pub primary: P,
+    pub fallback: F,
+}
+
+impl<P, F> FallbackDetector<P, F> {
+    pub fn new(primary: P, fallback: F) -> Self {
+        Self { primary, fallback }
+    }
+}
+
+impl<P: Detector, F: Detector> Detector for FallbackDetector<P, F> {
+    fn detect(&self, frame: &Mat) -> Result<Detections> {
+        let detections = self.primary.detect(frame)?;
+        if !detections.cards.is_empty() {
+            return Ok(detections);
+        }
+        info!("primary detector found no cards, trying the fallback");
+        self.fallback.detect(frame)
+    }
+
+    fn detect_in(&self, frame: &Mat, regions: &[Region]) -> Result<Detections> {
+        let detections = self.primary.detect_in(frame, regions)?;
+        if !detections.cards.is_empty() {
+            return Ok(detections);
+        }
+        info!("primary detector found no cards, trying the fallback");
+        self.fallback.detect_in(frame, regions)
+    }
+}
+
+/// Picks the assembler for `layout`: a [`RegionAssembler`] when it describes
+/// its own regions, otherwise a [`ColumnAssembler`].
+pub fn layout_assembler(layout: LayoutConfig) -> Box<dyn StateAssembler + Send + Sync> {
+    let stats = layout.stats.clone();
+    let assembler: Box<dyn StateAssembler + Send + Sync> = match layout.regions.clone() {
+        Some(regions) => Box::new(RegionAssembler::new(regions, layout)),
+        None => Box::new(ColumnAssembler::new(layout)),
+    };
+    if !stats.is_enabled() {
+        return assembler;
+    }
+    match StatsReader::load(stats) {
+        Ok(reader) => Box::new(WithStats { assembler, reader }),
+        Err(e) => {
+            warn!(error = %e, "couldn't load digit templates, not reading the header");
+            assembler
+        }
+    }
+}
+
+/// Adds the header's score, time and moves to the states `assembler` makes.
+#[derive(Debug)]
+pub struct WithStats<A> {
+    pub assembler: A,
+    pub reader: StatsReader,
+}
+
+impl<A: StateAssembler> StateAssembler for WithStats<A> {
+    fn assemble(&self, detections: &Detections, frame: &Mat) -> GameState {
+        let mut state = self.assembler.assemble(detections, frame);
+        match self.reader.read(frame) {
+            Ok(stats) => state.stats = Some(stats),
+            Err(e) => warn!(error = %e, "couldn't read the header"),
+        }
+        state
+    }
+}
+
+/// Assigns detections to piles by splitting the frame into equal-width columns.
+#[derive(Debug, Clone)]
+pub struct ColumnAssembler {
+    layout: LayoutConfig,
+}
+
+impl ColumnAssembler {
+    pub fn new(layout: LayoutConfig) -> Self {
+        Self { layout }
+    }
+}
+
+impl StateAssembler for ColumnAssembler {
+    fn assemble(&self, detections: &Detections, frame: &Mat) -> GameState {
+        let mut state = assemble::generate_game_state(detections, frame.cols(), &self.layout);
+        // the stock is the first column
+        let boxes: Vec<BoundingBox> = detections
+            .cards
+            .iter()
+            .chain(&detections.backs)
+            .chain(detections.empty_slots.iter().flatten())
+            .cloned()
+            .collect();
+        let stock_end = assemble::column_ranges(&boxes, frame.cols(), &self.layout).first().map_or(0.0, |range| range.1);
+        let in_stock = |b: &BoundingBox| ((b.x1 + b.x2) as f32 / 2.0 / frame.cols() as f32) < stock_end;
+        state.stock_remaining = stock::stock_remaining(frame, detections, in_stock, &self.layout.stock);
+        state
+    }
+}
+
+/// Assigns detections to the piles of a [`LayoutDescriptor`] by which region they fall in.
+#[derive(Debug, Clone)]
+pub struct RegionAssembler {
+    regions: LayoutDescriptor,
+    layout: LayoutConfig,
+}
+
+impl RegionAssembler {
+    pub fn new(regions: LayoutDescriptor, layout: LayoutConfig) -> Self {
+        Self { regions, layout }
+    }
+}
+
+impl StateAssembler for RegionAssembler {
+    fn assemble(&self, detections: &Detections, frame: &Mat) -> GameState {
+        let mut state = assemble::generate_game_state_from_regions(
+            detections,
+            frame.cols(),
+            frame.rows(),
+            &self.regions,
+            &self.layout,
+        );
+        let (width, height) = (frame.cols() as f32, frame.rows() as f32);
+        let in_stock = |b: &BoundingBox| {
+            let x = (b.x1 + b.x2) as f32 / 2.0 / width;
+            let y = (b.y1 + b.y2) as f32 / 2.0 / height;
+            self.regions.stock.contains(x, y)
+        };
+        state.stock_remaining = stock::stock_remaining(frame, detections, in_stock, &self.layout.stock);
+        state
+    }
+}
+
+/// Everything produced by one pass through a [`Pipeline`].
+#[derive(Debug)]
+pub struct PipelineOutput {
+    pub frame: Mat,
+    pub detections: Detections,
+    pub state: GameState,
+}
+
+/// Capture → detect → assemble, with each stage swappable.
+pub struct Pipeline<S, D, A> {
+    pub source: S,
+    pub detector: D,
+    pub assembler: A,
+}
+
+impl<S: FrameSource, D: Detector, A: StateAssembler> Pipeline<S, D, A> {
+    pub fn new(source: S, detector: D, assembler: A) -> Self {
+        Self { source, detector, assembler }
+    }
+
+    /// Runs every stage on the source's next frame, matching all of it.
+    pub fn run(&mut self) -> Result<PipelineOutput> {
+        self.run_with(None)
+    }
+
+    /// Like [`run`](Self::run), but only rematches what `differ` finds
+    /// changed since the last frame it saw.
+    pub fn run_diffed(&mut self, differ: &mut Differ) -> Result<PipelineOutput> {
+        self.run_with(Some(differ))
+    }
+
+    #[tracing::instrument(name = "pipeline", skip_all)]
+    fn run_with(&mut self, differ: Option<&mut Differ>) -> Result<PipelineOutput> {
+        let frame = self.source.next_frame()?;
+        let detections = match differ {
+            Some(differ) => differ.detect(&self.detector, &frame)?,
+            None => self.detector.detect(&frame)?,
+        };
+        let state = self.assembler.assemble(&detections, &frame);
+
+        Ok(PipelineOutput { frame, detections, state })
+    }
+}
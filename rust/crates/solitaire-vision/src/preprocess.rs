@@ -0,0 +1,42 @@
+//! Normalizes greyscale frames and templates alike before they're matched,
+//! for themes and compression that throw the raw grey levels off.
+
+use crate::config::{Equalization, PreprocessConfig};
+use crate::error::Result;
+use opencv::core::{Mat, Size};
+use opencv::imgproc::{canny_def, create_clahe, equalize_hist, gaussian_blur_def};
+use opencv::prelude::*;
+
+/// The greyscale `img` equalized, blurred and turned into an edge map, each
+/// as far as `config` asks for.
+pub fn preprocess(img: &Mat, config: &PreprocessConfig) -> Result<Mat> {
+    let mut img = img.clone();
+    match config.equalize {
+        Equalization::None => {}
+        Equalization::Histogram => {
+            let mut equalized = Mat::default();
+            equalize_hist(&img, &mut equalized)?;
+            img = equalized;
+        }
+        Equalization::Clahe => {
+            let tile = config.clahe_tile_size.max(1);
+            let mut clahe = create_clahe(config.clahe_clip_limit, Size::new(tile, tile))?;
+            let mut equalized = Mat::default();
+            clahe.apply(&img, &mut equalized)?;
+            img = equalized;
+        }
+    }
+    if config.blur > 1 {
+        // Gaussian kernels need an odd size
+        let side = config.blur | 1;
+        let mut blurred = Mat::default();
+        gaussian_blur_def(&img, &mut blurred, Size::new(side, side), 0.0)?;
+        img = blurred;
+    }
+    if let Some([low, high]) = config.edges {
+        let mut edges = Mat::default();
+        canny_def(&img, &mut edges, low, high)?;
+        img = edges;
+    }
+    Ok(img)
+}
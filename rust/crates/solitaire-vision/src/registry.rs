@@ -0,0 +1,96 @@
+use crate::config::DetectionConfig;
+use crate::error::{Result, VisionError};
+use crate::features::FeatureDetector;
+use crate::pipeline::{Detector, FallbackDetector, TemplateDetector, TemplateObserver};
+use std::collections::BTreeMap;
+
+/// A detector backend chosen at runtime.
+pub type DynDetector = Box<dyn Detector + Send + Sync>;
+
+/// Builds a backend from the shared settings.
+pub type DetectorFactory = fn(&DetectorSettings) -> Result<DynDetector>;
+
+/// Everything a backend may need to construct itself. Backends ignore what
+/// doesn't apply to them.
+#[derive(Clone)]
+pub struct DetectorSettings {
+    pub template_dir: String,
+    pub detection: DetectionConfig,
+    /// Called after each template is matched, for backends that match templates.
+    pub observer: Option<TemplateObserver>,
+}
+
+/// Detector backends by name, as selected with `--detector`.
+#[derive(Clone)]
+pub struct DetectorRegistry {
+    factories: BTreeMap<&'static str, DetectorFactory>,
+}
+
+impl DetectorRegistry {
+    pub const DEFAULT: &'static str = "template";
+
+    pub fn empty() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a backend, replacing any registered under the same name.
+    pub fn register(&mut self, name: &'static str, factory: DetectorFactory) -> &mut Self {
+        self.factories.insert(name, factory);
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.factories.keys().copied()
+    }
+
+    pub fn create(&self, name: &str, settings: &DetectorSettings) -> Result<DynDetector> {
+        let factory = self.factories.get(name).ok_or_else(|| VisionError::UnknownDetector {
+            name: name.to_string(),
+            available: self.names().map(str::to_string).collect(),
+        })?;
+        factory(settings)
+    }
+}
+
+/// The backends built into this crate.
+impl Default for DetectorRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("template", template_detector);
+        registry.register("feature", feature_detector);
+        registry.register("template+feature", template_with_feature_fallback);
+        #[cfg(feature = "onnx")]
+        registry.register("onnx", onnx_detector);
+        registry
+    }
+}
+
+fn template_detector(settings: &DetectorSettings) -> Result<DynDetector> {
+    Ok(Box::new(load_template_detector(settings)?))
+}
+
+fn feature_detector(settings: &DetectorSettings) -> Result<DynDetector> {
+    Ok(Box::new(FeatureDetector::load(&settings.template_dir, settings.detection.clone())?))
+}
+
+fn template_with_feature_fallback(settings: &DetectorSettings) -> Result<DynDetector> {
+    let template = load_template_detector(settings)?;
+    let features = FeatureDetector::new(template.templates(), settings.detection.clone())?;
+    Ok(Box::new(FallbackDetector::new(template, features)))
+}
+
+#[cfg(feature = "onnx")]
+fn onnx_detector(settings: &DetectorSettings) -> Result<DynDetector> {
+    let model = &settings.detection.onnx.model;
+    Ok(Box::new(crate::onnx::OnnxDetector::load(model, settings.detection.clone())?))
+}
+
+fn load_template_detector(settings: &DetectorSettings) -> Result<TemplateDetector> {
+    let mut detector = TemplateDetector::load(&settings.template_dir, settings.detection.clone())?;
+    if let Some(observer) = &settings.observer {
+        detector = detector.with_observer(observer.clone());
+    }
+    Ok(detector)
+}
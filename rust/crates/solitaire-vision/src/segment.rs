@@ -0,0 +1,35 @@
+//! Finds the white faces of cards, so ranks and suits are only looked for
+//! where a card actually is and not in the page around the game.
+
+use crate::config::SegmentationConfig;
+use crate::error::Result;
+use opencv::core::{Mat, Point, Rect, Vector};
+use opencv::imgproc::{bounding_rect, find_contours, threshold, CHAIN_APPROX_SIMPLE, RETR_EXTERNAL, THRESH_BINARY};
+use opencv::prelude::*;
+use tracing::debug;
+
+/// Bounding rectangles of the bright blobs in the greyscale `img` that are
+/// sized like a card or a stack of them, grown by the configured padding.
+#[tracing::instrument(skip_all)]
+pub fn card_regions(img: &Mat, config: &SegmentationConfig) -> Result<Vec<Rect>> {
+    let mut mask = Mat::default();
+    threshold(img, &mut mask, f64::from(config.white_threshold), 255.0, THRESH_BINARY)?;
+    let mut contours = Vector::<Vector<Point>>::new();
+    find_contours(&mask, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE, Point::default())?;
+
+    let bounds = Rect::new(0, 0, img.cols(), img.rows());
+    let frame_area = bounds.area() as f32;
+    let mut regions = Vec::new();
+    for contour in &contours {
+        let rect = bounding_rect(&contour)?;
+        let area = rect.area() as f32 / frame_area;
+        if area < config.min_area || area > config.max_area {
+            continue;
+        }
+        let padding = config.padding;
+        let padded = Rect::new(rect.x - padding, rect.y - padding, rect.width + 2 * padding, rect.height + 2 * padding);
+        regions.push(padded & bounds);
+    }
+    debug!(contours = contours.len(), regions = regions.len(), "segmented cards");
+    Ok(regions)
+}
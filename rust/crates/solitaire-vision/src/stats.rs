@@ -0,0 +1,97 @@
+//! Reads the score, time and move counter from the game's header by matching
+//! digit templates in their regions.
+
+use crate::config::{NmsConfig, Region, StatsConfig};
+use crate::detect::{self, BoundingBox, Template};
+use crate::error::Result;
+use opencv::core::{Mat, Rect};
+use opencv::prelude::*;
+use solitaire_state::Stats;
+use tracing::{debug, info};
+
+// digits of a number sit side by side, so any real overlap is a second match
+// of the same one
+const DIGIT_OVERLAP: f32 = 0.3;
+
+/// Matches the digit templates `0.png` to `9.png` in each configured header
+/// region and reads the digits left to right.
+#[derive(Debug, Clone)]
+pub struct StatsReader {
+    digits: Vec<Template>,
+    config: StatsConfig,
+}
+
+impl StatsReader {
+    pub fn new(digits: Vec<Template>, config: StatsConfig) -> Self {
+        Self { digits, config }
+    }
+
+    /// Loads the digit templates from the configured directory; other
+    /// templates there, like separators, are ignored.
+    pub fn load(config: StatsConfig) -> Result<Self> {
+        let digits: Vec<Template> = detect::load_templates(&config.digits_dir)?
+            .into_iter()
+            .filter(|template| template.label.len() == 1 && template.label.chars().all(|c| c.is_ascii_digit()))
+            .collect();
+        info!(dir = %config.digits_dir, digits = digits.len(), "loaded digit templates");
+        Ok(Self::new(digits, config))
+    }
+
+    /// The numbers shown in `frame`'s header, each `None` if its region isn't
+    /// configured or no digits were found in it. The time is in seconds.
+    #[tracing::instrument(skip_all)]
+    pub fn read(&self, frame: &Mat) -> Result<Stats> {
+        let gray = detect::to_gray(frame)?;
+        let read = |region: Option<Region>| match region {
+            Some(region) => self.read_digits(&gray, &region),
+            None => Ok(None),
+        };
+        let stats = Stats {
+            score: read(self.config.score)?.and_then(|digits| digits.parse().ok()),
+            time: read(self.config.time)?.and_then(|digits| seconds(&digits)),
+            moves: read(self.config.moves)?.and_then(|digits| digits.parse().ok()),
+        };
+        debug!(?stats, "read header");
+        Ok(stats)
+    }
+
+    fn read_digits(&self, gray: &Mat, region: &Region) -> Result<Option<String>> {
+        let area = detect::region_rect(region, Rect::new(0, 0, gray.cols(), gray.rows()));
+        let crop = Mat::roi(gray, area)?.try_clone()?;
+
+        let mut boxes = Vec::new();
+        for digit in &self.digits {
+            if digit.image.cols() > crop.cols() || digit.image.rows() > crop.rows() {
+                continue;
+            }
+            let matches = detect::match_template_masked(&crop, &digit.image, digit.mask.as_ref(), self.config.threshold)?;
+            boxes.extend(detect::create_bounding_boxes(
+                matches,
+                digit.image.cols(),
+                digit.image.rows(),
+                digit.label.clone(),
+            ));
+        }
+        let mut digits = detect::non_maximum_suppression(boxes, DIGIT_OVERLAP, &NmsConfig::default());
+        digits.sort_by_key(|b: &BoundingBox| b.x1);
+
+        let text: String = digits.iter().map(|b| b.label.as_str()).collect();
+        Ok((!text.is_empty()).then_some(text))
+    }
+}
+
+// The digits of a clock read right to left as seconds, minutes and hours,
+// two each, since separators aren't matched: "1234" is 12:34.
+fn seconds(digits: &str) -> Option<u32> {
+    let mut seconds = 0;
+    let mut unit = 1;
+    let mut rest = digits;
+    while !rest.is_empty() {
+        let split = rest.len().saturating_sub(2);
+        let (head, field) = rest.split_at(split);
+        seconds += field.parse::<u32>().ok()? * unit;
+        unit *= 60;
+        rest = head;
+    }
+    Some(seconds)
+}
@@ -0,0 +1,60 @@
+//! Estimates how many cards are left in the stock from the edges of the
+//! face-down stack showing under its top card.
+
+use crate::config::StockConfig;
+use crate::detect::{self, BoundingBox, Detections};
+use crate::error::Result;
+use opencv::core::Mat;
+use opencv::prelude::*;
+use tracing::{debug, warn};
+
+/// Cards left in the stock: 0 if its empty slot was detected, an estimate
+/// from its stack's edges if its back was, `None` if neither or if
+/// estimation is off. `in_stock` says whether a box lies in the stock.
+pub fn stock_remaining(
+    frame: &Mat,
+    detections: &Detections,
+    in_stock: impl Fn(&BoundingBox) -> bool,
+    config: &StockConfig,
+) -> Option<u32> {
+    if !config.enabled {
+        return None;
+    }
+    // the top card of the stack is the topmost back
+    let Some(back) = detections.backs.iter().filter(|b| in_stock(b)).min_by_key(|b| b.y1) else {
+        let empty = detections.empty_slots.iter().flatten().any(&in_stock);
+        return empty.then_some(0);
+    };
+    match count_edges(frame, back, config) {
+        Ok(edges) => {
+            let remaining = (1 + edges) * config.cards_per_edge;
+            debug!(edges, remaining, "estimated stock");
+            Some(remaining)
+        }
+        Err(e) => {
+            warn!(error = %e, "couldn't measure the stock");
+            None
+        }
+    }
+}
+
+// Dark card borders crossed going down from the bottom of `back`, offset by
+// `start_offset`, over `max_depth` pixels at its horizontal center.
+fn count_edges(frame: &Mat, back: &BoundingBox, config: &StockConfig) -> Result<u32> {
+    let gray = detect::to_gray(frame)?;
+    let x = ((back.x1 + back.x2) / 2).clamp(0, gray.cols() - 1);
+    let start = (back.y2 + config.start_offset).max(0);
+    let end = (start + config.max_depth).min(gray.rows());
+
+    let mut edges = 0;
+    let mut previous: Option<u8> = None;
+    for y in start..end {
+        let value = *gray.at_2d::<u8>(y, x)?;
+        // counting only bright to dark, so each border counts once
+        if previous.is_some_and(|previous| previous.saturating_sub(value) >= config.min_contrast) {
+            edges += 1;
+        }
+        previous = Some(value);
+    }
+    Ok(edges)
+}
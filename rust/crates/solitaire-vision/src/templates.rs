@@ -0,0 +1,325 @@
+use crate::cache;
+use crate::config::SegmentationConfig;
+use crate::detect::{self, BACK_LABEL, EMPTY_LABEL, RANK_LABELS, SUIT_LABELS, TEN_DIGITS};
+use crate::error::{Result, VisionError};
+use crate::segment;
+use opencv::core::{Mat, Rect};
+use opencv::prelude::*;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::{fs, path::Path};
+use tracing::{debug, info};
+
+/// Templates whose height differs from the median of their class by more
+/// than this fraction are reported as inconsistent.
+const HEIGHT_TOLERANCE: f32 = 0.25;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateStatus {
+    Ok { width: i32, height: i32 },
+    Missing,
+    Unreadable(String),
+    /// Loaded, but its height is far from the other templates of its class.
+    Inconsistent { width: i32, height: i32, expected_height: i32 },
+    /// A PNG in the directory that isn't one of the known labels.
+    Unexpected,
+}
+
+#[derive(Debug, Clone)]
+pub struct TemplateCheck {
+    pub label: String,
+    pub status: TemplateStatus,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TemplateReport {
+    pub checks: Vec<TemplateCheck>,
+}
+
+impl TemplateReport {
+    /// Whether every rank and suit template is present, loadable and consistently sized.
+    pub fn is_complete(&self) -> bool {
+        self.checks.iter().all(|check| {
+            matches!(check.status, TemplateStatus::Ok { .. } | TemplateStatus::Unexpected)
+        })
+    }
+}
+
+/// Verifies that `dir` holds a loadable template for every rank and suit,
+/// as a PNG or a directory of variants, and that templates of the same class
+/// are of similar size.
+pub fn check(dir: &str) -> Result<TemplateReport> {
+    let dir_path = Path::new(dir);
+    let entries = fs::read_dir(dir_path).map_err(|source| VisionError::TemplateDir {
+        path: dir_path.into(),
+        source,
+    })?;
+
+    let mut report = TemplateReport {
+        checks: check_class(dir_path, &RANK_LABELS),
+    };
+    report.checks.extend(check_class(dir_path, &SUIT_LABELS));
+
+    let mut unexpected: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() || path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .filter(|label| {
+            !RANK_LABELS.contains(&label.as_str())
+                && !SUIT_LABELS.contains(&label.as_str())
+                && !TEN_DIGITS.contains(&label.as_str())
+                && label != BACK_LABEL
+                && label != EMPTY_LABEL
+                && detect::foundation_suit(label).is_none()
+                && label != detect::WON_LABEL
+                && label != detect::LOST_LABEL
+        })
+        .collect();
+    unexpected.sort();
+    report.checks.extend(unexpected.into_iter().map(|label| TemplateCheck {
+        label,
+        status: TemplateStatus::Unexpected,
+    }));
+
+    Ok(report)
+}
+
+fn check_class(dir: &Path, labels: &[&str]) -> Vec<TemplateCheck> {
+    let mut checks: Vec<TemplateCheck> = labels
+        .iter()
+        .map(|label| {
+            let variants = dir.join(label);
+            let path = if variants.is_dir() {
+                // the first variant stands in for the others
+                first_png(&variants).unwrap_or(variants)
+            } else {
+                dir.join(format!("{label}.png"))
+            };
+            let status = if !path.is_file() {
+                TemplateStatus::Missing
+            } else {
+                match detect::load_image(&path.to_string_lossy()) {
+                    Ok(image) => TemplateStatus::Ok {
+                        width: image.cols(),
+                        height: image.rows(),
+                    },
+                    Err(e) => TemplateStatus::Unreadable(e.to_string()),
+                }
+            };
+            TemplateCheck {
+                label: label.to_string(),
+                status,
+            }
+        })
+        .collect();
+
+    let mut heights: Vec<i32> = checks
+        .iter()
+        .filter_map(|check| match check.status {
+            TemplateStatus::Ok { height, .. } => Some(height),
+            _ => None,
+        })
+        .collect();
+    if heights.is_empty() {
+        return checks;
+    }
+    heights.sort_unstable();
+    let median = heights[heights.len() / 2];
+
+    for check in &mut checks {
+        if let TemplateStatus::Ok { width, height } = check.status {
+            let deviation = (height - median).abs() as f32 / median as f32;
+            if deviation > HEIGHT_TOLERANCE {
+                check.status = TemplateStatus::Inconsistent {
+                    width,
+                    height,
+                    expected_height: median,
+                };
+            }
+        }
+    }
+
+    checks
+}
+
+fn first_png(dir: &Path) -> Option<PathBuf> {
+    let mut pngs: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")))
+        .collect();
+    pngs.sort();
+    pngs.into_iter().next()
+}
+
+/// Grey level below which a pixel of a card face counts as printed.
+const INK_LEVEL: u8 = 160;
+
+/// How [`generate`] finds cards in reference screenshots.
+#[derive(Debug, Clone)]
+pub struct GenerateOptions {
+    /// Suit of each row of cards, top to bottom and screenshot after
+    /// screenshot. Every row holds the 13 ranks from A to K, left to right.
+    pub suits: Vec<String>,
+    /// Part of a card's width and height, from its top left corner, that
+    /// holds its rank and, below it, its suit.
+    pub corner: f32,
+    /// Finds the card faces.
+    pub segmentation: SegmentationConfig,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            suits: SUIT_LABELS.iter().map(|suit| suit.to_string()).collect(),
+            corner: 0.3,
+            segmentation: SegmentationConfig::default(),
+        }
+    }
+}
+
+/// A template written by [`generate`].
+#[derive(Debug, Clone)]
+pub struct GeneratedTemplate {
+    pub label: String,
+    pub path: PathBuf,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Cuts rank and suit templates out of color screenshots whose cards are
+/// laid out in rows as `options` describes, and saves them to `out`. Ranks
+/// are taken from the first row and suits from the first column, at the
+/// size they're shown at.
+#[tracing::instrument(skip_all, fields(screenshots = screenshots.len()))]
+pub fn generate(screenshots: &[Mat], out: &Path, options: &GenerateOptions) -> Result<Vec<GeneratedTemplate>> {
+    let mut rows: Vec<(&Mat, Vec<Rect>)> = Vec::new();
+    for screenshot in screenshots {
+        let gray = detect::to_gray(screenshot)?;
+        let cards = segment::card_regions(&gray, &options.segmentation)?;
+        rows.extend(card_rows(cards).into_iter().map(|row| (screenshot, row)));
+    }
+    if rows.len() != options.suits.len() {
+        return Err(VisionError::TemplateGeneration(format!(
+            "found {} rows of cards for {} suits",
+            rows.len(),
+            options.suits.len()
+        )));
+    }
+    for (i, (_, row)) in rows.iter().enumerate() {
+        if row.len() != RANK_LABELS.len() {
+            return Err(VisionError::TemplateGeneration(format!(
+                "row {} has {} cards, expected {}",
+                i + 1,
+                row.len(),
+                RANK_LABELS.len()
+            )));
+        }
+    }
+
+    fs::create_dir_all(out).map_err(|e| VisionError::TemplateGeneration(format!("{}: {e}", out.display())))?;
+    let mut generated = Vec::new();
+    let mut save = |label: &str, screenshot: &Mat, glyph: Rect| -> Result<()> {
+        let path = out.join(format!("{label}.png"));
+        detect::save_image(&Mat::roi(screenshot, glyph)?.try_clone()?, &path.to_string_lossy())?;
+        info!(label, path = %path.display(), width = glyph.width, height = glyph.height, "saved template");
+        generated.push(GeneratedTemplate {
+            label: label.to_string(),
+            path,
+            width: glyph.width,
+            height: glyph.height,
+        });
+        Ok(())
+    };
+
+    let (first, first_row) = &rows[0];
+    for (rank, card) in RANK_LABELS.iter().zip(first_row) {
+        let (glyph, _) = corner_glyphs(first, *card, options.corner)?;
+        save(rank, first, glyph)?;
+    }
+    for (suit, (screenshot, row)) in options.suits.iter().zip(&rows) {
+        let (_, glyph) = corner_glyphs(screenshot, row[0], options.corner)?;
+        save(suit, screenshot, glyph)?;
+    }
+    // detectors built from here on should see the new files
+    cache::clear();
+    Ok(generated)
+}
+
+// card rectangles grouped into rows, top to bottom, each left to right
+fn card_rows(mut cards: Vec<Rect>) -> Vec<Vec<Rect>> {
+    cards.sort_by_key(|card| card.y + card.height / 2);
+    let mut rows: Vec<Vec<Rect>> = Vec::new();
+    for card in cards {
+        let center = card.y + card.height / 2;
+        match rows.last_mut() {
+            Some(row) if (center - (row[0].y + row[0].height / 2)).abs() < row[0].height / 2 => row.push(card),
+            _ => rows.push(vec![card]),
+        }
+    }
+    for row in &mut rows {
+        row.sort_by_key(|card| card.x);
+    }
+    debug!(rows = rows.len(), "grouped cards into rows");
+    rows
+}
+
+// the rank and suit in the top left corner of `card`, as the first two bands
+// of printed rows, in frame coordinates
+fn corner_glyphs(screenshot: &Mat, card: Rect, corner: f32) -> Result<(Rect, Rect)> {
+    // skip the card's outline
+    let margin = (card.width / 40).max(2);
+    let area = Rect::new(
+        card.x + margin,
+        card.y + margin,
+        (card.width as f32 * corner) as i32,
+        (card.height as f32 * corner) as i32,
+    );
+    let gray = detect::to_gray(&Mat::roi(screenshot, area)?.try_clone()?)?;
+    let inked = |ys: Range<i32>, xs: Range<i32>| -> Result<bool> {
+        for y in ys {
+            for x in xs.clone() {
+                if *gray.at_2d::<u8>(y, x)? < INK_LEVEL {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    };
+
+    let mut bands: Vec<(i32, i32)> = Vec::new();
+    let mut start = None;
+    for y in 0..=gray.rows() {
+        let row_inked = y < gray.rows() && inked(y..y + 1, 0..gray.cols())?;
+        match (row_inked, start) {
+            (true, None) => start = Some(y),
+            (false, Some(from)) => {
+                bands.push((from, y));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    let glyph = |(top, bottom): (i32, i32)| -> Result<Rect> {
+        let mut columns = Vec::new();
+        for x in 0..gray.cols() {
+            if inked(top..bottom, x..x + 1)? {
+                columns.push(x);
+            }
+        }
+        let (left, right) = (columns[0], columns[columns.len() - 1] + 1);
+        // a pixel of background around the glyph, as in hand-cut templates
+        let rect = Rect::new(area.x + left - 1, area.y + top - 1, right - left + 2, bottom - top + 2);
+        Ok(rect & Rect::new(0, 0, screenshot.cols(), screenshot.rows()))
+    };
+    match bands.as_slice() {
+        [rank, suit, ..] => Ok((glyph(*rank)?, glyph(*suit)?)),
+        _ => Err(VisionError::TemplateGeneration(format!(
+            "no rank and suit found in the corner of the card at {}, {}",
+            card.x, card.y
+        ))),
+    }
+}
@@ -0,0 +1,54 @@
+//! Tells the game's visual themes apart by the color of their background.
+
+use crate::error::Result;
+use opencv::core::{Mat, Size, Vec3b};
+use opencv::imgproc::{resize, INTER_AREA};
+use opencv::prelude::*;
+use std::collections::HashMap;
+use tracing::debug;
+
+// side of the thumbnail colors are counted on, and bits of each channel kept
+// when binning them, so gradients and compression noise share a bin
+const THUMBNAIL_SIZE: i32 = 64;
+const BIN_SHIFT: u8 = 3;
+
+/// The most common color of the BGR `img`, as `[r, g, b]`. Cards and UI
+/// cover less of a board than the background, so this is its color.
+pub fn background_color(img: &Mat) -> Result<[u8; 3]> {
+    let mut small = Mat::default();
+    resize(img, &mut small, Size::new(THUMBNAIL_SIZE, THUMBNAIL_SIZE), 0.0, 0.0, INTER_AREA)?;
+
+    let mut bins: HashMap<[u8; 3], (u32, [u32; 3])> = HashMap::new();
+    for pixel in small.data_typed::<Vec3b>()? {
+        let [b, g, r] = pixel.0;
+        let (count, sum) = bins.entry([r >> BIN_SHIFT, g >> BIN_SHIFT, b >> BIN_SHIFT]).or_default();
+        *count += 1;
+        for (total, channel) in sum.iter_mut().zip([r, g, b]) {
+            *total += u32::from(channel);
+        }
+    }
+    let (count, sum) = bins.into_values().max_by_key(|(count, _)| *count).unwrap_or_default();
+    // the mean of the bin rather than its corner
+    Ok(sum.map(|total| (total / count.max(1)) as u8))
+}
+
+/// Of `themes`, named with their background colors as `[r, g, b]`, the one
+/// whose background is closest to `img`'s, if it's within `max_distance`.
+#[tracing::instrument(skip_all)]
+pub fn identify<'a>(img: &Mat, themes: impl IntoIterator<Item = (&'a str, [u8; 3])>, max_distance: f64) -> Result<Option<&'a str>> {
+    let background = background_color(img)?;
+    let distance = |color: [u8; 3]| {
+        background
+            .iter()
+            .zip(color)
+            .map(|(&a, b)| (f64::from(a) - f64::from(b)).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    };
+    let closest = themes
+        .into_iter()
+        .map(|(name, color)| (name, distance(color)))
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    debug!(?background, ?closest, "identified theme");
+    Ok(closest.filter(|&(_, distance)| distance <= max_distance).map(|(name, _)| name))
+}
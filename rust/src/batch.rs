@@ -0,0 +1,133 @@
+use crate::config::Config;
+use crate::error::{Result, SolitaireOcrError};
+use crate::journal::{self, Journal};
+use crate::vision::cache;
+use crate::pipeline::Detector;
+use crate::state::GameState;
+use std::{fs, path::{Path, PathBuf}, sync::Arc};
+use tracing::info;
+
+/// Outcome of translating one screenshot in a batch run.
+#[derive(Debug)]
+pub struct BatchItem {
+    pub input: PathBuf,
+    pub state_path: PathBuf,
+    pub annotated_path: Option<PathBuf>,
+    pub result: Result<GameState>,
+}
+
+/// Receives progress events from a batch run. Every method defaults to doing nothing.
+pub trait Progress: Send + Sync {
+    /// Called once before the first image, with the number of images and templates.
+    fn begin(&self, _images: usize, _templates: usize) {}
+    fn image_started(&self, _input: &Path) {}
+    /// Called after each template has been matched against the current image.
+    fn template_matched(&self, _label: &str) {}
+    fn image_finished(&self, _item: &BatchItem) {}
+    fn finish(&self) {}
+}
+
+/// Discards all progress events.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl Progress for NoProgress {}
+
+/// Translates every PNG in `input_dir`, writing `<name>.json` and, when
+/// `annotate` is set, `<name>_boxes.png` for each into `output_dir`.
+///
+/// Templates are loaded once up front. A failure on one image is recorded in
+/// its [`BatchItem`] and doesn't stop the rest of the batch.
+///
+/// Every result is also recorded in a [`Journal`] in `output_dir`. With
+/// `resume` set, images the journal already records as translated are skipped
+/// and left out of the returned items.
+pub fn translate_dir(
+    input_dir: &Path,
+    output_dir: &Path,
+    annotate: bool,
+    resume: bool,
+    config: &Config,
+    progress: Arc<dyn Progress>,
+) -> Result<Vec<BatchItem>> {
+    let mut images = list_images(input_dir)?;
+    if resume {
+        let completed = Journal::completed(output_dir)?;
+        let total = images.len();
+        images.retain(|input| !completed.contains(&journal::file_name(input)));
+        info!("Resuming: {} of {total} images already translated", total - images.len());
+    }
+
+    let observer = Arc::clone(&progress);
+    let detector = config.detector_observed(Some(Arc::new(move |template| {
+        observer.template_matched(&template.label)
+    })))?;
+
+    fs::create_dir_all(output_dir).map_err(|source| SolitaireOcrError::Io {
+        path: output_dir.into(),
+        source,
+    })?;
+    let mut journal = Journal::open(output_dir, resume)?;
+
+    let templates = cache::load(&config.template_dir).map_or(0, |templates| templates.len());
+    progress.begin(images.len(), templates);
+    let mut items = Vec::with_capacity(images.len());
+    for input in images {
+        progress.image_started(&input);
+        let item = translate_into(&detector, input, output_dir, annotate, config);
+        progress.image_finished(&item);
+        journal.record(&item)?;
+        items.push(item);
+    }
+    progress.finish();
+
+    Ok(items)
+}
+
+/// Translates a single image, naming its outputs after it inside `output_dir`.
+pub fn translate_into(
+    detector: &impl Detector,
+    input: PathBuf,
+    output_dir: &Path,
+    annotate: bool,
+    config: &Config,
+) -> BatchItem {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    let state_path = output_dir.join(format!("{stem}.json"));
+    let annotated_path = annotate.then(|| output_dir.join(format!("{stem}_boxes.png")));
+
+    let result = crate::translate_with(
+        detector,
+        &input.to_string_lossy(),
+        annotated_path.as_ref().map(|path| path.to_string_lossy()).as_deref(),
+        Some(&state_path.to_string_lossy()),
+        config,
+    );
+
+    BatchItem {
+        input,
+        state_path,
+        annotated_path,
+        result,
+    }
+}
+
+/// PNG files directly inside `dir`, sorted by name.
+pub fn list_images(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = fs::read_dir(dir).map_err(|source| SolitaireOcrError::InputDir {
+        path: dir.into(),
+        source,
+    })?;
+
+    let mut images: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+        })
+        .collect();
+    images.sort();
+
+    Ok(images)
+}
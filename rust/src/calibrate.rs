@@ -0,0 +1,166 @@
+use crate::config::Config;
+use crate::{detect, vision};
+use crate::error::{Result, SolitaireOcrError};
+use crate::pipeline::{self, FileSource, FrameSource, Rectified};
+use crate::state::GameState;
+use solitaire_vision::DetectionConfig;
+use std::fs;
+use std::path::Path;
+use tracing::{debug, info};
+
+/// Card and suit thresholds to try, each from `min` to `max` in `step`s.
+#[derive(Debug, Clone, Copy)]
+pub struct Sweep {
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+}
+
+impl Sweep {
+    fn values(&self) -> impl Iterator<Item = f32> + Clone + '_ {
+        let steps = ((self.max - self.min) / self.step).round().max(0.0) as u32;
+        // rounded to the step's precision so the config gets 0.79, not 0.78999996
+        (0..=steps).map(move |i| ((self.min + self.step * i as f32) * 1000.0).round() / 1000.0)
+    }
+}
+
+/// The best thresholds found and how far off they still read the image.
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    pub card_threshold: f32,
+    pub suit_threshold: f32,
+    /// Cards read wrong, missing or extra at these thresholds.
+    pub errors: usize,
+    /// Threshold pairs tried.
+    pub tried: usize,
+}
+
+/// Sweeps the card and suit thresholds over `image`, whose true state is
+/// `truth`, and returns the pair reading it best. Ties go to the pair
+/// closest to the configured thresholds. Templates are matched once at the
+/// lowest threshold, so each pair only costs suppression and assembly.
+#[tracing::instrument(skip_all, fields(image))]
+pub fn run(image: &str, truth: &GameState, config: &Config, sweep: &Sweep) -> Result<Calibration> {
+    let frame = Rectified::new(FileSource::new(image), config.perspective.clone()).next_frame()?;
+    let templates = vision::cache::load(&config.template_dir)?;
+    let assembler = pipeline::layout_assembler(config.layout.clone());
+
+    let detection = config.detection_config();
+    let lowest = DetectionConfig {
+        card_threshold: sweep.min,
+        suit_threshold: sweep.min,
+        ..detection.clone()
+    };
+    let gray = detect::to_gray(&frame)?;
+    let matches = detect::match_templates(&gray, &templates, &lowest, &|_| {})?;
+
+    let current = (config.detection.card_threshold, config.detection.suit_threshold);
+    let distance = |(card, suit): (f32, f32)| (card - current.0).abs() + (suit - current.1).abs();
+    let mut best: Option<Calibration> = None;
+    let mut tried = 0;
+    for card_threshold in sweep.values() {
+        for suit_threshold in sweep.values() {
+            let candidate = DetectionConfig {
+                card_threshold,
+                suit_threshold,
+                ..detection.clone()
+            };
+            let mut detections = matches.above(&candidate).into_detections(&frame, &candidate)?;
+            if candidate.verification.enabled {
+                detect::verify_ranks(&gray, &templates, &mut detections.cards, &candidate)?;
+            }
+            if !candidate.confusion.pairs.is_empty() {
+                solitaire_vision::confusion::disambiguate(&frame, &gray, &templates, &mut detections, &candidate)?;
+            }
+            let errors = errors(truth, &assembler.assemble(&detections, &frame));
+            debug!(card_threshold, suit_threshold, errors, "tried thresholds");
+            tried += 1;
+
+            let better = best.as_ref().is_none_or(|best| {
+                errors < best.errors
+                    || (errors == best.errors
+                        && distance((card_threshold, suit_threshold))
+                            < distance((best.card_threshold, best.suit_threshold)))
+            });
+            if better {
+                best = Some(Calibration {
+                    card_threshold,
+                    suit_threshold,
+                    errors,
+                    tried: 0,
+                });
+            }
+        }
+    }
+
+    let mut best = best.expect("a sweep always tries at least one pair");
+    best.tried = tried;
+    info!(best.card_threshold, best.suit_threshold, best.errors, tried, "calibrated thresholds");
+    Ok(best)
+}
+
+// cards in `actual` that aren't where `expected` has them, counting missing and extra ones
+fn errors(expected: &GameState, actual: &GameState) -> usize {
+    fn pile(expected: &[String], actual: &[String]) -> usize {
+        let wrong = expected.iter().zip(actual).filter(|(e, a)| e != a).count();
+        wrong + expected.len().abs_diff(actual.len())
+    }
+
+    let empty = Vec::new();
+    let columns = expected.game_piles.len().max(actual.game_piles.len());
+    let game_piles: usize = (0..columns)
+        .map(|i| {
+            pile(
+                expected.game_piles.get(i).unwrap_or(&empty),
+                actual.game_piles.get(i).unwrap_or(&empty),
+            )
+        })
+        .sum();
+    pile(&expected.draw_pile, &actual.draw_pile) + game_piles + pile(&expected.discard_pile, &actual.discard_pile)
+}
+
+/// Writes the calibrated thresholds into the config file at `path`, under
+/// `profile`'s section if given, leaving the rest of the file and its
+/// comments as they are. The file is created if it doesn't exist.
+pub fn write(path: &Path, profile: Option<&str>, calibration: &Calibration) -> Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(source) => {
+            return Err(SolitaireOcrError::ConfigRead {
+                path: path.into(),
+                source,
+            })
+        }
+    };
+    let mut document: toml_edit::DocumentMut = contents.parse().map_err(|source| SolitaireOcrError::ConfigEdit {
+        path: path.into(),
+        source: Box::new(source),
+    })?;
+
+    let mut table = document.as_table_mut();
+    if let Some(profile) = profile {
+        table = implicit_table(table, "profiles");
+        table = implicit_table(table, profile);
+    }
+    let detection = implicit_table(table, "detection");
+    // via a string so 0.79 is written as 0.79 rather than its nearest f64
+    let value = |threshold: f32| toml_edit::value(format!("{threshold}").parse::<f64>().expect("a float prints as one"));
+    detection["card_threshold"] = value(calibration.card_threshold);
+    detection["suit_threshold"] = value(calibration.suit_threshold);
+
+    fs::write(path, document.to_string()).map_err(|source| SolitaireOcrError::Io {
+        path: path.into(),
+        source,
+    })
+}
+
+// the table at `key`, added without a header of its own if it's missing
+fn implicit_table<'a>(table: &'a mut toml_edit::Table, key: &str) -> &'a mut toml_edit::Table {
+    let item = table.entry(key).or_insert_with(|| {
+        let mut new = toml_edit::Table::new();
+        new.set_implicit(true);
+        toml_edit::Item::Table(new)
+    });
+    item.as_table_mut().expect("config sections are tables")
+}
@@ -0,0 +1,278 @@
+use clap::{ArgAction, ArgGroup, Args, Parser, Subcommand};
+use solitaire_ocr::capture::{Backend, Browser, Difficulty, Viewport};
+use solitaire_ocr::daemon::GameOverAction;
+use std::{path::PathBuf, time::Duration};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "solitaire-ocr",
+    version,
+    about = "Read the board of Google Solitaire from a screenshot",
+    after_help = "Failures print a JSON object with stage, message and recoverable to stderr and exit with\n\
+                  3 (config), 4 (capture), 5 (detection), 6 (invalid state), 7 (io) or 130 (interrupted).\n\n\
+                  Any config value can also be set with a SOLITAIRE_OCR_<SECTION>__<KEY> environment\n\
+                  variable, e.g. SOLITAIRE_OCR_DETECTION__CARD_THRESHOLD, or SOLITAIRE_OCR_<KEY> for keys\n\
+                  in only one section, e.g. SOLITAIRE_OCR_TEMPLATE_DIR; see solitaire-ocr.toml."
+)]
+pub struct Cli {
+    /// Config file to load [default: solitaire-ocr.toml if present]
+    #[arg(long, global = true, env = "SOLITAIRE_OCR_CONFIG")]
+    pub config: Option<String>,
+
+    /// Named profile from the config to apply (e.g. google-easy-1080p)
+    #[arg(long, global = true, env = "SOLITAIRE_OCR_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Browser to capture from, chrome (via chromedriver) or firefox (via geckodriver)
+    /// [default: capture.browser from the config, or chrome]
+    #[arg(long, global = true)]
+    pub browser: Option<Browser>,
+
+    /// How to drive the browser, webdriver or cdp (Chrome only, needs the cdp feature)
+    /// [default: capture.backend from the config, or webdriver]
+    #[arg(long, global = true)]
+    pub capture_backend: Option<Backend>,
+
+    /// Game to start, easy (draw 1) or hard (draw 3); also sets layout.draw_count
+    /// [default: capture.difficulty from the config, or easy]
+    #[arg(long, global = true)]
+    pub difficulty: Option<Difficulty>,
+
+    /// Port to start the WebDriver server on [default: a free port]
+    #[arg(long, global = true)]
+    pub webdriver_port: Option<u16>,
+
+    /// Start games on this running WebDriver server, e.g. a Selenium Grid hub,
+    /// instead of launching a local driver
+    #[arg(long, global = true, conflicts_with = "webdriver_port")]
+    pub webdriver_url: Option<String>,
+
+    /// Capture from the browser behind this running WebDriver server as it is,
+    /// instead of launching one and starting a new game
+    #[arg(long, global = true, value_name = "WEBDRIVER_URL")]
+    pub attach: Option<String>,
+
+    /// Run the browser without a visible window
+    #[arg(long, global = true)]
+    pub headless: bool,
+
+    /// Size of the page area in the browser, e.g. 1920x1080
+    #[arg(long, global = true)]
+    pub viewport: Option<Viewport>,
+
+    /// Read live games from this many screenshots, voting on every card
+    /// [default: consensus.frames from the config, or 1]
+    #[arg(long, global = true, value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(1..))]
+    pub consensus: Option<u32>,
+
+    /// Detector backend to use [default: detector from the config, or template]
+    #[arg(long, global = true)]
+    pub detector: Option<String>,
+
+    /// Log more detail (-v for debug, -vv for trace)
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Only log warnings and errors
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Fail if the detected state is impossible (unknown or duplicate cards)
+    #[arg(long, global = true)]
+    pub strict: bool,
+
+    /// Check the WebDriver server, templates, inputs and output paths and print what
+    /// would be done, without launching a browser or writing anything
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start a new game in the browser and save a screenshot of it
+    Capture {
+        /// Where to write the screenshot
+        #[arg(long, default_value = "screenshot.png")]
+        screenshot: String,
+    },
+    /// Detect the game state in an existing screenshot, or every PNG in a directory
+    #[command(group(ArgGroup::new("input").required(true).args(["image", "dir", "watch"])))]
+    #[command(group(ArgGroup::new("batch").args(["dir", "watch"])))]
+    Translate {
+        /// Screenshot to read
+        image: Option<String>,
+
+        /// Translate every PNG in this directory instead of a single image
+        #[arg(long)]
+        dir: Option<PathBuf>,
+
+        /// Keep watching this directory and translate PNGs as they appear
+        #[arg(long)]
+        watch: Option<PathBuf>,
+
+        /// Where batch results are written [default: the input directory]
+        #[arg(long, requires = "batch")]
+        out: Option<PathBuf>,
+
+        /// Skip images the output directory's journal records as already translated
+        #[arg(long, requires = "dir")]
+        resume: bool,
+
+        /// The input is a photo of a physical game: straighten its board first
+        #[arg(long)]
+        photo: bool,
+
+        /// Also write every template's match scores on the image as a colorized
+        /// heatmap PNG into this directory [default: heatmaps]
+        #[arg(long, value_name = "DIR", num_args = 0..=1, default_missing_value = "heatmaps", requires = "image")]
+        debug_heatmaps: Option<PathBuf>,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Capture a new game and detect its state
+    Run {
+        /// Also write each screenshot here, e.g. to look into a bad reading;
+        /// detection reads it from memory either way
+        #[arg(long)]
+        screenshot: Option<String>,
+
+        /// Keep the game open and re-capture it at this interval (e.g. 2s, 500ms)
+        #[arg(long, value_parser = humantime::parse_duration)]
+        watch_interval: Option<Duration>,
+
+        /// In watch mode, also append every state as a JSON line to this file
+        #[arg(long, requires = "watch_interval")]
+        state_log: Option<String>,
+
+        /// In watch mode, what to do when the game ends: stop, dismiss or new-game
+        #[arg(long, default_value_t = GameOverAction::Stop, requires = "watch_interval")]
+        on_game_over: GameOverAction,
+
+        /// In watch mode, append every finished game's outcome as a JSON line to this file
+        #[arg(long, requires = "watch_interval")]
+        outcome_log: Option<String>,
+
+        /// In watch mode, record every capture into a video (.mp4, .webm or .avi)
+        #[arg(long, requires = "watch_interval")]
+        record: Option<String>,
+
+        /// In watch mode, capture the next frame while the last one is still
+        /// being detected; each state is reported a cycle later
+        #[arg(long, requires = "watch_interval")]
+        pipeline: bool,
+
+        /// Capture this many games at once in separate browsers; each writes
+        /// its files with -<session> added to the name
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..), conflicts_with = "watch_interval")]
+        sessions: u16,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Interactively capture, detect and tune thresholds against a live game
+    Repl,
+    /// Check that OpenCV and the template set detect a bundled screenshot correctly
+    Selftest,
+    /// Find the card and suit thresholds that read a screenshot with a known state best
+    Calibrate {
+        /// Screenshot to calibrate on
+        image: String,
+
+        /// Its true game state as JSON [default: the image's path with .json]
+        #[arg(long)]
+        truth: Option<String>,
+
+        /// Lowest threshold tried
+        #[arg(long, default_value_t = 0.6)]
+        min: f32,
+
+        /// Highest threshold tried
+        #[arg(long, default_value_t = 0.95)]
+        max: f32,
+
+        /// Step between thresholds tried
+        #[arg(long, default_value_t = 0.01)]
+        step: f32,
+
+        /// Save the best thresholds to the config file, in the selected profile if any
+        #[arg(long)]
+        write: bool,
+    },
+    /// Inspect template sets
+    Templates {
+        #[command(subcommand)]
+        command: TemplatesCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TemplatesCommand {
+    /// Verify that every rank and suit template exists, loads and is consistently sized
+    Check {
+        /// Template directory [default: template_dir from the config]
+        dir: Option<String>,
+    },
+    /// Cut rank and suit templates out of screenshots showing every card, laid
+    /// out in rows of A to K, one row per suit
+    Generate {
+        /// Screenshots, whose rows of cards are read top to bottom, one screenshot after another
+        #[arg(required = true)]
+        screenshots: Vec<String>,
+
+        /// Where to save the templates [default: template_dir from the config]
+        #[arg(long)]
+        out: Option<String>,
+
+        /// Suit of each row of cards, in order
+        #[arg(long, value_delimiter = ',', default_value = "hearts,diamonds,clubs,spades")]
+        suits: Vec<String>,
+
+        /// Part of a card's width and height, from its top left corner, holding its rank and suit
+        #[arg(long, default_value_t = 0.3)]
+        corner: f32,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct OutputArgs {
+    /// Where to write the screenshot annotated with detected boxes
+    #[arg(long, default_value = "output_with_boxes.png")]
+    pub annotated: String,
+
+    /// Don't write the annotated screenshot
+    #[arg(long, conflicts_with = "annotated")]
+    pub no_annotated: bool,
+
+    /// Where to write the game state JSON
+    #[arg(long, default_value = "output.json")]
+    pub state: String,
+
+    /// Print each game state as a line of JSON on stdout; single images skip writing --state
+    #[arg(long, conflicts_with = "state")]
+    pub stdout: bool,
+}
+
+impl OutputArgs {
+    pub fn state_path(&self) -> Option<&str> {
+        (!self.stdout).then_some(self.state.as_str())
+    }
+
+    pub fn annotated_path(&self) -> Option<&str> {
+        (!self.no_annotated).then_some(self.annotated.as_str())
+    }
+}
+
+impl Cli {
+    pub fn log_level(&self) -> tracing::Level {
+        match (self.quiet, self.verbose) {
+            (true, _) => tracing::Level::WARN,
+            (false, 0) => tracing::Level::INFO,
+            (false, 1) => tracing::Level::DEBUG,
+            (false, _) => tracing::Level::TRACE,
+        }
+    }
+}
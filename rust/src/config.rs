@@ -0,0 +1,373 @@
+use crate::consensus::ConsensusConfig;
+use crate::error::{Result, SolitaireOcrError};
+use crate::page_state::StateSource;
+use opencv::core::Mat;
+use serde::{Deserialize, Serialize};
+use solitaire_capture::CaptureConfig;
+use solitaire_vision::pipeline::TemplateObserver;
+use solitaire_vision::theme;
+use solitaire_vision::{DetectionConfig, DetectorRegistry, DetectorSettings, DynDetector, LayoutConfig, PerspectiveConfig};
+use std::{collections::BTreeMap, env, fs, path::Path};
+use tracing::{info, warn};
+
+/// Prefix of the environment variables layered over the config file. The
+/// rest of the name is the key's path with sections split by `__`, e.g.
+/// `SOLITAIRE_OCR_DETECTION__CARD_THRESHOLD`; a key set in only one section
+/// may leave the section out, as in `SOLITAIRE_OCR_HEADLESS`.
+pub const ENV_PREFIX: &str = "SOLITAIRE_OCR_";
+// read by the CLI itself rather than layered over the config
+const CLI_ENV_VARS: [&str; 2] = ["SOLITAIRE_OCR_CONFIG", "SOLITAIRE_OCR_PROFILE"];
+
+/// Tunable detection and layout parameters, loaded from `solitaire-ocr.toml`.
+///
+/// Every field has a default, so a config file only needs the values it changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Directory containing one PNG template per rank and suit.
+    pub template_dir: String,
+    /// Detector backend, by its name in the [`DetectorRegistry`].
+    pub detector: String,
+    /// Whether live captures report the detected state, the page's own, or
+    /// the detected one checked against the page's.
+    pub state_source: StateSource,
+    pub detection: DetectionConfig,
+    pub layout: LayoutConfig,
+    /// Straightening photos of a physical game before they're read.
+    pub perspective: PerspectiveConfig,
+    pub capture: CaptureConfig,
+    /// Reading live games from several screenshots at once.
+    pub consensus: ConsensusConfig,
+    /// Profile applied when none is chosen on the command line.
+    pub profile: Option<String>,
+    /// Named partial configs, e.g. `[profiles.google-hard-4k]`, layered over
+    /// the top-level values when selected.
+    pub profiles: BTreeMap<String, toml::Table>,
+    /// The game's visual themes, e.g. `[themes.dark]`, told apart on screen
+    /// by their background. Empty to always use the values above.
+    pub themes: BTreeMap<String, Theme>,
+    /// How far, as a distance between RGB colors, a frame's background can
+    /// be from a theme's and still count as it.
+    pub theme_tolerance: f64,
+}
+
+/// A visual theme of the game and the settings that read it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Theme {
+    /// Background color of the board as `[r, g, b]`.
+    pub background: [u8; 3],
+    /// Profile applied to frames showing this theme, e.g. one pointing
+    /// `template_dir` at templates cut from its deck.
+    pub profile: String,
+}
+
+impl Config {
+    pub const FILE_NAME: &'static str = "solitaire-ocr.toml";
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| SolitaireOcrError::ConfigRead {
+            path: path.into(),
+            source,
+        })?;
+        toml::from_str(&contents).map_err(|source| SolitaireOcrError::ConfigParse {
+            path: path.into(),
+            source: Box::new(source),
+        })
+    }
+
+    /// Like [`Config::load`], but falls back to the defaults when `path` doesn't exist.
+    pub fn load_or_default(path: impl AsRef<Path>) -> Result<Self> {
+        if path.as_ref().exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+}
+
+impl Config {
+    /// The detection settings, limited to the layout's regions if it asks for that.
+    pub fn detection_config(&self) -> DetectionConfig {
+        DetectionConfig {
+            search_regions: self.layout.search_regions(),
+            ignored_regions: self.layout.ignore_regions.clone(),
+            ..self.detection.clone()
+        }
+    }
+
+    /// Builds the configured detector backend.
+    pub fn detector(&self) -> Result<DynDetector> {
+        self.detector_observed(None)
+    }
+
+    /// Like [`Config::detector`], calling `observer` after each template a
+    /// template-matching backend tries.
+    pub fn detector_observed(&self, observer: Option<TemplateObserver>) -> Result<DynDetector> {
+        let settings = DetectorSettings {
+            template_dir: self.template_dir.clone(),
+            detection: self.detection_config(),
+            observer,
+        };
+        Ok(DetectorRegistry::default().create(&self.detector, &settings)?)
+    }
+}
+
+impl Config {
+    /// Returns this config with the named profile's values layered on top.
+    pub fn with_profile(&self, name: &str) -> Result<Self> {
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| SolitaireOcrError::UnknownProfile(name.to_string()))?;
+
+        let mut base = Self {
+            profile: None,
+            profiles: BTreeMap::new(),
+            ..self.clone()
+        };
+        let mut merged = toml::Table::try_from(&base).expect("config always serializes to a table");
+        merge_tables(&mut merged, profile);
+
+        base = merged.try_into().map_err(|source| SolitaireOcrError::InvalidProfile {
+            name: name.to_string(),
+            source: Box::new(source),
+        })?;
+        base.profile = Some(name.to_string());
+        base.profiles = self.profiles.clone();
+        Ok(base)
+    }
+
+    /// Applies `name`, or the config's own `profile` if no name is given.
+    pub fn select_profile(&self, name: Option<&str>) -> Result<Self> {
+        match name.or(self.profile.as_deref()) {
+            Some(name) => self.with_profile(name),
+            None => Ok(self.clone()),
+        }
+    }
+
+    /// The theme showing in the BGR `frame`, if it matches one of `themes`.
+    pub fn identify_theme(&self, frame: &Mat) -> Result<Option<&str>> {
+        let themes = self.themes.iter().map(|(name, theme)| (name.as_str(), theme.background));
+        Ok(theme::identify(frame, themes, self.theme_tolerance)?)
+    }
+
+    /// Returns this config with the profile of the theme showing in `frame`
+    /// applied, or unchanged if no theme matches.
+    pub fn for_frame(&self, frame: &Mat) -> Result<Self> {
+        let Some(name) = self.identify_theme(frame)? else {
+            return Ok(self.clone());
+        };
+        info!(theme = name, "identified theme");
+        self.with_profile(&self.themes[name].profile)
+    }
+}
+
+impl Config {
+    /// Returns this config with any `SOLITAIRE_OCR_*` variables, named as
+    /// described at [`ENV_PREFIX`], layered on top. Applied after the
+    /// profile, so the environment wins over both.
+    pub fn with_env(&self) -> Result<Self> {
+        let mut merged = toml::Table::try_from(self).expect("config always serializes to a table");
+
+        let mut vars: Vec<(String, String)> = env::vars()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX) && !CLI_ENV_VARS.contains(&name.as_str()))
+            .collect();
+        vars.sort();
+        for (name, value) in vars {
+            let Some(key) = env_key(&merged, &name[ENV_PREFIX.len()..]) else {
+                warn!(name, "doesn't name exactly one config value, ignoring it");
+                continue;
+            };
+            let value = env_value(value, lookup(&merged, &key));
+            let (field, parents) = key.split_last().expect("env var keys are never empty");
+            let mut overlay = toml::Table::from_iter([(field.to_string(), value)]);
+            for parent in parents.iter().rev() {
+                overlay = toml::Table::from_iter([(parent.to_string(), toml::Value::Table(overlay))]);
+            }
+            merge_tables(&mut merged, &overlay);
+
+            // deserialize after each variable so an error names the one at fault
+            merged.clone().try_into::<Self>().map_err(|source| SolitaireOcrError::InvalidEnv {
+                name,
+                source: Box::new(source),
+            })?;
+        }
+
+        Ok(merged.try_into().expect("validated after each variable"))
+    }
+}
+
+// the path of the key `name` (an env var name without its prefix) stands
+// for in `config`: split on `__`, or the shallowest key of that name if
+// there's only one of them
+fn env_key(config: &toml::Table, name: &str) -> Option<Vec<String>> {
+    let name = name.to_ascii_lowercase();
+    if name.contains("__") {
+        let key: Vec<String> = name.split("__").map(str::to_string).collect();
+        // the key itself may be an option that's unset, and so missing
+        let (_, parents) = key.split_last()?;
+        return lookup_parent(config, parents).map(|_| key);
+    }
+    let mut found = Vec::new();
+    find_keys(config, &name, &mut Vec::new(), &mut found);
+    let shallowest = found.iter().map(Vec::len).min()?;
+    match found.iter().filter(|key| key.len() == shallowest).collect::<Vec<_>>().as_slice() {
+        [key] => Some((*key).clone()),
+        _ => None,
+    }
+}
+
+fn find_keys(table: &toml::Table, name: &str, path: &mut Vec<String>, found: &mut Vec<Vec<String>>) {
+    for (key, value) in table {
+        path.push(key.clone());
+        if key == name {
+            found.push(path.clone());
+        }
+        if let toml::Value::Table(inner) = value {
+            find_keys(inner, name, path, found);
+        }
+        path.pop();
+    }
+}
+
+fn lookup_parent<'a>(config: &'a toml::Table, parents: &[String]) -> Option<&'a toml::Table> {
+    parents.iter().try_fold(config, |table, key| table.get(key)?.as_table())
+}
+
+fn lookup<'a>(config: &'a toml::Table, key: &[String]) -> Option<&'a toml::Value> {
+    let (field, parents) = key.split_last()?;
+    lookup_parent(config, parents)?.get(field)
+}
+
+// `value` read as the type of `current`, the key's value so far. Unset
+// options, arrays and tables take TOML syntax, e.g. `[0.8, 1.0]`, and
+// anything that doesn't parse is passed on as a string for deserialization
+// to report.
+fn env_value(value: String, current: Option<&toml::Value>) -> toml::Value {
+    match current {
+        Some(toml::Value::String(_)) => toml::Value::String(value),
+        Some(toml::Value::Boolean(_)) => match value.to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => toml::Value::Boolean(true),
+            "false" | "0" | "no" | "off" => toml::Value::Boolean(false),
+            _ => toml::Value::String(value),
+        },
+        Some(toml::Value::Integer(_)) => value.parse().map_or(toml::Value::String(value), toml::Value::Integer),
+        Some(toml::Value::Float(_)) => value.parse().map_or(toml::Value::String(value), toml::Value::Float),
+        _ => format!("value = {value}")
+            .parse::<toml::Table>()
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or(toml::Value::String(value)),
+    }
+}
+
+// recursively overwrite `base` with `overlay`, keeping keys the overlay doesn't mention
+fn merge_tables(base: &mut toml::Table, overlay: &toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table);
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            template_dir: "templates".to_string(),
+            detector: DetectorRegistry::DEFAULT.to_string(),
+            state_source: StateSource::default(),
+            detection: DetectionConfig::default(),
+            layout: LayoutConfig::default(),
+            perspective: PerspectiveConfig::default(),
+            capture: CaptureConfig::default(),
+            consensus: ConsensusConfig::default(),
+            profile: None,
+            profiles: BTreeMap::new(),
+            themes: BTreeMap::new(),
+            theme_tolerance: 40.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> toml::Table {
+        toml::Table::try_from(Config::default()).unwrap()
+    }
+
+    fn key(path: &[&str]) -> Option<Vec<String>> {
+        Some(path.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn env_key_follows_sections() {
+        let config = config();
+        assert_eq!(env_key(&config, "DETECTION__CARD_THRESHOLD"), key(&["detection", "card_threshold"]));
+        assert_eq!(env_key(&config, "CAPTURE__SETTLE__TIMEOUT_MS"), key(&["capture", "settle", "timeout_ms"]));
+        // unset options are missing from the table, but their section isn't
+        assert_eq!(env_key(&config, "CAPTURE__WEBDRIVER_PORT"), key(&["capture", "webdriver_port"]));
+        assert_eq!(env_key(&config, "NO_SUCH_SECTION__KEY"), None);
+    }
+
+    #[test]
+    fn env_key_finds_keys_of_one_section() {
+        let config = config();
+        assert_eq!(env_key(&config, "TEMPLATE_DIR"), key(&["template_dir"]));
+        assert_eq!(env_key(&config, "HEADLESS"), key(&["capture", "headless"]));
+        // in many sections
+        assert_eq!(env_key(&config, "ENABLED"), None);
+        assert_eq!(env_key(&config, "NO_SUCH_KEY"), None);
+    }
+
+    #[test]
+    fn env_value_takes_the_type_of_the_key() {
+        let boolean = toml::Value::Boolean(false);
+        assert_eq!(env_value("1".into(), Some(&boolean)), toml::Value::Boolean(true));
+        assert_eq!(env_value("no".into(), Some(&boolean)), toml::Value::Boolean(false));
+        assert_eq!(env_value("9".into(), Some(&toml::Value::String(String::new()))), toml::Value::String("9".into()));
+        assert_eq!(env_value("1".into(), Some(&toml::Value::Float(0.5))), toml::Value::Float(1.0));
+        assert_eq!(env_value("4444".into(), None), toml::Value::Integer(4444));
+        assert_eq!(
+            env_value("[0.9, 1.0]".into(), Some(&toml::Value::Array(Vec::new()))),
+            toml::Value::Array(vec![toml::Value::Float(0.9), toml::Value::Float(1.0)])
+        );
+        assert_eq!(env_value("http://host:4444".into(), None), toml::Value::String("http://host:4444".into()));
+    }
+
+    #[test]
+    fn env_wins_over_profile_and_file() {
+        let file: Config = toml::from_str(
+            r#"
+            template_dir = "file"
+
+            [detection]
+            card_threshold = 0.5
+
+            [profiles.fast.detection]
+            card_threshold = 0.6
+            suit_threshold = 0.6
+            "#,
+        )
+        .unwrap();
+        // the only test reading the environment, so no other sees these
+        env::set_var("SOLITAIRE_OCR_DETECTION__CARD_THRESHOLD", "0.7");
+        env::set_var("SOLITAIRE_OCR_HEADLESS", "yes");
+        let config = file.with_profile("fast").and_then(|config| config.with_env());
+        env::remove_var("SOLITAIRE_OCR_DETECTION__CARD_THRESHOLD");
+        env::remove_var("SOLITAIRE_OCR_HEADLESS");
+
+        let config = config.unwrap();
+        assert_eq!(config.template_dir, "file");
+        assert_eq!(config.detection.suit_threshold, 0.6);
+        assert_eq!(config.detection.card_threshold, 0.7);
+        assert!(config.capture.headless);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::capture;
+use crate::config::Config;
+use crate::error::Result;
+use crate::live::{CapturedFrame, LiveSession};
+use crate::pipeline::Detector;
+use crate::state::{Confidence, GameState};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Reading a live game from several screenshots instead of one, so a frame
+/// caught mid-animation or blurred by compression is outvoted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsensusConfig {
+    /// Screenshots per reading; 1 reads each screenshot on its own.
+    pub frames: u32,
+    /// Time between them in milliseconds.
+    pub interval_ms: u64,
+}
+
+impl Default for ConsensusConfig {
+    fn default() -> Self {
+        Self {
+            frames: 1,
+            interval_ms: 300,
+        }
+    }
+}
+
+impl ConsensusConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.frames > 1
+    }
+}
+
+/// Captures `config.consensus.frames` screenshots of `session`'s game and
+/// detects each, writing the last one annotated to `annotated_path` if
+/// given. Returns the last screenshot and the states merged with [`merge`],
+/// or the first detection error if no frame could be read.
+pub async fn detect(
+    session: &mut LiveSession,
+    detector: &impl Detector,
+    annotated_path: Option<&str>,
+    config: &Config,
+) -> Result<(CapturedFrame, Result<GameState>)> {
+    let frames = config.consensus.frames.max(1);
+    let mut states = Vec::new();
+    let mut first_error = None;
+    let mut last = None;
+    for i in 0..frames {
+        if i > 0 {
+            tokio::time::sleep(Duration::from_millis(config.consensus.interval_ms)).await;
+        }
+        let captured = session.capture_frame().await?;
+        let annotated_path = annotated_path.filter(|_| i + 1 == frames);
+        match crate::translate_source(detector, captured.source(), annotated_path, None, config) {
+            Ok(state) => states.push(state),
+            Err(e) => {
+                warn!(frame = captured.index, error = %e, "consensus frame failed detection");
+                first_error.get_or_insert(e);
+            }
+        }
+        last = Some(captured);
+    }
+
+    let last = last.expect("at least one frame is captured");
+    let merged = match (merge(&states), first_error) {
+        (Some(state), _) => Ok(state),
+        (None, Some(e)) => Err(e),
+        (None, None) => unreachable!("every frame either detects or fails"),
+    };
+    debug!(frames, detected = states.len(), "merged consensus");
+    Ok((last, merged))
+}
+
+/// Starts a session, reads its new game with [`detect`], writes the last
+/// screenshot to `path` if given and closes it again. Returns the state the page
+/// reported, as [`live::capture_screenshot`](crate::live::capture_screenshot)
+/// does, and the merged detection.
+pub async fn capture(config: &Config, path: Option<&str>, annotated_path: Option<&str>) -> Result<(Option<GameState>, Result<GameState>)> {
+    let detector = config.detector()?;
+    let mut session = LiveSession::start(&config.capture).await?;
+    let (captured, detected) = detect(&mut session, &detector, annotated_path, config).await?;
+    if let Some(path) = path {
+        capture::write_screenshot(&captured.png, path)?;
+    }
+    let page_state = session.page_state().await?;
+    session.close().await?;
+    if config.consensus.is_enabled() {
+        info!(frames = config.consensus.frames, "read game by consensus");
+    }
+    Ok((page_state, detected))
+}
+
+/// Merges `states` detected from the same board by majority vote: how many
+/// piles there are, how long each is, and which card is at each position,
+/// with ties going to the later state. Each position's confidence is the
+/// best of the states that agree with the vote there, and every other field
+/// is voted on as a whole. `None` if there are no states.
+pub fn merge(states: &[GameState]) -> Option<GameState> {
+    let latest = states.last()?;
+    let (draw_pile, draw_confidence) = merge_pile(
+        &states
+            .iter()
+            .map(|s| (&s.draw_pile, s.confidence.as_ref().map(|c| &c.draw_pile)))
+            .collect::<Vec<_>>(),
+    );
+    let (discard_pile, discard_confidence) = merge_pile(
+        &states
+            .iter()
+            .map(|s| (&s.discard_pile, s.confidence.as_ref().map(|c| &c.discard_pile)))
+            .collect::<Vec<_>>(),
+    );
+    let columns = vote(states, |s| s.game_piles.len()).unwrap_or(0);
+    let (game_piles, game_confidence): (Vec<_>, Vec<_>) = (0..columns)
+        .map(|i| {
+            merge_pile(
+                &states
+                    .iter()
+                    .filter_map(|s| {
+                        let confidence = s.confidence.as_ref().and_then(|c| c.game_piles.get(i));
+                        s.game_piles.get(i).map(|labels| (labels, confidence))
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .unzip();
+
+    Some(GameState {
+        draw_pile,
+        game_piles,
+        discard_pile,
+        confidence: latest.confidence.as_ref().map(|_| Confidence {
+            draw_pile: draw_confidence,
+            game_piles: game_confidence,
+            discard_pile: discard_confidence,
+        }),
+        stock_remaining: vote(states, |s| s.stock_remaining).flatten(),
+        waste: vote(states, |s| s.waste.clone()).flatten(),
+        stats: vote(states, |s| s.stats.clone()).flatten(),
+        outcome: vote(states, |s| s.outcome).flatten(),
+        hint: vote(states, |s| s.hint.clone()).flatten(),
+        selected: vote(states, |s| s.selected.clone()).flatten(),
+        rank_only: vote(states, |s| s.rank_only.clone()).flatten(),
+    })
+}
+
+// `field` of `states` as most of them have it
+fn vote<T: PartialEq + Clone>(states: &[GameState], field: impl Fn(&GameState) -> T) -> Option<T> {
+    majority(&states.iter().map(field).collect::<Vec<_>>())
+}
+
+// a pile's labels and their confidences, if the state has any
+type Pile<'a> = (&'a Vec<String>, Option<&'a Vec<Option<f32>>>);
+
+// one pile voted on position by position, with each position's confidence
+fn merge_pile(piles: &[Pile]) -> (Vec<String>, Vec<Option<f32>>) {
+    let len = majority(&piles.iter().map(|(labels, _)| labels.len()).collect::<Vec<_>>()).unwrap_or(0);
+    (0..len)
+        .map(|i| {
+            let label = majority(&piles.iter().filter_map(|(labels, _)| labels.get(i)).collect::<Vec<_>>())
+                .expect("as many piles as voted for the length are at least this long")
+                .clone();
+            let confidence = piles
+                .iter()
+                .filter(|(labels, _)| labels.get(i) == Some(&label))
+                .filter_map(|(_, confidence)| confidence.and_then(|c| c.get(i).copied().flatten()))
+                .max_by(f32::total_cmp);
+            (label, confidence)
+        })
+        .unzip()
+}
+
+// the value most of `values` agree on, the later one on a tie
+fn majority<T: PartialEq + Clone>(values: &[T]) -> Option<T> {
+    values
+        .iter()
+        .max_by_key(|value| values.iter().filter(|other| other == value).count())
+        .cloned()
+}
@@ -0,0 +1,299 @@
+use crate::capture::{self, GameOutcome};
+use crate::config::Config;
+use crate::consensus;
+use crate::error::{Result, SolitaireOcrError};
+use crate::live::{CapturedFrame, LiveSession};
+use crate::page_state::{self, StateSource};
+use crate::state::{self, GameState};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use solitaire_vision::diff::Differ;
+use solitaire_vision::DynDetector;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fmt, str::FromStr};
+use tokio::sync::mpsc;
+use tokio::task;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{info, warn};
+
+/// What watch mode does once the game shows its end-of-game dialog.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GameOverAction {
+    /// Stop watching.
+    #[default]
+    Stop,
+    /// Close the dialog and keep watching the finished board.
+    Dismiss,
+    /// Deal a new game and keep watching that.
+    NewGame,
+}
+
+impl FromStr for GameOverAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "stop" => Ok(GameOverAction::Stop),
+            "dismiss" => Ok(GameOverAction::Dismiss),
+            "new-game" => Ok(GameOverAction::NewGame),
+            _ => Err(format!("unknown action {s:?}, expected stop, dismiss or new-game")),
+        }
+    }
+}
+
+impl fmt::Display for GameOverAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GameOverAction::Stop => "stop",
+            GameOverAction::Dismiss => "dismiss",
+            GameOverAction::NewGame => "new-game",
+        })
+    }
+}
+
+/// A finished game, as written to the outcome log.
+#[derive(Debug, Serialize)]
+pub struct OutcomeRecord<'a> {
+    pub outcome: GameOutcome,
+    /// Number of frames captured before the game ended.
+    pub frames: u64,
+    /// The last state detected before the dialog appeared.
+    pub state: Option<&'a GameState>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DaemonOptions {
+    /// Time between the start of consecutive captures.
+    pub interval: Duration,
+    /// Overwritten with the latest screenshot each cycle.
+    pub screenshot_path: Option<String>,
+    /// Overwritten with the latest annotated screenshot each cycle.
+    pub annotated_path: Option<String>,
+    /// Overwritten with the latest game state each cycle.
+    pub state_path: Option<String>,
+    /// Print every detected state as a line of JSON on stdout.
+    pub stdout: bool,
+    /// Every detected state is appended here as a line of JSON.
+    pub state_log: Option<String>,
+    pub on_game_over: GameOverAction,
+    /// Every finished game is appended here as a line of JSON.
+    pub outcome_log: Option<String>,
+    /// Capture the next frame while the last one is still being detected,
+    /// instead of one after the other. Each state is then reported a cycle
+    /// later. Ignored with consensus, which detects as it captures.
+    pub pipeline: bool,
+}
+
+/// Re-captures and re-detects the game open in `session` every
+/// `options.interval` until capture fails, or until the game ends with
+/// [`GameOverAction::Stop`].
+///
+/// Detection failures are logged and retried on the next cycle, since a frame
+/// caught mid-animation is expected to fail now and then.
+pub async fn run(session: &mut LiveSession, config: &Config, options: &DaemonOptions) -> Result<()> {
+    let detector = Arc::new(config.detector()?);
+    let mut worker = (options.pipeline && !config.consensus.is_enabled())
+        .then(|| Worker::spawn(Arc::clone(&detector), config, options.annotated_path.clone()));
+
+    // successive frames of the one game, so only what changed is matched again
+    let mut differ = Differ::new(&config.detection_config());
+
+    let mut ticker = interval(options.interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_state = None;
+    let mut frames = 0;
+
+    loop {
+        ticker.tick().await;
+
+        if let Some(worker) = &mut worker {
+            for (job, detected) in worker.finished() {
+                last_state = report(&job.captured, detected, job.page_state, config, options)?.or(last_state);
+            }
+        }
+
+        if let Some(outcome) = session.browser().game_outcome().await? {
+            // the frames still being detected are the game's last
+            if let Some(worker) = &mut worker {
+                for (job, detected) in worker.drain().await {
+                    last_state = report(&job.captured, detected, job.page_state, config, options)?.or(last_state);
+                }
+            }
+            info!(%outcome, frames, "game over");
+            if let Some(log) = &options.outcome_log {
+                let record = OutcomeRecord {
+                    outcome,
+                    frames,
+                    state: last_state.as_ref(),
+                };
+                append_outcome(&record, log)?;
+            }
+            match options.on_game_over {
+                GameOverAction::Stop => return Ok(()),
+                GameOverAction::Dismiss => session.browser().dismiss_dialog().await?,
+                GameOverAction::NewGame => {
+                    session.browser().new_game().await?;
+                    differ.reset();
+                    last_state = None;
+                    frames = 0;
+                }
+            }
+            continue;
+        }
+        frames += 1;
+
+        if let Some(worker) = &mut worker {
+            let captured = session.capture_frame().await?;
+            let page_state = read_page_state(session, config).await?;
+            worker.send(Job { captured, page_state }).await;
+            continue;
+        }
+        let (captured, detected) = if config.consensus.is_enabled() {
+            consensus::detect(session, &*detector, options.annotated_path.as_deref(), config).await?
+        } else {
+            let captured = session.capture_frame().await?;
+            let detected = crate::translate_diffed(
+                &*detector,
+                &mut differ,
+                captured.source(),
+                options.annotated_path.as_deref(),
+                None,
+                config,
+            );
+            (captured, detected)
+        };
+        let page_state = read_page_state(session, config).await?;
+        last_state = report(&captured, detected, page_state, config, options)?.or(last_state);
+    }
+}
+
+async fn read_page_state(session: &LiveSession, config: &Config) -> Result<Option<GameState>> {
+    match config.state_source {
+        StateSource::Ocr => Ok(None),
+        _ => session.page_state().await,
+    }
+}
+
+// writes out the state detected in `captured`, returning it unless
+// detection failed
+fn report(
+    captured: &CapturedFrame,
+    detected: Result<GameState>,
+    page_state: Option<GameState>,
+    config: &Config,
+    options: &DaemonOptions,
+) -> Result<Option<GameState>> {
+    if let Some(path) = &options.screenshot_path {
+        capture::write_screenshot(&captured.png, path)?;
+    }
+    match page_state::reconcile(config.state_source, detected, page_state) {
+        Ok(game_state) => {
+            if let Some(state_path) = &options.state_path {
+                state::save_game_state(&game_state, state_path)?;
+            }
+            if let Some(log) = &options.state_log {
+                state::append_game_state(&game_state, log)?;
+            }
+            if options.stdout {
+                state::print_game_state(&game_state)?;
+            }
+            if let Some(state_path) = &options.state_path {
+                info!("Game state saved to {state_path}");
+            }
+            Ok(Some(game_state))
+        }
+        Err(e) => {
+            warn!(error = %e, "detection failed, retrying next cycle");
+            Ok(None)
+        }
+    }
+}
+
+/// A captured frame on its way to detection, with the state the page
+/// reported alongside it.
+struct Job {
+    captured: CapturedFrame,
+    page_state: Option<GameState>,
+}
+
+// detects frames on a thread of its own while the capture loop moves on to
+// the next ones, at most one frame waiting behind the one being detected
+struct Worker {
+    jobs: mpsc::Sender<Job>,
+    done: mpsc::UnboundedReceiver<(Job, Result<GameState>)>,
+    in_flight: usize,
+}
+
+impl Worker {
+    fn spawn(detector: Arc<DynDetector>, config: &Config, annotated_path: Option<String>) -> Self {
+        let (jobs, mut queue) = mpsc::channel::<Job>(1);
+        let (finished, done) = mpsc::unbounded_channel();
+        let config = config.clone();
+        // detection is CPU bound, keep it off the threads driving the browser
+        task::spawn_blocking(move || {
+            // the worker sees every frame in order, so differences them itself
+            let mut differ = Differ::new(&config.detection_config());
+            while let Some(job) = queue.blocking_recv() {
+                let detected = crate::translate_diffed(
+                    &*detector,
+                    &mut differ,
+                    job.captured.source(),
+                    annotated_path.as_deref(),
+                    None,
+                    &config,
+                );
+                if finished.send((job, detected)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            jobs,
+            done,
+            in_flight: 0,
+        }
+    }
+
+    // waits while the queue is full, i.e. capture is a frame ahead
+    async fn send(&mut self, job: Job) {
+        self.jobs.send(job).await.map_err(drop).expect("detection worker stopped");
+        self.in_flight += 1;
+    }
+
+    // the frames detected since the last call, in capture order
+    fn finished(&mut self) -> Vec<(Job, Result<GameState>)> {
+        let mut finished = Vec::new();
+        while let Ok(done) = self.done.try_recv() {
+            finished.push(done);
+        }
+        self.in_flight -= finished.len();
+        finished
+    }
+
+    // waits for every frame sent so far to be detected
+    async fn drain(&mut self) -> Vec<(Job, Result<GameState>)> {
+        let mut finished = Vec::with_capacity(self.in_flight);
+        while self.in_flight > 0 {
+            finished.push(self.done.recv().await.expect("detection worker stopped"));
+            self.in_flight -= 1;
+        }
+        finished
+    }
+}
+
+fn append_outcome(record: &OutcomeRecord, path: &str) -> Result<()> {
+    let mut line = serde_json::to_string(record).map_err(state::StateError::from)?;
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|source| SolitaireOcrError::Io {
+            path: path.into(),
+            source,
+        })
+}
@@ -0,0 +1,166 @@
+use crate::batch;
+use crate::capture::{self, Backend, Browser, CaptureConfig};
+use crate::journal::Journal;
+use crate::templates::{self, TemplateStatus};
+use solitaire_vision::DetectorRegistry;
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+
+/// One thing a dry run verified, and what was wrong with it if anything.
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub description: String,
+    pub problem: Option<String>,
+}
+
+/// What a command would do and whether its environment is ready for it,
+/// collected without starting a browser or writing any output.
+#[derive(Debug, Clone, Default)]
+pub struct DryRun {
+    pub plan: Vec<String>,
+    pub checks: Vec<Check>,
+}
+
+impl DryRun {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.problem.is_none())
+    }
+
+    pub fn step(&mut self, step: impl Into<String>) {
+        self.plan.push(step.into());
+    }
+
+    fn check(&mut self, description: impl Into<String>, problem: Option<String>) {
+        self.checks.push(Check {
+            description: description.into(),
+            problem,
+        });
+    }
+
+    /// Checks the driver a new session would launch. Nothing is launched when
+    /// attaching or using a remote server, and the CDP backend launches the
+    /// browser itself.
+    pub fn driver(&mut self, config: &CaptureConfig) {
+        let browser = config.browser;
+        if config.backend == Backend::Cdp {
+            let problem = match browser {
+                Browser::Chrome if cfg!(feature = "cdp") => None,
+                Browser::Chrome => Some("this build doesn't have the cdp feature".to_string()),
+                Browser::Firefox => Some("only Chrome can be driven over CDP".to_string()),
+            };
+            self.check("cdp capture backend", problem);
+            return;
+        }
+        if config.attach.is_some() || !config.start_driver {
+            return;
+        }
+        if let Some(path) = &config.driver_path {
+            let problem = (!Path::new(path).is_file()).then(|| "not found".to_string());
+            self.check(format!("{} at {path}", browser.driver()), problem);
+        } else if browser == Browser::Chrome && config.manage_driver {
+            let cache = capture::chromedriver::cache_dir();
+            self.check(format!("chromedriver matching Chrome is cached in or downloaded to {}", cache.display()), None);
+        } else {
+            match capture::find_driver(browser) {
+                Some(path) => self.check(format!("{} found at {}", browser.driver(), path.display()), None),
+                None => self.check(browser.driver(), Some("not found on PATH".to_string())),
+            }
+        }
+    }
+
+    pub fn detector(&mut self, name: &str) {
+        let registry = DetectorRegistry::default();
+        let problem = (!registry.names().any(|known| known == name)).then(|| {
+            format!("unknown, available: {}", registry.names().collect::<Vec<_>>().join(", "))
+        });
+        self.check(format!("detector backend {name:?}"), problem);
+    }
+
+    pub fn templates(&mut self, dir: &str) {
+        if !Path::new(dir).exists() {
+            self.check(format!("no templates in {dir}, using the built-in set"), None);
+            return;
+        }
+        let problem = match templates::check(dir) {
+            Ok(report) if report.is_complete() => None,
+            Ok(report) => {
+                let bad: Vec<&str> = report
+                    .checks
+                    .iter()
+                    .filter(|check| !matches!(check.status, TemplateStatus::Ok { .. } | TemplateStatus::Unexpected))
+                    .map(|check| check.label.as_str())
+                    .collect();
+                Some(format!("missing or unusable: {}", bad.join(", ")))
+            }
+            Err(e) => Some(e.to_string()),
+        };
+        self.check(format!("templates in {dir}"), problem);
+    }
+
+    pub fn input_file(&mut self, path: &Path) {
+        let problem = match fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => None,
+            Ok(_) => Some("not a file".to_string()),
+            Err(e) => Some(e.to_string()),
+        };
+        self.check(format!("input {} is readable", path.display()), problem);
+    }
+
+    /// Checks `dir` can be listed and returns how many PNGs it holds.
+    pub fn input_dir(&mut self, dir: &Path) -> usize {
+        match batch::list_images(dir) {
+            Ok(images) => {
+                self.check(format!("input directory {} has {} images", dir.display(), images.len()), None);
+                images.len()
+            }
+            Err(e) => {
+                self.check(format!("input directory {}", dir.display()), Some(e.to_string()));
+                0
+            }
+        }
+    }
+
+    /// Checks the journal in `output_dir` and returns how many images it records as done.
+    pub fn journal(&mut self, output_dir: &Path) -> usize {
+        match Journal::completed(output_dir) {
+            Ok(completed) => completed.len(),
+            Err(e) => {
+                self.check(format!("journal in {}", output_dir.display()), Some(e.to_string()));
+                0
+            }
+        }
+    }
+
+    /// Checks a file can be created at `path`.
+    pub fn writable_file(&mut self, path: &Path) {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let problem = probe_dir(parent).err();
+        self.check(format!("{} is writable", path.display()), problem);
+    }
+
+    /// Checks `dir` exists and is writable, or can be created.
+    pub fn writable_dir(&mut self, dir: &Path) {
+        // the closest ancestor that exists is where create_dir_all would start
+        let existing = dir.ancestors().find(|ancestor| ancestor.exists()).unwrap_or(Path::new("."));
+        let existing = if existing.as_os_str().is_empty() { Path::new(".") } else { existing };
+        let problem = probe_dir(existing).err();
+        self.check(format!("{} is writable", dir.display()), problem);
+    }
+}
+
+// create and remove a file, since permission bits alone don't account for ACLs or read-only mounts
+fn probe_dir(dir: &Path) -> Result<(), String> {
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()));
+    }
+    let probe = dir.join(".solitaire-ocr-dry-run");
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .map_err(|e| e.to_string())?;
+    fs::remove_file(&probe).map_err(|e| e.to_string())
+}
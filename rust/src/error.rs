@@ -0,0 +1,176 @@
+use serde::Serialize;
+use solitaire_capture::CaptureError;
+use solitaire_state::StateError;
+use solitaire_vision::VisionError;
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+/// Everything that can go wrong between launching the browser and writing the game state.
+#[derive(Debug, Error)]
+pub enum SolitaireOcrError {
+    #[error(transparent)]
+    Capture(#[from] CaptureError),
+
+    #[error(transparent)]
+    Vision(#[from] VisionError),
+
+    #[error(transparent)]
+    State(#[from] StateError),
+
+    #[error("failed to read config {}: {source}", path.display())]
+    ConfigRead {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("invalid config {}: {source}", path.display())]
+    ConfigParse {
+        path: PathBuf,
+        #[source]
+        source: Box<toml::de::Error>,
+    },
+
+    #[error("can't update config {}: {source}", path.display())]
+    ConfigEdit {
+        path: PathBuf,
+        #[source]
+        source: Box<toml_edit::TomlError>,
+    },
+
+    #[error("no profile named {0:?} in config")]
+    UnknownProfile(String),
+
+    #[error("invalid profile {name:?}: {source}")]
+    InvalidProfile {
+        name: String,
+        #[source]
+        source: Box<toml::de::Error>,
+    },
+
+    #[error("invalid value in ${name}: {source}")]
+    InvalidEnv {
+        name: String,
+        #[source]
+        source: Box<toml::de::Error>,
+    },
+
+    #[error("nothing to translate: give an image, --dir or --watch")]
+    NoInput,
+
+    #[error("failed to read input directory {}: {source}", path.display())]
+    InputDir {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to watch directory: {0}")]
+    Watch(#[from] notify::Error),
+
+    #[error("failed to write {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to record video to {}: {reason}", path.display())]
+    Recording { path: PathBuf, reason: String },
+
+    #[error("clicked {card} but the game shows {} as selected", selected.as_deref().unwrap_or("nothing"))]
+    NotSelected { card: String, selected: Option<String> },
+
+    #[error("incomplete template set in {dir}: {}", problems.join(", "))]
+    IncompleteTemplates { dir: String, problems: Vec<String> },
+
+    #[error("self-test detected the fixture wrongly: {}", mismatches.join("; "))]
+    SelftestFailed { mismatches: Vec<String> },
+
+    #[error("dry run found problems: {}", problems.join("; "))]
+    DryRun { problems: Vec<String> },
+
+    #[error("interrupted by signal")]
+    Interrupted,
+}
+
+/// The part of the pipeline an error came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Config,
+    Capture,
+    Detection,
+    State,
+    Io,
+    Interrupted,
+}
+
+impl Stage {
+    /// Process exit code used when the CLI fails in this stage.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Stage::Config => 3,
+            Stage::Capture => 4,
+            Stage::Detection => 5,
+            Stage::State => 6,
+            Stage::Io => 7,
+            // 128 + SIGINT, as shells report it
+            Stage::Interrupted => 130,
+        }
+    }
+}
+
+impl SolitaireOcrError {
+    pub fn stage(&self) -> Stage {
+        match self {
+            Self::ConfigRead { .. }
+            | Self::ConfigParse { .. }
+            | Self::ConfigEdit { .. }
+            | Self::UnknownProfile(_)
+            | Self::InvalidProfile { .. }
+            | Self::InvalidEnv { .. }
+            | Self::DryRun { .. }
+            | Self::NoInput
+            | Self::IncompleteTemplates { .. }
+            | Self::Vision(VisionError::UnknownDetector { .. }) => Stage::Config,
+            Self::Capture(CaptureError::Io { .. }) => Stage::Io,
+            Self::Capture(_) | Self::NotSelected { .. } => Stage::Capture,
+            Self::Vision(_) | Self::SelftestFailed { .. } => Stage::Detection,
+            Self::State(StateError::Io { .. } | StateError::Read { .. }) => Stage::Io,
+            Self::State(_) => Stage::State,
+            Self::InputDir { .. } | Self::Watch(_) | Self::Io { .. } | Self::Recording { .. } => Stage::Io,
+            Self::Interrupted => Stage::Interrupted,
+        }
+    }
+
+    /// Whether retrying the same operation has a reasonable chance of succeeding,
+    /// e.g. a WebDriver hiccup or a frame caught mid-animation.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Self::Capture(e) => e.is_recoverable(),
+            Self::Vision(VisionError::ImageDecode) | Self::State(StateError::Invalid(_)) | Self::NotSelected { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// JSON-serializable summary of an error for automation consuming stderr.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorReport {
+    pub stage: Stage,
+    pub message: String,
+    pub recoverable: bool,
+}
+
+impl From<&SolitaireOcrError> for ErrorReport {
+    fn from(error: &SolitaireOcrError) -> Self {
+        Self {
+            stage: error.stage(),
+            message: error.to_string(),
+            recoverable: error.is_recoverable(),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, SolitaireOcrError>;
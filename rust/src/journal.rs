@@ -0,0 +1,171 @@
+use crate::batch::BatchItem;
+use crate::error::{Result, SolitaireOcrError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// One line of the journal: which image was translated, where its state went
+/// and why it failed, if it did.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// File name of the input, relative to the batch's input directory.
+    pub input: String,
+    pub state_path: PathBuf,
+    pub error: Option<String>,
+}
+
+impl From<&BatchItem> for JournalEntry {
+    fn from(item: &BatchItem) -> Self {
+        Self {
+            input: file_name(&item.input),
+            state_path: item.state_path.clone(),
+            error: item.result.as_ref().err().map(ToString::to_string),
+        }
+    }
+}
+
+/// Append-only JSON lines record of a batch run, kept in the output directory
+/// so an interrupted run can pick up where it stopped.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    pub const FILE_NAME: &'static str = "solitaire-ocr-journal.jsonl";
+
+    /// Opens the journal in `output_dir`, keeping its entries when `resume` is
+    /// set and starting a fresh one otherwise.
+    pub fn open(output_dir: &Path, resume: bool) -> Result<Self> {
+        let path = output_dir.join(Self::FILE_NAME);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(resume)
+            .write(true)
+            .truncate(!resume)
+            .open(&path)
+            .map_err(|source| SolitaireOcrError::Io {
+                path: path.clone(),
+                source,
+            })?;
+        Ok(Self { path, file })
+    }
+
+    /// Inputs the journal at `output_dir` records as translated successfully.
+    /// Failed entries aren't included, so a resumed run retries them.
+    pub fn completed(output_dir: &Path) -> Result<HashSet<String>> {
+        let path = output_dir.join(Self::FILE_NAME);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashSet::new()),
+            Err(source) => return Err(SolitaireOcrError::Io { path, source }),
+        };
+
+        let mut completed = HashSet::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|source| SolitaireOcrError::Io {
+                path: path.clone(),
+                source,
+            })?;
+            // a run killed mid-write can leave a truncated last line
+            let Ok(entry) = serde_json::from_str::<JournalEntry>(&line) else {
+                continue;
+            };
+            match entry.error {
+                None if fs::metadata(&entry.state_path).is_ok() => {
+                    completed.insert(entry.input);
+                }
+                _ => {
+                    completed.remove(&entry.input);
+                }
+            }
+        }
+        Ok(completed)
+    }
+
+    pub fn record(&mut self, item: &BatchItem) -> Result<()> {
+        let mut line = serde_json::to_string(&JournalEntry::from(item)).expect("journal entries always serialize");
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|()| self.file.flush())
+            .map_err(|source| SolitaireOcrError::Io {
+                path: self.path.clone(),
+                source,
+            })
+    }
+}
+
+pub(crate) fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an empty directory of its own for each test
+    fn output_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("solitaire-ocr-journal-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn item(dir: &Path, input: &str, failed: bool) -> BatchItem {
+        let state_path = dir.join(input).with_extension("json");
+        if !failed {
+            fs::write(&state_path, "{}").unwrap();
+        }
+        BatchItem {
+            input: PathBuf::from("screenshots").join(input),
+            state_path,
+            annotated_path: None,
+            result: if failed {
+                Err(SolitaireOcrError::Interrupted)
+            } else {
+                Ok(serde_json::from_str(r#"{"draw_pile": [], "game_piles": [], "discard_pile": []}"#).unwrap())
+            },
+        }
+    }
+
+    #[test]
+    fn completed_skips_failed_and_missing_states() {
+        let dir = output_dir("completed");
+        let mut journal = Journal::open(&dir, false).unwrap();
+        journal.record(&item(&dir, "a.png", false)).unwrap();
+        journal.record(&item(&dir, "b.png", true)).unwrap();
+        journal.record(&item(&dir, "c.png", false)).unwrap();
+        journal.record(&item(&dir, "d.png", false)).unwrap();
+        // failed on a later run
+        journal.record(&item(&dir, "d.png", true)).unwrap();
+        fs::remove_file(dir.join("c.json")).unwrap();
+        // killed mid-write
+        fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join(Journal::FILE_NAME))
+            .and_then(|mut file| file.write_all(b"{\"input\":\"e.p"))
+            .unwrap();
+
+        assert_eq!(Journal::completed(&dir).unwrap(), HashSet::from(["a.png".to_string()]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_keeps_entries_only_to_resume() {
+        let dir = output_dir("resume");
+        assert!(Journal::completed(&dir).unwrap().is_empty());
+        Journal::open(&dir, false).unwrap().record(&item(&dir, "a.png", false)).unwrap();
+
+        Journal::open(&dir, true).unwrap().record(&item(&dir, "b.png", false)).unwrap();
+        let completed = Journal::completed(&dir).unwrap();
+        assert_eq!(completed, HashSet::from(["a.png".to_string(), "b.png".to_string()]));
+
+        Journal::open(&dir, false).unwrap();
+        assert!(Journal::completed(&dir).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,147 @@
+//! Reads the board of the Google Solitaire web game out of a screenshot.
+//!
+//! The pipeline has three stages, each in its own crate so they can be used
+//! on their own: [`capture`] drives a browser to take a screenshot,
+//! [`vision`] template-matches ranks and suits in it and assembles them into
+//! a [`GameState`], defined with its serialization in [`state`]. This crate
+//! ties them together with configuration and the batch, watch and daemon modes.
+
+pub mod batch;
+pub mod calibrate;
+pub mod config;
+pub mod consensus;
+pub mod daemon;
+pub mod dry_run;
+pub mod error;
+pub mod journal;
+pub mod live;
+pub mod moves;
+pub mod page_state;
+pub mod parallel;
+pub mod recording;
+pub mod selftest;
+pub mod watch;
+
+pub use solitaire_capture as capture;
+pub use solitaire_state as state;
+pub use solitaire_vision as vision;
+pub use solitaire_vision::{assemble, detect, pipeline, templates};
+
+pub use config::Config;
+pub use error::{ErrorReport, Result, SolitaireOcrError, Stage};
+pub use live::{CapturedFrame, LiveSession};
+pub use page_state::StateSource;
+pub use moves::{MoveExecutor, MoveObserver, ObservedMoves, PlayedMove};
+pub use recording::Recorder;
+pub use solitaire_state::GameState;
+pub use solitaire_vision::{BoundingBox, Detections, Template};
+
+use pipeline::{Detector, FileSource, FrameSource, MemorySource, Pipeline, Rectified};
+use vision::diff::Differ;
+use std::path::Path;
+
+/// Runs detection on the image at `image_path`, writing the resulting state as
+/// JSON to `state_path` and an annotated copy to `annotated_path`, each if given.
+/// With themes configured, it's read with the profile of the one it shows.
+pub fn translate(
+    image_path: &str,
+    annotated_path: Option<&str>,
+    state_path: Option<&str>,
+    config: &Config,
+) -> Result<GameState> {
+    if config.themes.is_empty() {
+        let detector = config.detector()?;
+        return translate_with(&detector, image_path, annotated_path, state_path, config);
+    }
+    let frame = detect::load_color_image(image_path)?;
+    let config = config.for_frame(&frame)?;
+    let detector = config.detector()?;
+    translate_source(&detector, MemorySource::new(frame), annotated_path, state_path, &config)
+}
+
+/// Same as [`translate`], but with a detector that was already set up, so
+/// its templates can be reused across images.
+pub fn translate_with(
+    detector: &impl Detector,
+    image_path: &str,
+    annotated_path: Option<&str>,
+    state_path: Option<&str>,
+    config: &Config,
+) -> Result<GameState> {
+    translate_source(detector, FileSource::new(image_path), annotated_path, state_path, config)
+}
+
+/// Writes a heatmap of every template's match scores on the image at
+/// `image_path` into `dir`, created if needed, and returns each file's name
+/// with its best score. See [`vision::heatmap::write_heatmaps`].
+pub fn write_heatmaps(image_path: &str, dir: &Path, config: &Config) -> Result<Vec<(String, f32)>> {
+    let frame = Rectified::new(FileSource::new(image_path), config.perspective.clone()).next_frame()?;
+    let templates = vision::cache::load(&config.template_dir)?;
+    std::fs::create_dir_all(dir).map_err(|source| SolitaireOcrError::Io {
+        path: dir.into(),
+        source,
+    })?;
+    Ok(vision::heatmap::write_heatmaps(&frame, &templates, &config.detection_config(), dir)?)
+}
+
+/// Runs detection on the next frame of `source`, writing the same outputs as [`translate`].
+pub fn translate_source(
+    detector: &impl Detector,
+    source: impl FrameSource,
+    annotated_path: Option<&str>,
+    state_path: Option<&str>,
+    config: &Config,
+) -> Result<GameState> {
+    translate_frame(detector, None, source, annotated_path, state_path, config)
+}
+
+/// Like [`translate_source`], but only rematches what `differ` finds changed
+/// since the last frame it saw, for successive frames of one game.
+pub fn translate_diffed(
+    detector: &impl Detector,
+    differ: &mut Differ,
+    source: impl FrameSource,
+    annotated_path: Option<&str>,
+    state_path: Option<&str>,
+    config: &Config,
+) -> Result<GameState> {
+    translate_frame(detector, Some(differ), source, annotated_path, state_path, config)
+}
+
+fn translate_frame(
+    detector: &impl Detector,
+    differ: Option<&mut Differ>,
+    source: impl FrameSource,
+    annotated_path: Option<&str>,
+    state_path: Option<&str>,
+    config: &Config,
+) -> Result<GameState> {
+    let source = Rectified::new(source, config.perspective.clone());
+    let mut pipeline = Pipeline::new(source, detector, pipeline::layout_assembler(config.layout.clone()));
+    let mut output = match differ {
+        Some(differ) => pipeline.run_diffed(differ)?,
+        None => pipeline.run()?,
+    };
+
+    if let Some(annotated_path) = annotated_path {
+        detect::draw_bounding_boxes(&mut output.frame, &output.detections.cards)?;
+        detect::draw_bounding_boxes(&mut output.frame, &output.detections.suits)?;
+        detect::draw_bounding_boxes(&mut output.frame, &output.detections.backs)?;
+        if let Some(empty_slots) = &output.detections.empty_slots {
+            detect::draw_bounding_boxes(&mut output.frame, empty_slots)?;
+        }
+        detect::draw_bounding_boxes(&mut output.frame, &output.detections.foundation_slots)?;
+        detect::draw_bounding_boxes(&mut output.frame, &output.detections.end_screens)?;
+        detect::draw_bounding_boxes(&mut output.frame, &output.detections.highlights)?;
+        detect::draw_bounding_boxes(&mut output.frame, &output.detections.selected)?;
+
+        // save image with bounding boxes
+        detect::save_image(&output.frame, annotated_path)?;
+    }
+
+    if let Some(state_path) = state_path {
+        state::save_game_state(&output.state, state_path)?;
+    }
+
+    Ok(output.state)
+}
@@ -0,0 +1,134 @@
+use crate::capture::{self, BrowserSession, CaptureConfig, CaptureError, Viewport};
+use crate::detect;
+use crate::error::Result;
+use crate::pipeline::MemorySource;
+use crate::recording::Recorder;
+use crate::state::{GameState, StateError};
+use opencv::core::Mat;
+use opencv::prelude::*;
+use tracing::{debug, info};
+
+/// One screenshot taken from a [`LiveSession`], both encoded and decoded.
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    /// Counts up from 0 over the lifetime of the session.
+    pub index: u64,
+    /// In CSS pixels if the session normalizes the pixel ratio.
+    pub png: Vec<u8>,
+    /// The png decoded in BGR color.
+    pub frame: Mat,
+}
+
+impl CapturedFrame {
+    /// The decoded frame as a pipeline source.
+    pub fn source(&self) -> MemorySource {
+        MemorySource::new(self.frame.clone())
+    }
+}
+
+/// A browser kept open on one game for as many captures as needed, so
+/// repeated detections follow the same game instead of dealing a new one.
+pub struct LiveSession {
+    browser: BrowserSession,
+    frames: u64,
+    // applied to every screenshot to undo the display's device pixel ratio
+    scale: f64,
+    expected_size: Option<Viewport>,
+    recorder: Option<Recorder>,
+}
+
+impl LiveSession {
+    /// Starts the browser and a new game, or attaches to one, as `config` says.
+    pub async fn start(config: &CaptureConfig) -> Result<Self> {
+        let browser = BrowserSession::start(config).await?;
+        let mut scale = 1.0;
+        if config.normalize_pixel_ratio {
+            let ratio = browser.device_pixel_ratio().await?;
+            if ratio > 0.0 && ratio != 1.0 {
+                info!(ratio, "scaling screenshots to CSS pixels");
+                scale = 1.0 / ratio;
+            }
+        }
+        Ok(Self {
+            browser,
+            frames: 0,
+            scale,
+            expected_size: config.expected_size,
+            recorder: None,
+        })
+    }
+
+    /// Wraps a session that's already running, using its screenshots as they are.
+    pub fn new(browser: BrowserSession) -> Self {
+        Self {
+            browser,
+            frames: 0,
+            scale: 1.0,
+            expected_size: None,
+            recorder: None,
+        }
+    }
+
+    /// Adds every frame captured from now on to `recorder`'s video.
+    pub fn record(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Screenshots the game as it is right now and decodes it.
+    pub async fn capture_frame(&mut self) -> Result<CapturedFrame> {
+        let mut png = self.browser.screenshot().await?;
+        if self.scale != 1.0 {
+            png = detect::scale_png(&png, self.scale)?;
+        }
+        let frame = detect::decode_color_image(&png)?;
+        if let Some(expected) = self.expected_size {
+            let actual = Viewport {
+                width: frame.cols() as u32,
+                height: frame.rows() as u32,
+            };
+            if actual != expected {
+                return Err(CaptureError::UnexpectedSize { expected, actual }.into());
+            }
+        }
+        let index = self.frames;
+        self.frames += 1;
+        debug!(index, "captured frame");
+        let captured = CapturedFrame { index, png, frame };
+        if let Some(recorder) = &mut self.recorder {
+            recorder.write(&captured)?;
+        }
+        Ok(captured)
+    }
+
+    /// The state the page reports through `state_script`, if one is configured.
+    pub async fn page_state(&self) -> Result<Option<GameState>> {
+        match self.browser.page_state().await? {
+            Some(value) => Ok(Some(serde_json::from_value(value).map_err(StateError::from)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn browser(&self) -> &BrowserSession {
+        &self.browser
+    }
+
+    /// Closes the browser, or leaves it running if the session attached to it,
+    /// and finishes the recording if there is one.
+    pub async fn close(self) -> Result<()> {
+        let recorded = self.recorder.map_or(Ok(()), Recorder::finish);
+        self.browser.close().await?;
+        recorded
+    }
+}
+
+/// Starts a session, writes one screenshot of the new game to `path` and
+/// closes it again. Returns the state the page reported at the same time,
+/// if there's a `state_script` to read it with.
+pub async fn capture_screenshot(config: &CaptureConfig, path: &str) -> Result<Option<GameState>> {
+    let mut session = LiveSession::start(config).await?;
+    let captured = session.capture_frame().await?;
+    capture::write_screenshot(&captured.png, path)?;
+    let page_state = session.page_state().await?;
+    session.close().await?;
+    Ok(page_state)
+}
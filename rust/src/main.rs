@@ -1,396 +1,497 @@
-use fantoccini::{ClientBuilder, Locator};
-use std::{process::{Child, Command}, time::Duration, fs};
-use tokio::time::sleep;
-use opencv::core::{Mat, Point, Scalar, Rect};
-use opencv::imgcodecs::{imread, imwrite, IMREAD_COLOR};
-use opencv::imgproc::{cvt_color, match_template, rectangle, LINE_8, TM_CCOEFF_NORMED,COLOR_BGR2GRAY};
-use opencv::prelude::*;
-use serde::Serialize;
-
-#[derive(Debug, Clone)]
-struct BoundingBox {
-    x1: i32,
-    y1: i32,
-    x2: i32,
-    y2: i32,
-    label: String,
-}
-
-#[derive(Serialize)]
-struct GameState {
-    draw_pile: Vec<String>,
-    game_piles: Vec<Vec<String>>,
-    discard_pile: Vec<String>,
-}
+mod cli;
+mod progress;
+mod repl;
+mod signal;
+
+use clap::Parser;
+use cli::{Cli, Command, TemplatesCommand};
+use progress::ProgressBars;
+use signal::until_shutdown;
+use solitaire_ocr::batch::{self, BatchItem};
+use solitaire_ocr::capture::Backend;
+use solitaire_ocr::daemon::GameOverAction;
+use solitaire_ocr::dry_run::DryRun;
+use solitaire_ocr::templates::{self, TemplateStatus};
+use solitaire_ocr::{
+    calibrate, capture, consensus, daemon, detect, live, page_state, parallel, selftest, state, translate, watch, Config, ErrorReport, GameState,
+    LiveSession, Recorder, SolitaireOcrError,
+};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use tracing::{error, info};
 
 #[tokio::main]
-async fn main() -> Result<(), fantoccini::error::CmdError> {
-    // start chrome and go to solitaire
-    let mut chrome = start_chrome()?;
-
-    let client = ClientBuilder::native()
-        .connect("http://localhost:4444")
-        .await
-        .expect("failed to connect to WebDriver");
-
-        
-    client.goto("https://www.google.com/logos/fnbx/solitaire/standalone.html").await?;
-
-    client.wait().for_element(Locator::Id("solitaire-easy-button")).await?;
-    let easy_btn = client.find(Locator::Id("solitaire-easy-button")).await?;
-    easy_btn.click().await?;
-
-    sleep(Duration::from_secs(3)).await;
-
-    // take screenshot
-    let ss = client.screenshot().await?;
-    std::fs::write("screenshot.png", ss).expect("failed to write screenshot");
-
-    chrome.kill()?;
-    chrome.wait()?;
-
-    // convert screenshot to game state
-    translate().expect("Failed to translate");
-
-    Ok(())
-}
-
-fn translate() -> opencv::Result<()> {
-    // to test with manual pngs replace screenshot with png name and comment out the chromium code
-    let image_path = "screenshot.png";
-    let mut img = load_image(image_path)?;
-    let output_path = "output_with_boxes.png";
-
-    let templates = get_templates();
-    let card_threshold = 0.79;
-    let suit_threshold = 0.85;
-
-    let mut card_bounding_boxes = Vec::new();
-    let mut suit_bounding_boxes = Vec::new();
-
-    for template_path in &templates {
-        let template = load_image(template_path)?;
-        // use png name for label
-        let label = template_path.split('\\').last().unwrap().replace(".png", "");
-
-        // match card values and suits with different thresholds for accuracy
-        let threshold = if label == "hearts" || label == "diamonds" || label == "clubs" || label == "spades" {
-            suit_threshold
-        } else {
-            card_threshold
-        };
-
-        let matches = match_template_with_threshold(&img, &template, threshold)?;
-        let boxes = create_bounding_boxes(matches, template.cols(), template.rows(), label);
-
-        if threshold == suit_threshold {
-            suit_bounding_boxes.extend(boxes);
-        } else {
-            card_bounding_boxes.extend(boxes);
-        }
-    }
-
-    // nms for both
-    let filtered_cards = non_maximum_suppression(card_bounding_boxes.clone(), 0.5);
-    let filtered_suits = non_maximum_suppression(suit_bounding_boxes.clone(), 0.5);
-
-    draw_bounding_boxes(&mut img, &filtered_cards)?;
-    draw_bounding_boxes(&mut img, &filtered_suits)?;
-
-    // save image with bounding boxes
-    save_image(&img, output_path)?;
-
-    let game_state = generate_game_state(filtered_cards, filtered_suits, img.cols(), 40);
-    let _ = save_game_state(&game_state, "output.json");
-
-    println!("Game state saved to output.json");
-
-    Ok(())
-}
-
-fn start_chrome() -> Result<Child, std::io::Error> {
-    Command::new("chromedriver")
-        .arg("--port=4444")
-        .spawn()
-}
-
-fn get_templates() -> Vec<String> {
-    let template_dir = "templates";
-    fs::read_dir(template_dir)
-        .expect("Failed to read templates directory")
-        .filter_map(|entry| entry.ok())
-        .map(|entry| entry.path().to_str().unwrap().to_string())
-        .collect()
-}
-
-// load image in greyscale
-fn load_image(path: &str) -> opencv::Result<Mat> {
-    let img = imread(path, IMREAD_COLOR)?;
-    let mut gray = Mat::default();
-    cvt_color(&img, &mut gray, COLOR_BGR2GRAY, 0)?;
-    Ok(gray)
-}
-
-fn match_template_with_threshold(
-    img: &Mat,
-    template: &Mat,
-    threshold: f32,
-) -> opencv::Result<Vec<Point>> {
-    let mut result = Mat::default();
-    // find matches
-    match_template(img, template, &mut result, TM_CCOEFF_NORMED, &Mat::default())?;
-
-    // filter matches by threshold
-    let mut matches = Vec::new();
-    for y in 0..result.rows() {
-        for x in 0..result.cols() {
-            let value = *result.at_2d::<f32>(y, x)?;
-            if value >= threshold {
-                matches.push(Point::new(x, y));
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    tracing_subscriber::fmt()
+        .with_max_level(cli.log_level())
+        .with_writer(std::io::stderr)
+        .init();
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // a single JSON object on stderr so automation doesn't have to parse log lines
+            let report = ErrorReport::from(&e);
+            match serde_json::to_string(&report) {
+                Ok(json) => eprintln!("{json}"),
+                Err(_) => eprintln!("{e}"),
             }
+            ExitCode::from(e.stage().exit_code())
         }
     }
-    Ok(matches)
 }
 
-fn create_bounding_boxes(
-    matches: Vec<Point>,
-    template_width: i32,
-    template_height: i32,
-    label: String,
-) -> Vec<BoundingBox> {
-    matches
-        .into_iter()
-        .map(|pt| BoundingBox {
-            x1: pt.x,
-            y1: pt.y,
-            x2: pt.x + template_width,
-            y2: pt.y + template_height,
-            label: label.clone(),
-        })
-        .collect()
-}
-
-fn non_maximum_suppression(
-    boxes: Vec<BoundingBox>,
-    overlap_thresh: f32,
-) -> Vec<BoundingBox> {
-    let mut filtered_boxes = Vec::new();
-    let mut boxes = boxes.clone();
-
-    // sorted by bottom right corner
-    boxes.sort_by(|a, b| b.y2.cmp(&a.y2));
-    while let Some(current) = boxes.pop() {
-        filtered_boxes.push(current.clone());
-        boxes.retain(|b| {
-            let inter_x1 = current.x1.max(b.x1);
-            let inter_y1 = current.y1.max(b.y1);
-            let inter_x2 = current.x2.min(b.x2);
-            let inter_y2 = current.y2.min(b.y2);
-
-            let inter_area = (inter_x2 - inter_x1).max(0) * (inter_y2 - inter_y1).max(0);
-            let box_area = (b.x2 - b.x1) * (b.y2 - b.y1);
-            let overlap = inter_area as f32 / box_area as f32;
-
-            overlap <= overlap_thresh
-        });
+async fn run(cli: Cli) -> Result<(), SolitaireOcrError> {
+    let mut config = match &cli.config {
+        Some(path) => Config::load(path)?,
+        None => Config::load_or_default(Config::FILE_NAME)?,
+    }
+    .select_profile(cli.profile.as_deref())?
+    .with_env()?;
+    if let Some(detector) = &cli.detector {
+        config.detector = detector.clone();
+    }
+    if let Some(frames) = cli.consensus {
+        config.consensus.frames = frames;
+    }
+    if let Some(backend) = cli.capture_backend {
+        config.capture.backend = backend;
+    }
+    if let Some(difficulty) = cli.difficulty {
+        config.capture.difficulty = difficulty;
+        config.layout.draw_count = difficulty.draw_count();
+    }
+    if let Some(browser) = cli.browser {
+        config.capture.browser = browser;
+    }
+    if let Some(port) = cli.webdriver_port {
+        config.capture.webdriver_port = Some(port);
+        config.capture.webdriver_url = None;
+    }
+    if let Some(url) = &cli.webdriver_url {
+        config.capture.webdriver_url = Some(url.clone());
+        config.capture.start_driver = false;
+    }
+    if let Some(url) = &cli.attach {
+        config.capture.attach = Some(url.clone());
+    }
+    if cli.headless {
+        config.capture.headless = true;
+    }
+    if let Some(viewport) = cli.viewport {
+        config.capture.viewport = Some(viewport);
     }
-    filtered_boxes
-}
-
-fn associate_cards_and_suits(
-    cards: Vec<BoundingBox>,
-    suits: Vec<BoundingBox>,
-) -> Vec<BoundingBox> {
-    let mut associated_cards = Vec::new();
-
-    for mut card in cards {
-        let mut closest_suit = None;
-        let mut min_distance = i32::MAX;
 
-        // find closest suit
-        for suit in &suits {
-            let horizontal_distance = (suit.x1 - card.x2).abs();
-            let vertical_overlap = (suit.y1 <= card.y2) && (suit.y2 >= card.y1);
+    if let Command::Translate { photo: true, .. } = cli.command {
+        config.perspective.enabled = true;
+    }
 
-            if vertical_overlap && horizontal_distance < min_distance {
-                min_distance = horizontal_distance;
-                closest_suit = Some(suit.label.clone());
+    if cli.dry_run {
+        let dry_run = plan(&cli.command, &config);
+        for (i, step) in dry_run.plan.iter().enumerate() {
+            println!("{}. {step}", i + 1);
+        }
+        for check in &dry_run.checks {
+            match &check.problem {
+                None => println!("ok    {}", check.description),
+                Some(problem) => println!("FAIL  {}: {problem}", check.description),
             }
         }
-
-        // associate card with suit
-        if let Some(suit_label) = closest_suit {
-            card.label = format!("{} {}", card.label, suit_label);
+        if !dry_run.passed() {
+            let problems = dry_run
+                .checks
+                .into_iter()
+                .filter_map(|check| Some(format!("{}: {}", check.description, check.problem?)))
+                .collect();
+            return Err(SolitaireOcrError::DryRun { problems });
         }
-
-        associated_cards.push(card);
+        info!("Dry run passed");
+        return Ok(());
     }
 
-    associated_cards
-}
-
-
-fn group_bounding_boxes_by_x_percentage(
-    bounding_boxes: &[BoundingBox],
-    image_width: i32,
-) -> std::collections::HashMap<String, Vec<BoundingBox>> {
-    let percentage_ranges = (0..9).map(|i| (i as f32 / 9.0, (i + 1) as f32 / 9.0));
-    let mut grouped_boxes: std::collections::HashMap<String, Vec<BoundingBox>> = 
-        percentage_ranges.clone().map(|(start, end)| (format!("{:.0}%-{:.0}%", start * 100.0, end * 100.0), Vec::new()))
-        .collect();
-
-    for b in bounding_boxes {
-        let center_x = (b.x1 + b.x2) as f32 / 2.0;
-        let x_percentage = center_x / image_width as f32;
-
-        for (start, end) in percentage_ranges.clone() {
-            if start <= x_percentage && x_percentage < end {
-                let range_key = format!("{:.0}%-{:.0}%", start * 100.0, end * 100.0);
-                grouped_boxes.get_mut(&range_key).unwrap().push(b.clone());
-                break;
+    match cli.command {
+        Command::Capture { screenshot } => {
+            until_shutdown(live::capture_screenshot(&config.capture, &screenshot)).await?;
+            info!("Screenshot saved to {screenshot}");
+        }
+        Command::Translate { image: Some(image), output, debug_heatmaps, .. } => {
+            if let Some(dir) = &debug_heatmaps {
+                let written = solitaire_ocr::write_heatmaps(&image, dir, &config)?;
+                info!("{} heatmaps saved to {}", written.len(), dir.display());
+            }
+            let state = translate(&image, output.annotated_path(), output.state_path(), &config)?;
+            if cli.strict {
+                state.validate()?;
             }
+            report_state(&state, &output)?;
         }
-    }
-
-    grouped_boxes
-}
-
-fn group_bounding_boxes_by_y_range(
-    bounding_boxes: &[BoundingBox],
-    y_range_step: i32,
-) -> Vec<Vec<BoundingBox>> {
-    let mut grouped_rows: Vec<Vec<BoundingBox>> = Vec::new();
-    let mut current_row: Vec<BoundingBox> = Vec::new();
-
-    let mut y_start = 0;
-    let mut y_end = y_range_step;
-
-    for b in bounding_boxes {
-        let center_y = (b.y1 + b.y2) / 2;
-
-        if y_start <= center_y && center_y < y_end {
-            current_row.push(b.clone());
-        } else {
-            if !current_row.is_empty() {
-                grouped_rows.push(current_row.clone());
+        Command::Translate { image: None, watch: Some(watch), out, output, .. } => {
+            let out = out.unwrap_or_else(|| watch.clone());
+            watch::watch_dir(&watch, &out, !output.no_annotated, &config, |item| {
+                log_batch_item(item);
+                print_batch_item(item, &output);
+            })?;
+        }
+        Command::Translate { image: None, dir: Some(dir), out, resume, output, .. } => {
+            let out = out.unwrap_or_else(|| dir.clone());
+            let progress = Arc::new(ProgressBars::new(cli.quiet));
+            let items = batch::translate_dir(&dir, &out, !output.no_annotated, resume, &config, progress)?;
+
+            items.iter().for_each(log_batch_item);
+            items.iter().for_each(|item| print_batch_item(item, &output));
+            let failed = items.iter().filter(|item| item.result.is_err()).count();
+            info!("Translated {} of {} images", items.len() - failed, items.len());
+        }
+        // clap requires one of the inputs, so this is only reached if that changes
+        Command::Translate { .. } => return Err(SolitaireOcrError::NoInput),
+        Command::Run { screenshot, sessions, output, .. } if sessions > 1 => {
+            let options = parallel::ParallelOptions {
+                sessions: sessions.into(),
+                screenshot_path: screenshot,
+                annotated_path: output.annotated_path().map(str::to_string),
+                state_path: output.state_path().map(str::to_string),
+            };
+            let outcomes = until_shutdown(parallel::run(&config, &options)).await?;
+            for outcome in &outcomes {
+                match &outcome.result {
+                    Ok(state) if output.stdout => print_tagged_state(outcome.session, state),
+                    Ok(_) => info!("session {}: {}", outcome.session, outcome.state_path.as_deref().unwrap_or("stdout")),
+                    Err(e) => error!("session {}: {e}", outcome.session),
+                }
             }
-            current_row = vec![b.clone()];
-            y_start = center_y / y_range_step * y_range_step;
-            y_end = y_start + y_range_step;
         }
-    }
+        Command::Run {
+            screenshot,
+            watch_interval: Some(interval),
+            state_log,
+            on_game_over,
+            outcome_log,
+            record,
+            pipeline,
+            output,
+            ..
+        } => {
+            // one video frame per capture, so it plays back at the watch interval
+            let recorder = record
+                .map(|path| Recorder::new(path, 1.0 / interval.as_secs_f64()))
+                .transpose()?;
+            let mut session = until_shutdown(LiveSession::start(&config.capture)).await?;
+            if let Some(recorder) = recorder {
+                session.record(recorder);
+            }
+            let options = daemon::DaemonOptions {
+                interval,
+                screenshot_path: screenshot,
+                annotated_path: output.annotated_path().map(str::to_string),
+                state_path: output.state_path().map(str::to_string),
+                stdout: output.stdout,
+                state_log,
+                on_game_over,
+                outcome_log,
+                pipeline,
+            };
+            // watch mode runs until stopped, so a signal is a normal way to end it
+            let result = match until_shutdown(daemon::run(&mut session, &config, &options)).await {
+                Err(SolitaireOcrError::Interrupted) => Ok(()),
+                result => result,
+            };
+            session.close().await?;
+            result?;
+        }
+        Command::Run { screenshot, watch_interval: None, output, .. } => {
+            // a single frame unless consensus is on, decoded without a trip through the disk
+            let (page_state, detected) =
+                until_shutdown(consensus::capture(&config, screenshot.as_deref(), output.annotated_path())).await?;
+            let state = page_state::reconcile(config.state_source, detected, page_state)?;
+            if let Some(path) = output.state_path() {
+                state::save_game_state(&state, path)?;
+            }
+            if cli.strict {
+                state.validate()?;
+            }
+            report_state(&state, &output)?;
+        }
+        Command::Repl => until_shutdown(repl::run(config)).await?,
+        Command::Calibrate { image, truth, min, max, step, write } => {
+            let truth_path = truth.unwrap_or_else(|| Path::new(&image).with_extension("json").display().to_string());
+            let truth = state::load_game_state(&truth_path)?;
+            let calibration = calibrate::run(&image, &truth, &config, &calibrate::Sweep { min, max, step })?;
+            println!(
+                "card_threshold = {}\nsuit_threshold = {}\n{} cards off after trying {} pairs",
+                calibration.card_threshold, calibration.suit_threshold, calibration.errors, calibration.tried
+            );
+            if write {
+                let path = cli.config.as_deref().unwrap_or(Config::FILE_NAME);
+                calibrate::write(Path::new(path), config.profile.as_deref(), &calibration)?;
+                info!("Thresholds saved to {path}");
+            }
+        }
+        Command::Selftest => {
+            let report = selftest::run(&config)?;
+            if !report.passed() {
+                return Err(SolitaireOcrError::SelftestFailed { mismatches: report.mismatches() });
+            }
+            info!("Self-test passed");
+        }
+        Command::Templates { command: TemplatesCommand::Check { dir } } => {
+            let dir = dir.unwrap_or(config.template_dir);
+            let report = templates::check(&dir)?;
+
+            for check in &report.checks {
+                match &check.status {
+                    TemplateStatus::Ok { width, height } => println!("ok          {:<8} {width}x{height}", check.label),
+                    TemplateStatus::Missing => println!("missing     {}", check.label),
+                    TemplateStatus::Unreadable(e) => println!("unreadable  {:<8} {e}", check.label),
+                    TemplateStatus::Inconsistent { width, height, expected_height } => println!(
+                        "size        {:<8} {width}x{height}, expected height around {expected_height}",
+                        check.label
+                    ),
+                    TemplateStatus::Unexpected => println!("unexpected  {}", check.label),
+                }
+            }
 
-    if !current_row.is_empty() {
-        grouped_rows.push(current_row);
+            if !report.is_complete() {
+                let problems = report
+                    .checks
+                    .into_iter()
+                    .filter_map(|check| match check.status {
+                        TemplateStatus::Missing => Some(format!("{} missing", check.label)),
+                        TemplateStatus::Unreadable(_) => Some(format!("{} unreadable", check.label)),
+                        TemplateStatus::Inconsistent { .. } => Some(format!("{} of inconsistent size", check.label)),
+                        TemplateStatus::Ok { .. } | TemplateStatus::Unexpected => None,
+                    })
+                    .collect();
+                return Err(SolitaireOcrError::IncompleteTemplates { dir, problems });
+            }
+            info!("Template set in {dir} is complete");
+        }
+        Command::Templates { command: TemplatesCommand::Generate { screenshots, out, suits, corner } } => {
+            let out = out.unwrap_or(config.template_dir);
+            let screenshots = screenshots
+                .iter()
+                .map(|path| detect::load_color_image(path))
+                .collect::<Result<Vec<_>, _>>()?;
+            let options = templates::GenerateOptions {
+                suits,
+                corner,
+                segmentation: config.detection.segmentation.clone(),
+            };
+            for template in templates::generate(&screenshots, Path::new(&out), &options)? {
+                println!("{:<8} {}x{}  {}", template.label, template.width, template.height, template.path.display());
+            }
+            info!("Templates saved to {out}");
+        }
     }
 
-    grouped_rows
+    Ok(())
 }
 
-fn generate_game_state(
-    cards: Vec<BoundingBox>,
-    suits: Vec<BoundingBox>,
-    image_width: i32,
-    y_range_step: i32,
-) -> GameState {
-    let associated_cards = associate_cards_and_suits(cards, suits);
-
-    let grouped_by_x = group_bounding_boxes_by_x_percentage(&associated_cards, image_width);
-
-    let mut draw_pile = Vec::new();
-    let mut game_piles = vec![Vec::new(); 7];
-    let mut discard_pile = vec![None; 4];
-
-    for (x_range, boxes) in grouped_by_x {
-        let rows = group_bounding_boxes_by_y_range(&boxes, y_range_step);
+/// What `command` would do with `config`, and whether everything it needs is in place.
+fn plan(command: &Command, config: &Config) -> DryRun {
+    let mut dry_run = DryRun::default();
 
-        if x_range == "0%-11%" {
-            draw_pile = rows
-                .iter()
-                .flat_map(|row| row.iter().map(|b| b.label.clone()))
-                .collect();
-        } else if x_range == "89%-100%" {
-            for (i, row) in rows.iter().enumerate().take(4) {
-                if let Some(b) = row.first() {
-                    // temp: filters out J from discard, for some reason its always matched in that area
-                    if b.label.contains("J") {
-                        discard_pile[i] = Some("null".to_string());
-                    } else {
-                        discard_pile[i] = Some(b.label.clone());
-                    }
-                }
+    match command {
+        Command::Capture { screenshot } => {
+            dry_run.driver(&config.capture);
+            dry_run.writable_file(Path::new(screenshot));
+            plan_session(&mut dry_run, &config.capture);
+            dry_run.step(format!("save a screenshot to {screenshot}"));
+        }
+        Command::Translate { image: Some(image), output, debug_heatmaps, .. } => {
+            dry_run.input_file(Path::new(image));
+            dry_run.detector(&config.detector);
+            dry_run.templates(&config.template_dir);
+            if let Some(dir) = debug_heatmaps {
+                dry_run.writable_dir(dir);
+                dry_run.step(format!("save a match score heatmap per template to {}", dir.display()));
             }
-        } else if let Ok(start_percentage) = x_range
-            .split('-')
-            .next()
-            .unwrap()
-            .trim_end_matches('%')
-            .parse::<i32>()
-        {
-            let index = ((start_percentage - 11) / 11) as usize;
-            if index < game_piles.len() {
-                let starting_y = 75;
-
-                if let Some(first_box) = rows
-                .iter()
-                .flat_map(|row| row.iter())
-                .min_by_key(|b| b.y1)
-                {
-                    let null_rows = (first_box.y1.saturating_sub(starting_y)) / y_range_step;
-                    game_piles[index].resize(null_rows as usize, "null".to_string());
-                }
-                for row in rows {
-                    game_piles[index].extend(row.iter().map(|b| b.label.clone()));
+            dry_run.step(format!("detect cards in {image} using templates from {}", config.template_dir));
+            plan_outputs(&mut dry_run, output);
+            dry_run.step(format!("save the game state to {}", state_target(output)));
+        }
+        Command::Translate { image: None, dir, watch, out, resume, output, .. } => {
+            dry_run.detector(&config.detector);
+            dry_run.templates(&config.template_dir);
+            if let Some(watch) = watch {
+                let out = out.as_deref().unwrap_or(watch);
+                dry_run.input_dir(watch);
+                dry_run.writable_dir(out);
+                dry_run.step(format!("watch {} and translate new PNGs into {}", watch.display(), out.display()));
+            } else if let Some(dir) = dir {
+                let out = out.as_deref().unwrap_or(dir);
+                let images = dry_run.input_dir(dir);
+                let done = if *resume { dry_run.journal(out) } else { 0 };
+                dry_run.writable_dir(out);
+                dry_run.step(format!(
+                    "translate {} images from {} into {}",
+                    images.saturating_sub(done),
+                    dir.display(),
+                    out.display()
+                ));
+                if done > 0 {
+                    dry_run.step(format!("skip {done} images the journal records as translated"));
                 }
             }
+            if !output.no_annotated {
+                dry_run.step("write an annotated <name>_boxes.png next to each state");
+            }
+            if output.stdout {
+                dry_run.step("print each game state on stdout");
+            }
+        }
+        Command::Run { screenshot, watch_interval, state_log, on_game_over, outcome_log, record, pipeline, sessions, output } => {
+            dry_run.driver(&config.capture);
+            dry_run.detector(&config.detector);
+            dry_run.templates(&config.template_dir);
+            for log in [screenshot, state_log, outcome_log, record].into_iter().flatten() {
+                dry_run.writable_file(Path::new(log));
+            }
+            plan_session(&mut dry_run, &config.capture);
+            plan_outputs(&mut dry_run, output);
+            let capture_to = |session: Option<&str>| match (screenshot, session) {
+                (Some(path), Some(session)) => format!("capture to {}", parallel::session_path(path, session)),
+                (Some(path), None) => format!("capture to {path}"),
+                (None, _) => "capture".to_string(),
+            };
+            match watch_interval {
+                _ if *sessions > 1 => dry_run.step(format!(
+                    "in each of {sessions} sessions at once, {} and save the game state to {}",
+                    capture_to(Some("<session>")),
+                    output
+                        .state_path()
+                        .map_or("stdout".to_string(), |path| parallel::session_path(path, "<session>"))
+                )),
+                Some(interval) => dry_run.step(format!(
+                    "every {}, {} and save the game state to {}",
+                    humantime::format_duration(*interval),
+                    capture_to(None),
+                    state_target(output)
+                )),
+                None => dry_run.step(format!("{} and save the game state to {}", capture_to(None), state_target(output))),
+            }
+            if watch_interval.is_some() {
+                dry_run.step(match on_game_over {
+                    GameOverAction::Stop => "stop once the game ends",
+                    GameOverAction::Dismiss => "dismiss the dialog once the game ends and keep watching",
+                    GameOverAction::NewGame => "deal a new game whenever one ends",
+                });
+            }
+            if *pipeline && !config.consensus.is_enabled() {
+                dry_run.step("detect each capture while the next one is taken");
+            }
+            if let Some(path) = record {
+                dry_run.step(format!("record every capture into {path}"));
+            }
+        }
+        Command::Repl => {
+            dry_run.driver(&config.capture);
+            dry_run.detector(&config.detector);
+            dry_run.templates(&config.template_dir);
+            dry_run.step("start an interactive session; the browser is only launched on `capture`");
+        }
+        Command::Calibrate { image, truth, write, .. } => {
+            dry_run.input_file(Path::new(image));
+            let truth = truth.clone().unwrap_or_else(|| Path::new(image).with_extension("json").display().to_string());
+            dry_run.input_file(Path::new(&truth));
+            dry_run.templates(&config.template_dir);
+            dry_run.step(format!("sweep card and suit thresholds over {image} and compare with {truth}"));
+            if *write {
+                dry_run.step("save the best thresholds to the config file");
+            }
+        }
+        Command::Selftest => {
+            dry_run.detector(&config.detector);
+            dry_run.templates(&config.template_dir);
+            dry_run.step("detect the bundled screenshot and compare it with its known state");
+        }
+        Command::Templates { command: TemplatesCommand::Check { dir } } => {
+            let dir = dir.as_deref().unwrap_or(&config.template_dir);
+            dry_run.step(format!("check the template set in {dir}"));
+        }
+        Command::Templates { command: TemplatesCommand::Generate { screenshots, out, suits, .. } } => {
+            for screenshot in screenshots {
+                dry_run.input_file(Path::new(screenshot));
+            }
+            let out = out.as_deref().unwrap_or(&config.template_dir);
+            dry_run.step(format!("cut templates for {} suits out of the screenshots into {out}", suits.len()));
         }
     }
 
-    let discard_pile: Vec<String> = discard_pile
-        .into_iter()
-        .map(|card| card.unwrap_or_else(|| "null".to_string()))
-        .collect();
+    dry_run
+}
 
-    GameState {
-        draw_pile,
-        game_piles,
-        discard_pile,
+fn plan_session(dry_run: &mut DryRun, capture: &capture::CaptureConfig) {
+    let browser = capture.browser;
+    if capture.backend == Backend::Cdp {
+        dry_run.step(format!("launch {browser} over CDP and open a new {} game", capture.difficulty));
+        return;
+    }
+    if let Some(url) = &capture.attach {
+        dry_run.step(format!("attach to {browser} through the WebDriver server at {url} and use its current page"));
+        return;
+    }
+    if let (false, Some(url)) = (capture.start_driver, &capture.webdriver_url) {
+        dry_run.step(format!(
+            "start {browser} through the WebDriver server at {url} and open a new {} game",
+            capture.difficulty
+        ));
+        return;
     }
+    let address = match (&capture.webdriver_url, capture.webdriver_port) {
+        (Some(url), _) => url.clone(),
+        (None, Some(port)) => format!("http://localhost:{port}"),
+        (None, None) => "a free local port".to_string(),
+    };
+    dry_run.step(format!(
+        "start {browser} through {} at {address} and open a new {} game",
+        browser.driver(),
+        capture.difficulty
+    ));
 }
 
-fn save_game_state(state: &GameState, path: &str) -> std::io::Result<()> {
-    let json = serde_json::to_string_pretty(state)?;
-    fs::write(path, json)?;
-    Ok(())
+fn plan_outputs(dry_run: &mut DryRun, output: &cli::OutputArgs) {
+    if let Some(state) = output.state_path() {
+        dry_run.writable_file(Path::new(state));
+    }
+    if let Some(annotated) = output.annotated_path() {
+        dry_run.writable_file(Path::new(annotated));
+        dry_run.step(format!("save the annotated screenshot to {annotated}"));
+    }
 }
 
-fn draw_bounding_boxes(img: &mut Mat, bounding_boxes: &[BoundingBox]) -> opencv::Result<()> {
-    for bounding_box in bounding_boxes {
-        let rect = Rect::new(
-            bounding_box.x1,
-            bounding_box.y1,
-            bounding_box.x2 - bounding_box.x1,
-            bounding_box.y2 - bounding_box.y1,
-        );
+fn state_target(output: &cli::OutputArgs) -> &str {
+    output.state_path().unwrap_or("stdout")
+}
 
-        rectangle(
-            img,
-            rect,
-            Scalar::new(0.0, 255.0, 0.0, 0.0),
-            2,
-            LINE_8,
-            0,
-        )?;
+fn report_state(state: &GameState, output: &cli::OutputArgs) -> Result<(), SolitaireOcrError> {
+    match output.state_path() {
+        Some(path) => info!("Game state saved to {path}"),
+        None => state::print_game_state(state)?,
     }
     Ok(())
 }
 
-fn save_image(img: &Mat, output_path: &str) -> opencv::Result<()> {
-    imwrite(output_path, img, &opencv::core::Vector::new())
-        .and_then(|success| if success {
-            Ok(())
-        } else {
-            Err(opencv::Error::new(opencv::core::StsError, "Failed to save image"))
-        })
+// batch results are always written to the output directory; --stdout additionally streams them
+fn print_batch_item(item: &BatchItem, output: &cli::OutputArgs) {
+    if let (true, Ok(state)) = (output.stdout, &item.result) {
+        if let Err(e) = state::print_game_state(state) {
+            error!("{}: {e}", item.input.display());
+        }
+    }
+}
+
+fn print_tagged_state(session: usize, state: &GameState) {
+    match serde_json::to_string(&parallel::TaggedState { session, state }) {
+        Ok(json) => println!("{json}"),
+        Err(e) => error!("session {session}: {e}"),
+    }
+}
+
+fn log_batch_item(item: &BatchItem) {
+    match &item.result {
+        Ok(_) => info!("{} -> {}", item.input.display(), item.state_path.display()),
+        Err(e) => error!("{}: {e}", item.input.display()),
+    }
 }
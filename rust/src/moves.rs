@@ -0,0 +1,248 @@
+use crate::capture::{BrowserSession, CaptureConfig, PageRect};
+use crate::config::Config;
+use crate::error::{Result, SolitaireOcrError};
+use crate::live::{CapturedFrame, LiveSession};
+use crate::state::GameState;
+use crate::vision::diff::Differ;
+use crate::vision::DynDetector;
+use opencv::prelude::*;
+use solitaire_vision::BoundingBox;
+use std::sync::Arc;
+use std::{fmt, time::Duration};
+use tracing::{info, warn};
+
+/// Plays moves by clicking cards, relying on the game's click-to-move: a
+/// clicked card goes to the best place it can legally go. Moves it won't
+/// make on a click, like moving a run between tableau columns, are dragged.
+///
+/// Positions are taken in pixels of a captured frame and mapped onto the page
+/// through the canvas's current position and size.
+pub struct MoveExecutor<'a> {
+    browser: &'a BrowserSession,
+    drag_duration: Duration,
+}
+
+impl<'a> MoveExecutor<'a> {
+    pub fn new(browser: &'a BrowserSession, config: &CaptureConfig) -> Self {
+        Self {
+            browser,
+            drag_duration: Duration::from_millis(config.drag_duration_ms),
+        }
+    }
+
+    /// Clicks the middle of `card`, a box detected in `frame`.
+    pub async fn click_card(&self, card: &BoundingBox, frame: &CapturedFrame) -> Result<()> {
+        info!(card = %card.label, "playing card");
+        let (x, y) = center(card);
+        self.click_frame_point(x, y, frame).await
+    }
+
+    /// Drags `card` onto `target`, both boxes detected in `frame`. Dragging
+    /// a card in a tableau column takes the cards stacked on it along.
+    pub async fn drag_card(&self, card: &BoundingBox, target: &BoundingBox, frame: &CapturedFrame) -> Result<()> {
+        info!(card = %card.label, target = %target.label, "dragging card");
+        let canvas = self.browser.canvas_rect().await?;
+        let (width, height) = (frame.frame.cols(), frame.frame.rows());
+        let ((card_x, card_y), (target_x, target_y)) = (center(card), center(target));
+        let from = to_page(card_x, card_y, width, height, canvas);
+        let to = to_page(target_x, target_y, width, height, canvas);
+        Ok(self.browser.drag(from, to, self.drag_duration).await?)
+    }
+
+    /// Clicks the point `x`, `y` in pixels of `frame`.
+    pub async fn click_frame_point(&self, x: f64, y: f64, frame: &CapturedFrame) -> Result<()> {
+        let canvas = self.browser.canvas_rect().await?;
+        let (page_x, page_y) = to_page(x, y, frame.frame.cols(), frame.frame.rows(), canvas);
+        Ok(self.browser.click(page_x, page_y).await?)
+    }
+}
+
+/// A move as [`ObservedMoves`] played it, by the labels of the cards involved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Move {
+    Click { card: String },
+    Drag { card: String, target: String },
+    ClickOnto { card: String, target: String },
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Move::Click { card } => write!(f, "click {card}"),
+            Move::Drag { card, target } => write!(f, "drag {card} onto {target}"),
+            Move::ClickOnto { card, target } => write!(f, "click {card} then {target}"),
+        }
+    }
+}
+
+/// The board right after a move, once its animation has finished.
+#[derive(Debug, Clone)]
+pub struct PlayedMove {
+    /// Counts up from 0 over the moves played through one [`ObservedMoves`].
+    pub index: u64,
+    pub played: Move,
+    pub frame: CapturedFrame,
+    /// `None` if detection failed on the new frame.
+    pub state: Option<GameState>,
+    /// Whether the detected state differs from the one before the move. A
+    /// move that changed nothing was most likely ignored by the game.
+    pub changed: bool,
+}
+
+/// Called with every move [`ObservedMoves`] plays, e.g. to log it or pass it
+/// on through a channel.
+pub type MoveObserver = Arc<dyn Fn(&PlayedMove) + Send + Sync>;
+
+/// Plays moves like [`MoveExecutor`], then captures and re-detects the board
+/// after each one, so callers can check the move had the intended effect.
+///
+/// Moves are given as boxes detected in [`frame`](Self::frame), the latest capture.
+pub struct ObservedMoves<'a> {
+    session: &'a mut LiveSession,
+    config: &'a Config,
+    detector: DynDetector,
+    differ: Differ,
+    observer: Option<MoveObserver>,
+    frame: CapturedFrame,
+    state: Option<GameState>,
+    moves: u64,
+}
+
+impl<'a> ObservedMoves<'a> {
+    /// Captures and detects the board as it is before the first move.
+    pub async fn start(session: &'a mut LiveSession, config: &'a Config) -> Result<Self> {
+        let detector = config.detector()?;
+        let mut differ = Differ::new(&config.detection_config());
+        let frame = session.capture_frame().await?;
+        let state = detect_state(&detector, &mut differ, &frame, config);
+        Ok(Self {
+            session,
+            config,
+            detector,
+            differ,
+            observer: None,
+            frame,
+            state,
+            moves: 0,
+        })
+    }
+
+    pub fn with_observer(mut self, observer: MoveObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// The latest capture, which move positions refer to.
+    pub fn frame(&self) -> &CapturedFrame {
+        &self.frame
+    }
+
+    /// The state detected in [`frame`](Self::frame), if detection succeeded.
+    pub fn state(&self) -> Option<&GameState> {
+        self.state.as_ref()
+    }
+
+    /// Clicks `card` and captures the board once it has settled.
+    pub async fn click_card(&mut self, card: &BoundingBox) -> Result<PlayedMove> {
+        self.executor().click_card(card, &self.frame).await?;
+        self.observe(Move::Click {
+            card: card.label.clone(),
+        })
+        .await
+    }
+
+    /// Drags `card` onto `target` and captures the board once it has settled.
+    pub async fn drag_card(&mut self, card: &BoundingBox, target: &BoundingBox) -> Result<PlayedMove> {
+        self.executor().drag_card(card, target, &self.frame).await?;
+        self.observe(Move::Drag {
+            card: card.label.clone(),
+            target: target.label.clone(),
+        })
+        .await
+    }
+
+    /// Clicks `card` to select it, then `target` to move it there. In
+    /// between, the board is captured to check the game shows `card` as
+    /// selected, which needs `detection.selection` on; if it doesn't, the
+    /// first click was lost and the second isn't made, as it would select
+    /// `target` instead.
+    pub async fn click_onto(&mut self, card: &BoundingBox, target: &BoundingBox) -> Result<PlayedMove> {
+        self.executor().click_card(card, &self.frame).await?;
+        self.session.browser().wait_until_settled(&self.config.capture.settle).await?;
+        let frame = self.session.capture_frame().await?;
+        let selected = detect_state(&self.detector, &mut self.differ, &frame, self.config).and_then(|state| state.selected);
+        if !selected.as_deref().is_some_and(|selected| is_card(selected, &card.label)) {
+            return Err(SolitaireOcrError::NotSelected {
+                card: card.label.clone(),
+                selected,
+            });
+        }
+
+        // selecting doesn't move anything, so `target` is still where it was detected
+        self.executor().click_card(target, &self.frame).await?;
+        self.observe(Move::ClickOnto {
+            card: card.label.clone(),
+            target: target.label.clone(),
+        })
+        .await
+    }
+
+    fn executor(&self) -> MoveExecutor<'_> {
+        MoveExecutor::new(self.session.browser(), &self.config.capture)
+    }
+
+    async fn observe(&mut self, played: Move) -> Result<PlayedMove> {
+        self.session.browser().wait_until_settled(&self.config.capture.settle).await?;
+        let frame = self.session.capture_frame().await?;
+        let state = detect_state(&self.detector, &mut self.differ, &frame, self.config);
+        let changed = match (&state, &self.state) {
+            (Some(state), Some(previous)) => !state.same_cards(previous),
+            (state, previous) => state.is_some() != previous.is_some(),
+        };
+        if !changed {
+            warn!(%played, "board unchanged after move");
+        }
+
+        let played = PlayedMove {
+            index: self.moves,
+            played,
+            frame: frame.clone(),
+            state: state.clone(),
+            changed,
+        };
+        self.moves += 1;
+        self.frame = frame;
+        self.state = state;
+        if let Some(observer) = &self.observer {
+            observer(&played);
+        }
+        Ok(played)
+    }
+}
+
+fn detect_state(detector: &DynDetector, differ: &mut Differ, frame: &CapturedFrame, config: &Config) -> Option<GameState> {
+    match crate::translate_diffed(detector, differ, frame.source(), None, None, config) {
+        Ok(state) => Some(state),
+        Err(e) => {
+            warn!(index = frame.index, error = %e, "detection failed");
+            None
+        }
+    }
+}
+
+// whether the state's `label` is the card boxed as `box_label`, which is
+// just its rank for a box straight from detection
+fn is_card(label: &str, box_label: &str) -> bool {
+    label == box_label || label.split_once(' ').is_some_and(|(rank, _)| rank == box_label)
+}
+
+fn center(b: &BoundingBox) -> (f64, f64) {
+    ((b.x1 + b.x2) as f64 / 2.0, (b.y1 + b.y2) as f64 / 2.0)
+}
+
+// a frame covers exactly the canvas, but in device pixels rather than CSS pixels
+fn to_page(x: f64, y: f64, frame_width: i32, frame_height: i32, canvas: PageRect) -> (f64, f64) {
+    let scale_x = canvas.width / f64::from(frame_width.max(1));
+    let scale_y = canvas.height / f64::from(frame_height.max(1));
+    (canvas.x + x * scale_x, canvas.y + y * scale_y)
+}
@@ -0,0 +1,66 @@
+use crate::error::Result;
+use crate::state::GameState;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use tracing::warn;
+
+/// Where a live capture's game state comes from. Reading it from the page
+/// needs `capture.state_script`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateSource {
+    /// Detected in the screenshot.
+    #[default]
+    Ocr,
+    /// Read from the page, falling back to detection if the script returns nothing.
+    Page,
+    /// Detected, with every pile that disagrees with the page logged.
+    Compare,
+}
+
+impl FromStr for StateSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "ocr" => Ok(StateSource::Ocr),
+            "page" => Ok(StateSource::Page),
+            "compare" => Ok(StateSource::Compare),
+            _ => Err(format!("unknown state source {s:?}, expected ocr, page or compare")),
+        }
+    }
+}
+
+impl fmt::Display for StateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StateSource::Ocr => "ocr",
+            StateSource::Page => "page",
+            StateSource::Compare => "compare",
+        })
+    }
+}
+
+/// Picks the state to report from the `detected` one and the one the `page` reported.
+pub fn reconcile(source: StateSource, detected: Result<GameState>, page: Option<GameState>) -> Result<GameState> {
+    match (source, page) {
+        (StateSource::Ocr, _) => detected,
+        (StateSource::Page, Some(page)) => Ok(page),
+        (StateSource::Page, None) => {
+            warn!("page didn't report its state, using the detected one");
+            detected
+        }
+        (StateSource::Compare, Some(page)) => {
+            if let Ok(state) = &detected {
+                for mismatch in page.mismatches(state) {
+                    warn!("detected state differs from the page: {mismatch}");
+                }
+            }
+            detected
+        }
+        (StateSource::Compare, None) => {
+            warn!("page didn't report its state, nothing to compare with");
+            detected
+        }
+    }
+}
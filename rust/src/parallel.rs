@@ -0,0 +1,130 @@
+use crate::capture;
+use crate::config::Config;
+use crate::error::Result;
+use crate::live::LiveSession;
+use crate::pipeline::MemorySource;
+use crate::state::GameState;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::task::{self, JoinSet};
+use tracing::{info, warn, Instrument};
+
+#[derive(Debug, Clone)]
+pub struct ParallelOptions {
+    /// Number of browser sessions to run at once.
+    pub sessions: usize,
+    /// If set, each session writes its screenshot to this path with its ID
+    /// added, see [`session_path`].
+    pub screenshot_path: Option<String>,
+    pub annotated_path: Option<String>,
+    pub state_path: Option<String>,
+}
+
+/// Outcome of capturing and detecting one game of a parallel run.
+#[derive(Debug)]
+pub struct SessionOutcome {
+    /// Counts up from 0 in the order sessions were started.
+    pub session: usize,
+    pub screenshot_path: Option<String>,
+    pub state_path: Option<String>,
+    pub result: Result<GameState>,
+}
+
+/// A game state labelled with the session it came from, for streaming
+/// results of several sessions through one output.
+#[derive(Debug, Serialize)]
+pub struct TaggedState<'a> {
+    pub session: usize,
+    #[serde(flatten)]
+    pub state: &'a GameState,
+}
+
+/// `path` with `-<session>` inserted before its extension, e.g.
+/// `screenshot-2.png`, so sessions don't overwrite each other's files.
+pub fn session_path(path: &str, session: impl fmt::Display) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{session}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{session}"),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Starts `options.sessions` browsers at once, each with a new game, and
+/// captures and detects each one independently.
+///
+/// A session that fails is recorded in its [`SessionOutcome`] and doesn't
+/// stop the others. Outcomes are returned in session order.
+pub async fn run(config: &Config, options: &ParallelOptions) -> Result<Vec<SessionOutcome>> {
+    let mut config = config.clone();
+    let local_port = config.capture.webdriver_port.is_some() || config.capture.webdriver_url.is_some();
+    if options.sessions > 1 && config.capture.start_driver && local_port {
+        // every session starts its own driver, so they can't share one port
+        warn!("ignoring the configured WebDriver port, each session gets a free one");
+        config.capture.webdriver_port = None;
+        config.capture.webdriver_url = None;
+    }
+    let config = Arc::new(config);
+
+    let mut tasks = JoinSet::new();
+    for session in 0..options.sessions {
+        // one each, so sessions detect side by side without sharing any state;
+        // the templates themselves come from the cache
+        let detector = config.detector()?;
+        let config = Arc::clone(&config);
+        let screenshot_path = options.screenshot_path.as_deref().map(|path| session_path(path, session));
+        let annotated_path = options.annotated_path.as_deref().map(|path| session_path(path, session));
+        let state_path = options.state_path.as_deref().map(|path| session_path(path, session));
+
+        let span = tracing::info_span!("session", id = session);
+        tasks.spawn(
+            async move {
+                let result = async {
+                    let mut live = LiveSession::start(&config.capture).await?;
+                    let captured = live.capture_frame().await;
+                    live.close().await?;
+                    let captured = captured?;
+                    if let Some(path) = &screenshot_path {
+                        capture::write_screenshot(&captured.png, path)?;
+                    }
+
+                    // detection is CPU bound, keep it off the threads driving the browsers
+                    let state_path = state_path.clone();
+                    task::spawn_blocking(move || {
+                        crate::translate_source(
+                            &detector,
+                            MemorySource::new(captured.frame),
+                            annotated_path.as_deref(),
+                            state_path.as_deref(),
+                            &config,
+                        )
+                    })
+                    .await
+                    .expect("detection task panicked")
+                }
+                .await;
+
+                SessionOutcome {
+                    session,
+                    screenshot_path,
+                    state_path,
+                    result,
+                }
+            }
+            .instrument(span),
+        );
+    }
+
+    let mut outcomes = Vec::with_capacity(options.sessions);
+    while let Some(outcome) = tasks.join_next().await {
+        outcomes.push(outcome.expect("session task panicked"));
+    }
+    outcomes.sort_by_key(|outcome| outcome.session);
+
+    let failed = outcomes.iter().filter(|outcome| outcome.result.is_err()).count();
+    info!("Detected {} of {} sessions", outcomes.len() - failed, outcomes.len());
+    Ok(outcomes)
+}
@@ -0,0 +1,63 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use solitaire_ocr::batch::{BatchItem, Progress};
+use std::io::IsTerminal;
+use std::path::Path;
+
+const IMAGES_TEMPLATE: &str = "{prefix:>9} [{bar:40}] {pos}/{len} {elapsed_precise} (eta {eta}) {msg}";
+const TEMPLATES_TEMPLATE: &str = "{prefix:>9} [{bar:40}] {pos}/{len} {msg}";
+
+/// Batch progress drawn on stderr: one bar for images, one for the templates
+/// of the image in flight. Hidden when stderr isn't a terminal or output is quiet.
+pub struct ProgressBars {
+    images: ProgressBar,
+    templates: ProgressBar,
+}
+
+impl ProgressBars {
+    pub fn new(quiet: bool) -> Self {
+        let target = if quiet || !std::io::stderr().is_terminal() {
+            ProgressDrawTarget::hidden()
+        } else {
+            ProgressDrawTarget::stderr()
+        };
+        let multi = MultiProgress::with_draw_target(target);
+
+        let images = multi.add(ProgressBar::new(0).with_style(style(IMAGES_TEMPLATE)).with_prefix("images"));
+        let templates = multi.add(ProgressBar::new(0).with_style(style(TEMPLATES_TEMPLATE)).with_prefix("templates"));
+
+        Self { images, templates }
+    }
+}
+
+impl Progress for ProgressBars {
+    fn begin(&self, images: usize, templates: usize) {
+        self.images.set_length(images as u64);
+        self.templates.set_length(templates as u64);
+    }
+
+    fn image_started(&self, input: &Path) {
+        let name = input.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        self.images.set_message(name);
+        self.templates.reset();
+    }
+
+    fn template_matched(&self, label: &str) {
+        self.templates.set_message(label.to_string());
+        self.templates.inc(1);
+    }
+
+    fn image_finished(&self, _item: &BatchItem) {
+        self.images.inc(1);
+    }
+
+    fn finish(&self) {
+        self.templates.finish_and_clear();
+        self.images.finish_and_clear();
+    }
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template)
+        .expect("progress template is valid")
+        .progress_chars("=> ")
+}
@@ -0,0 +1,98 @@
+use crate::error::{Result, SolitaireOcrError};
+use crate::live::CapturedFrame;
+use crate::vision::VisionError;
+use opencv::core::{Mat, Size};
+use opencv::imgproc::{resize, INTER_AREA};
+use opencv::prelude::*;
+use opencv::videoio::VideoWriter;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// Writes captured frames into a video, one frame per capture, for replaying
+/// what a session saw afterwards.
+///
+/// The container follows the file extension: `.mp4`, `.webm` or `.avi`. The
+/// video is sized to the first frame and later frames are scaled to fit.
+pub struct Recorder {
+    path: PathBuf,
+    fourcc: i32,
+    fps: f64,
+    // opened on the first frame, once its size is known
+    writer: Option<(VideoWriter, Size)>,
+    frames: u64,
+}
+
+impl Recorder {
+    /// Prepares a recording to `path` played back at `fps` frames per second.
+    pub fn new(path: impl Into<PathBuf>, fps: f64) -> Result<Self> {
+        let path = path.into();
+        let codec = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("mp4") => ['m', 'p', '4', 'v'],
+            Some("webm") => ['V', 'P', '8', '0'],
+            Some("avi") => ['M', 'J', 'P', 'G'],
+            _ => {
+                return Err(SolitaireOcrError::Recording {
+                    path,
+                    reason: "unsupported format, expected .mp4, .webm or .avi".to_string(),
+                })
+            }
+        };
+        let [c1, c2, c3, c4] = codec;
+        let fourcc = VideoWriter::fourcc(c1, c2, c3, c4).map_err(VisionError::from)?;
+        Ok(Self {
+            path,
+            fourcc,
+            fps,
+            writer: None,
+            frames: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `captured` to the video.
+    pub fn write(&mut self, captured: &CapturedFrame) -> Result<()> {
+        let image = &captured.frame;
+        let size = image.size().map_err(VisionError::from)?;
+
+        let (writer, video_size) = match &mut self.writer {
+            Some((writer, video_size)) => (writer, *video_size),
+            None => {
+                let path = self.path.to_string_lossy();
+                let writer = VideoWriter::new(&path, self.fourcc, self.fps, size, true).map_err(VisionError::from)?;
+                if !writer.is_opened().map_err(VisionError::from)? {
+                    return Err(SolitaireOcrError::Recording {
+                        path: self.path.clone(),
+                        reason: "no encoder available for this format".to_string(),
+                    });
+                }
+                info!(path = %path, "recording session");
+                let (writer, _) = self.writer.insert((writer, size));
+                (writer, size)
+            }
+        };
+
+        if size == video_size {
+            writer.write(image).map_err(VisionError::from)?;
+        } else {
+            let mut resized = Mat::default();
+            resize(image, &mut resized, video_size, 0.0, 0.0, INTER_AREA).map_err(VisionError::from)?;
+            writer.write(&resized).map_err(VisionError::from)?;
+        }
+        self.frames += 1;
+        debug!(index = captured.index, "recorded frame");
+        Ok(())
+    }
+
+    /// Flushes the video to disk. Dropping the recorder does the same, but
+    /// without reporting errors.
+    pub fn finish(mut self) -> Result<()> {
+        if let Some((mut writer, _)) = self.writer.take() {
+            writer.release().map_err(VisionError::from)?;
+            info!(path = %self.path.display(), frames = self.frames, "recording saved");
+        }
+        Ok(())
+    }
+}
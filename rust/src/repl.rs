@@ -0,0 +1,223 @@
+use opencv::core::Mat;
+use solitaire_ocr::pipeline::{self, MemorySource, Pipeline, TemplateDetector};
+use solitaire_ocr::{detect, state, Config, GameState, LiveSession, Result};
+use std::io::Write;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+const HELP: &str = "\
+commands:
+  capture                          take a screenshot of the game (starts the browser if needed)
+  load <path>                      use a screenshot from disk instead
+  detect                           run detection on the current screenshot
+  rerun                            capture and detect in one go
+  undo                             take back the last move in the game
+  new                              deal a new game
+  show piles                       print the last detected state
+  show config                      print the current detection settings
+  set threshold card|suit <value>  change a class threshold
+  set threshold <label> <value>    override the threshold of one template
+  set nms <value>                  change the NMS overlap threshold
+  save [path]                      write the last state as JSON (default output.json)
+  help                             show this message
+  quit                             close the browser and exit";
+
+enum ReplCommand {
+    Capture,
+    Load(String),
+    Detect,
+    Rerun,
+    Undo,
+    NewGame,
+    ShowPiles,
+    ShowConfig,
+    SetThreshold(String, f32),
+    SetNms(f32),
+    Save(String),
+    Help,
+    Quit,
+}
+
+fn parse(line: &str) -> std::result::Result<ReplCommand, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let parse_value = |value: &str| value.parse::<f32>().map_err(|_| format!("not a number: {value}"));
+
+    match words.as_slice() {
+        ["capture"] => Ok(ReplCommand::Capture),
+        ["load", path] => Ok(ReplCommand::Load(path.to_string())),
+        ["detect"] => Ok(ReplCommand::Detect),
+        ["rerun"] => Ok(ReplCommand::Rerun),
+        ["undo"] => Ok(ReplCommand::Undo),
+        ["new"] => Ok(ReplCommand::NewGame),
+        ["show", "piles"] => Ok(ReplCommand::ShowPiles),
+        ["show", "config"] => Ok(ReplCommand::ShowConfig),
+        ["set", "threshold", target, value] => Ok(ReplCommand::SetThreshold(target.to_string(), parse_value(value)?)),
+        ["set", "nms", value] => Ok(ReplCommand::SetNms(parse_value(value)?)),
+        ["save"] => Ok(ReplCommand::Save("output.json".to_string())),
+        ["save", path] => Ok(ReplCommand::Save(path.to_string())),
+        ["help"] => Ok(ReplCommand::Help),
+        ["quit"] | ["exit"] => Ok(ReplCommand::Quit),
+        _ => Err(format!("unknown command: {line} (try `help`)")),
+    }
+}
+
+struct Repl {
+    config: Config,
+    detector: TemplateDetector,
+    session: Option<LiveSession>,
+    frame: Option<Mat>,
+    state: Option<GameState>,
+}
+
+impl Repl {
+    async fn capture(&mut self) -> Result<()> {
+        if self.session.is_none() {
+            println!("starting browser...");
+            self.session = Some(LiveSession::start(&self.config.capture).await?);
+        }
+        let session = self.session.as_mut().expect("session was just started");
+        self.frame = Some(session.capture_frame().await?.frame);
+        println!("captured screenshot");
+        Ok(())
+    }
+
+    fn detect(&mut self) -> Result<()> {
+        let Some(frame) = &self.frame else {
+            println!("no screenshot yet, use `capture` or `load <path>` first");
+            return Ok(());
+        };
+
+        let mut pipeline = Pipeline::new(
+            MemorySource::new(frame.clone()),
+            &self.detector,
+            pipeline::layout_assembler(self.config.layout.clone()),
+        );
+        let output = pipeline.run()?;
+        println!(
+            "detected {} ranks and {} suits",
+            output.detections.cards.len(),
+            output.detections.suits.len()
+        );
+        self.state = Some(output.state);
+        self.show_piles();
+        Ok(())
+    }
+
+    fn show_piles(&self) {
+        let Some(state) = &self.state else {
+            println!("nothing detected yet, use `detect` first");
+            return;
+        };
+        println!("draw:      {:?}", state.draw_pile);
+        for (i, pile) in state.game_piles.iter().enumerate() {
+            println!("pile {}:    {:?}", i + 1, pile);
+        }
+        println!("discard:   {:?}", state.discard_pile);
+    }
+
+    fn show_config(&self) {
+        let detection = self.detector.config();
+        println!("card threshold: {}", detection.card_threshold);
+        println!("suit threshold: {}", detection.suit_threshold);
+        println!("nms overlap:    {}", detection.nms_overlap_threshold);
+        for (class, threshold) in &detection.nms.overlap_thresholds {
+            println!("{:<15} {threshold}", format!("{class:?} overlap:").to_lowercase());
+        }
+        for (label, threshold) in &detection.template_thresholds {
+            println!("{label:<15} {threshold}");
+        }
+    }
+
+    fn set_threshold(&mut self, target: &str, value: f32) {
+        let detection = self.detector.config_mut();
+        match target {
+            "card" => detection.card_threshold = value,
+            "suit" => detection.suit_threshold = value,
+            label => {
+                detection.template_thresholds.insert(label.to_string(), value);
+            }
+        }
+        self.config.detection = detection.clone();
+    }
+
+    /// Returns `false` once the user asked to quit.
+    async fn execute(&mut self, command: ReplCommand) -> Result<bool> {
+        match command {
+            ReplCommand::Capture => self.capture().await?,
+            ReplCommand::Load(path) => {
+                self.frame = Some(detect::load_color_image(&path)?);
+                println!("loaded {path}");
+            }
+            ReplCommand::Detect => self.detect()?,
+            ReplCommand::Rerun => {
+                self.capture().await?;
+                self.detect()?;
+            }
+            ReplCommand::Undo => match &self.session {
+                Some(session) => session.browser().undo().await?,
+                None => println!("no game open, use `capture` first"),
+            },
+            ReplCommand::NewGame => match &self.session {
+                Some(session) => session.browser().new_game().await?,
+                None => println!("no game open, use `capture` first"),
+            },
+            ReplCommand::ShowPiles => self.show_piles(),
+            ReplCommand::ShowConfig => self.show_config(),
+            ReplCommand::SetThreshold(target, value) => self.set_threshold(&target, value),
+            ReplCommand::SetNms(value) => {
+                self.detector.config_mut().nms_overlap_threshold = value;
+                self.config.detection.nms_overlap_threshold = value;
+            }
+            ReplCommand::Save(path) => match &self.state {
+                Some(state) => {
+                    state::save_game_state(state, &path)?;
+                    println!("saved {path}");
+                }
+                None => println!("nothing detected yet, use `detect` first"),
+            },
+            ReplCommand::Help => println!("{HELP}"),
+            ReplCommand::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Interactive loop for tuning detection against a live game.
+pub async fn run(config: Config) -> Result<()> {
+    let detector = TemplateDetector::load(&config.template_dir, config.detection_config())?;
+    let mut repl = Repl {
+        config,
+        detector,
+        session: None,
+        frame: None,
+        state: None,
+    };
+
+    println!("solitaire-ocr repl, type `help` for commands");
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        print!("> ");
+        let _ = std::io::stdout().flush();
+
+        let Ok(Some(line)) = lines.next_line().await else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse(&line) {
+            Ok(command) => match repl.execute(command).await {
+                Ok(true) => {}
+                Ok(false) => break,
+                // keep the session alive so a bad threshold or a glitchy frame doesn't lose the game
+                Err(e) => println!("error: {e}"),
+            },
+            Err(message) => println!("{message}"),
+        }
+    }
+
+    if let Some(session) = repl.session.take() {
+        session.close().await?;
+    }
+    Ok(())
+}
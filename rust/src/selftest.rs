@@ -0,0 +1,40 @@
+use crate::config::Config;
+use crate::error::Result;
+use crate::pipeline::{self, MemorySource, Pipeline};
+use crate::state::{GameState, StateError};
+
+const FIXTURE_IMAGE: &[u8] = include_bytes!("../fixtures/selftest.png");
+const FIXTURE_STATE: &str = include_str!("../fixtures/selftest.json");
+
+/// Result of running detection on the bundled fixture screenshot.
+#[derive(Debug)]
+pub struct SelfTestReport {
+    pub expected: GameState,
+    pub actual: GameState,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.expected.same_cards(&self.actual)
+    }
+
+    /// One line per pile whose detected contents differ from the fixture.
+    pub fn mismatches(&self) -> Vec<String> {
+        self.expected.mismatches(&self.actual)
+    }
+}
+
+/// Runs the configured templates against the bundled fixture screenshot and
+/// compares the result with its known game state.
+pub fn run(config: &Config) -> Result<SelfTestReport> {
+    let expected: GameState = serde_json::from_str(FIXTURE_STATE).map_err(StateError::from)?;
+
+    let mut pipeline = Pipeline::new(
+        MemorySource::decode(FIXTURE_IMAGE)?,
+        config.detector()?,
+        pipeline::layout_assembler(config.layout.clone()),
+    );
+    let actual = pipeline.run()?.state;
+
+    Ok(SelfTestReport { expected, actual })
+}
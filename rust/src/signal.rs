@@ -0,0 +1,43 @@
+use solitaire_ocr::{Result, SolitaireOcrError};
+use std::future::Future;
+use tracing::warn;
+
+/// Completes on Ctrl-C, or SIGTERM on Unix.
+///
+/// Only browser commands wait on this: installing the handler replaces the
+/// default of exiting immediately, which is still what the blocking batch
+/// and watch loops want.
+pub async fn shutdown() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            },
+            Err(e) => {
+                warn!(error = %e, "failed to listen for SIGTERM");
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Runs `future` until it completes, or drops it and fails with
+/// [`SolitaireOcrError::Interrupted`] once a shutdown signal arrives.
+///
+/// Dropping a future that holds a browser session kills its WebDriver server.
+pub async fn until_shutdown<T>(future: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::select! {
+        output = future => output,
+        () = shutdown() => {
+            warn!("interrupted, shutting down");
+            Err(SolitaireOcrError::Interrupted)
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::batch::{self, BatchItem};
+use crate::config::Config;
+use crate::error::{Result, SolitaireOcrError};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// How long a file has to go without further events before it is considered
+/// fully written. Capture tools usually create the file and then fill it.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Watches `input_dir` for new or rewritten PNGs and translates each into
+/// `output_dir` as [`batch::translate_into`] would, calling `on_item` with
+/// every result. Runs until the watcher fails.
+pub fn watch_dir(
+    input_dir: &Path,
+    output_dir: &Path,
+    annotate: bool,
+    config: &Config,
+    mut on_item: impl FnMut(&BatchItem),
+) -> Result<()> {
+    let detector = config.detector()?;
+    fs::create_dir_all(output_dir).map_err(|source| SolitaireOcrError::Io {
+        path: output_dir.into(),
+        source,
+    })?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(input_dir, RecursiveMode::NonRecursive)?;
+    info!("Watching {} for screenshots", input_dir.display());
+
+    // last event time per candidate file, and annotated images we wrote
+    // ourselves so an output dir equal to the input dir doesn't loop
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    let mut produced: HashSet<PathBuf> = HashSet::new();
+
+    loop {
+        match rx.recv_timeout(SETTLE_TIME / 2) {
+            Ok(event) => {
+                let event = event?;
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        if is_png(&path) && !produced.contains(&path) {
+                            pending.insert(path, Instant::now());
+                        }
+                    }
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, last_event)| last_event.elapsed() >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for path in settled {
+            pending.remove(&path);
+            if !path.exists() {
+                debug!(path = %path.display(), "file disappeared before processing");
+                continue;
+            }
+
+            let item = batch::translate_into(&detector, path, output_dir, annotate, config);
+            if let Some(annotated) = &item.annotated_path {
+                produced.insert(annotated.clone());
+            }
+            on_item(&item);
+        }
+    }
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+}